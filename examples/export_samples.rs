@@ -0,0 +1,27 @@
+//! Export the samples of SoundFont to WAV files
+//!
+//! Usage: `cargo run --example export_samples -- <font.sf2> [<output directory>]`
+//!
+//! The SF3 fonts require the decoder of compressed samples, so run it with
+//! `--features with-lewton` (or `--features with-sf3,with-stb`) for them.
+
+use fluidlite::{sf2::SoundFont, FileReader, FsFileApi};
+use std::env;
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let font_path = args.next().expect("SoundFont path expected");
+    let out_dir = args.next().unwrap_or_else(|| "samples".into());
+
+    let mut reader = FileReader::open(&mut FsFileApi, &font_path).expect("Unable to open font");
+    let font = SoundFont::read(&mut reader).expect("Unable to parse font");
+
+    println!("{}: {} samples", font.info.name, font.samples.len());
+
+    for path in font
+        .export_wav(&mut reader, &out_dir)
+        .expect("Unable to export")
+    {
+        println!("  {}", path.display());
+    }
+}
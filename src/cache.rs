@@ -0,0 +1,258 @@
+use crate::{loader::read_file, FileApi, FontId, FsFileApi, Result, Synth};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, Weak},
+};
+
+/// The function which reads the whole file
+type ReadFn = Box<dyn FnMut(&Path) -> Result<Vec<u8>> + Send>;
+
+/**
+The cache of SoundFont files shared by synths
+
+The font file is read once and attached to any number of synths
+(see [`SharedFont::attach`]). Each synth gets its own font ID,
+so the bank offsets are set per synth as usual.
+The data is freed when the last handle is dropped,
+the attached fonts don't keep it.
+
+# Limitations
+
+The library can't share a loaded font between synths, because it modifies
+the font while playing (like the reference counts of samples). So each synth
+still parses the data and keeps its own copy of font, and the cache only
+avoids reading the file again. Keep the handle while attaching the font
+to synths, otherwise the file is read again.
+
+```no_run
+use fluidlite::{FontCache, Settings, Synth};
+
+let cache = FontCache::new();
+let synths = (0..4)
+    .map(|_| Synth::new(Settings::new().unwrap()).unwrap())
+    .collect::<Vec<_>>();
+
+// the file is read only once
+let font = cache.load("sf_/Boomwhacker.sf2").unwrap();
+let attached = synths
+    .iter()
+    .map(|synth| font.attach(synth))
+    .collect::<Result<Vec<_>, _>>()
+    .unwrap();
+drop(font);
+
+synths[1].set_bank_offset(attached[1].id(), 1).unwrap();
+```
+ */
+pub struct FontCache {
+    read: Mutex<ReadFn>,
+    fonts: Mutex<HashMap<PathBuf, Weak<FontData>>>,
+}
+
+impl Default for FontCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FontCache {
+    /// Create the cache which reads fonts from file system
+    pub fn new() -> Self {
+        Self::with_file_api(FsFileApi)
+    }
+
+    /// Create the cache which reads fonts using file API
+    pub fn with_file_api<F: FileApi + Send + 'static>(mut fileapi: F) -> Self {
+        Self {
+            read: Mutex::new(Box::new(move |path| read_file(&mut fileapi, path))),
+            fonts: Default::default(),
+        }
+    }
+
+    /**
+    Get the cached font or read it
+
+    The data is parsed by synths, so the invalid fonts fail to attach.
+     */
+    pub fn load<P: AsRef<Path>>(&self, filename: P) -> Result<SharedFont> {
+        let path = filename.as_ref();
+        let mut fonts = lock(&self.fonts);
+        fonts.retain(|_, font| font.strong_count() > 0);
+
+        if let Some(font) = fonts.get(path).and_then(Weak::upgrade) {
+            return Ok(SharedFont(font));
+        }
+
+        let data = (lock(&self.read))(path)?;
+        let font = Arc::new(FontData {
+            path: path.into(),
+            data,
+        });
+        fonts.insert(path.into(), Arc::downgrade(&font));
+        Ok(SharedFont(font))
+    }
+
+    /// Get the number of fonts which are still in use
+    pub fn len(&self) -> usize {
+        lock(&self.fonts)
+            .values()
+            .filter(|font| font.strong_count() > 0)
+            .count()
+    }
+
+    /// Check that no fonts are in use
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // the poisoned state is consistent, because the guarded calls don't leave it changed partially
+    mutex.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+/// The font data read by cache
+struct FontData {
+    path: PathBuf,
+    data: Vec<u8>,
+}
+
+/// The font data which synth reads while loading font
+struct FontBytes(Arc<FontData>);
+
+impl AsRef<[u8]> for FontBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0.data
+    }
+}
+
+/**
+The SoundFont read by [`FontCache`]
+ */
+#[derive(Clone)]
+pub struct SharedFont(Arc<FontData>);
+
+impl SharedFont {
+    /**
+    Attach the font to synth
+
+    The font is loaded from the shared data and put on top
+    of the SoundFont stack of synth (see [`Synth::sfload_bytes`]).
+    The font is unloaded from synth when the result is dropped.
+
+    The synth doesn't keep the data, so the font is reloaded from the file
+    (see [`Synth::sfreload`]).
+     */
+    pub fn attach<'a>(&self, synth: &'a Synth) -> Result<AttachedFont<'a>> {
+        let id = synth.sfload_data_once(&self.0.path, FontBytes(self.0.clone()), true)?;
+        Ok(AttachedFont { synth, id })
+    }
+
+    /// Get the number of handles of font
+    pub fn users(&self) -> usize {
+        Arc::strong_count(&self.0)
+    }
+}
+
+/**
+The font which the synth uses
+
+The font ID is assigned by synth, so the reference to font and the
+bank offset are got from synth by ID like for fonts loaded by synth.
+ */
+pub struct AttachedFont<'a> {
+    synth: &'a Synth,
+    id: FontId,
+}
+
+impl<'a> AttachedFont<'a> {
+    /// Get the font ID in synth
+    pub fn id(&self) -> FontId {
+        self.id
+    }
+}
+
+impl<'a> Drop for AttachedFont<'a> {
+    fn drop(&mut self) {
+        // the font may be already unloaded by user
+        let _ = self.synth.sfunload(self.id, true);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FontCache, IsFont, Settings, Synth};
+
+    #[test]
+    fn shared_font() {
+        let cache = FontCache::new();
+        let synth1 = Synth::new(Settings::new().unwrap()).unwrap();
+        let synth2 = Synth::new(Settings::new().unwrap()).unwrap();
+        synth2.sfload("sf_/Boomwhacker.sf3", true).unwrap();
+
+        let font = cache.load("sf_/Boomwhacker.sf2").unwrap();
+        let attached1 = font.attach(&synth1).unwrap();
+        let attached2 = cache
+            .load("sf_/Boomwhacker.sf2")
+            .unwrap()
+            .attach(&synth2)
+            .unwrap();
+
+        assert_eq!(cache.len(), 1);
+        // the attached fonts don't keep the data
+        assert_eq!(font.users(), 1);
+        assert_ne!(attached1.id(), attached2.id());
+
+        synth2.set_bank_offset(attached2.id(), 1).unwrap();
+        assert_eq!(synth1.get_bank_offset(attached1.id()).unwrap(), 0);
+        assert_eq!(synth2.get_bank_offset(attached2.id()).unwrap(), 1);
+
+        let font2 = synth2.get_sfont_by_id(attached2.id()).unwrap();
+        assert_eq!(font2.get_name().unwrap(), "sf_/Boomwhacker.sf2");
+        assert_eq!(font2.preset_iter().count(), 1);
+
+        synth1.program_select(0, attached1.id(), 0, 0).unwrap();
+        synth2.program_select(0, attached2.id(), 1, 0).unwrap();
+        synth1.note_on(0, 60, 100).unwrap();
+        synth2.note_on(0, 60, 100).unwrap();
+        let mut samples = [0f32; 512];
+        synth1.write(samples.as_mut()).unwrap();
+        assert!(samples.iter().any(|sample| *sample != 0.0));
+
+        drop(font);
+        assert!(cache.is_empty());
+        synth1.write(samples.as_mut()).unwrap();
+        assert!(samples.iter().any(|sample| *sample != 0.0));
+
+        drop(attached1);
+        drop(attached2);
+        assert_eq!(synth1.sfcount(), 0);
+        assert_eq!(synth2.sfcount(), 1);
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let cache = std::sync::Arc::new(FontCache::new());
+        let threads = (0..2)
+            .map(|_| {
+                let cache = cache.clone();
+                std::thread::spawn(move || {
+                    let synth = Synth::new(Settings::new().unwrap()).unwrap();
+                    let font = cache.load("sf_/Boomwhacker.sf2").unwrap();
+                    let attached = font.attach(&synth).unwrap();
+                    synth.program_select(0, attached.id(), 0, 0).unwrap();
+                    synth.note_on(0, 60, 100).unwrap();
+                    let mut samples = [0f32; 512];
+                    synth.write(samples.as_mut()).unwrap();
+                    samples.iter().any(|sample| *sample != 0.0)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for thread in threads {
+            assert!(thread.join().unwrap());
+        }
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,661 @@
+/*!
+DLS Level 1 and 2 collections
+
+The parser of Downloadable Sounds files (`.dls`) which converts
+the instruments into SoundFont presets, so the collection can be
+played by synth like any other font.
+
+The instruments (`lins`, `ins `), regions (`rgn `, `rgn2`), articulation
+(`art1`, `art2`) and wave pool (`wvpl`, `ptbl`) are parsed.
+The articulation connections are converted to generators when their
+sources are fixed (like envelopes and LFO parameters) and to modulators
+when their sources are MIDI controllers. The connections which match
+the default modulators of SoundFont are skipped.
+
+```no_run
+use fluidlite::{dls::Dls, FsFileApi, Settings, Synth};
+
+let dls = Dls::load(&mut FsFileApi, "gm.dls").unwrap();
+for instrument in &dls.instruments {
+    println!("{:03}:{:03} {}", instrument.bank(), instrument.prog, instrument.name);
+}
+
+let synth = Synth::new(Settings::new().unwrap()).unwrap();
+let id = synth.sfload_dls(&mut FsFileApi, "gm.dls", true).unwrap();
+synth.program_select(0, id, 0, 0).unwrap();
+```
+ */
+
+mod build;
+
+use crate::{loader::read_file, sf2::Range, sf2::SamplePcm, Error, FileApi, Result};
+use std::{path::Path, slice::ChunksExact};
+
+/// The flag of drum instruments in bank number
+const DRUMS_FLAG: u32 = 0x8000_0000;
+
+/**
+The articulation connection block
+
+The `scale` is 16.16 fixed point value in units of destination.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Connection {
+    pub source: u16,
+    pub control: u16,
+    pub destination: u16,
+    pub transform: u16,
+    pub scale: i32,
+}
+
+/**
+The sample loop
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WaveLoop {
+    /// The `0` for forward loop and `1` for loop until release
+    pub loop_type: u32,
+    pub start: u32,
+    pub length: u32,
+}
+
+/**
+The playback parameters of wave (`wsmp`)
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct WaveSample {
+    pub unity_note: u16,
+    /// The tuning in cents
+    pub fine_tune: i16,
+    /// The gain in 1/655360 dB
+    pub attenuation: i32,
+    pub options: u32,
+    pub loops: Vec<WaveLoop>,
+}
+
+/**
+The region of instrument
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Region {
+    pub keys: Range,
+    pub vels: Range,
+    pub options: u16,
+    /// The key group which notes are exclusive (`0` means none)
+    pub key_group: u16,
+    /// The parameters which override parameters of wave
+    pub wave_sample: Option<WaveSample>,
+    /// The index of wave in pool
+    pub wave: u32,
+    pub channel: u32,
+    pub connections: Vec<Connection>,
+}
+
+/**
+The instrument
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Instrument {
+    pub name: String,
+    /// The bank with MSB in bits 8..15, LSB in bits 0..7 and drums flag
+    pub bank: u32,
+    pub prog: u32,
+    pub regions: Vec<Region>,
+    /// The connections which apply to all regions
+    pub connections: Vec<Connection>,
+}
+
+impl Instrument {
+    /// Check that instrument is drum kit
+    pub fn is_drums(&self) -> bool {
+        self.bank & DRUMS_FLAG != 0
+    }
+
+    /**
+    Get the SoundFont bank (`128` for drums)
+
+    The bank select MSB and LSB are combined into 14-bit bank
+    like synth does (`MSB * 128 + LSB`), so the bank is selected
+    by sending both controls. Note that the melodic instruments
+    with MSB `1` and LSB `0` share the bank `128` with drums.
+     */
+    pub fn bank(&self) -> u16 {
+        if self.is_drums() {
+            128
+        } else {
+            ((self.bank >> 8 & 0x7F) << 7 | self.bank & 0x7F) as u16
+        }
+    }
+}
+
+/**
+The wave from pool
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Wave {
+    pub name: String,
+    pub pcm: SamplePcm,
+    pub wave_sample: Option<WaveSample>,
+}
+
+/**
+The parsed DLS collection
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Dls {
+    pub name: String,
+    pub instruments: Vec<Instrument>,
+    /// The waves in the order of pool table
+    pub waves: Vec<Wave>,
+}
+
+fn invalid(reason: &'static str) -> Error {
+    Error::Format { reason }
+}
+
+fn u16_at(data: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([data[pos], data[pos + 1]])
+}
+
+fn u32_at(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
+
+/// The chunk identifier and data
+pub(crate) type Chunk<'a> = ([u8; 4], &'a [u8]);
+
+/// Split data to chunks
+pub(crate) fn chunks(mut data: &[u8]) -> Result<Vec<Chunk<'_>>> {
+    let mut chunks = Vec::new();
+    while data.len() >= 8 {
+        let id = [data[0], data[1], data[2], data[3]];
+        let size = u32_at(data, 4) as usize;
+        if size > data.len() - 8 {
+            return Err(invalid("Chunk exceeds its parent"));
+        }
+        chunks.push((id, &data[8..8 + size]));
+        // the chunks are padded to even size
+        data = &data[(8 + size + (size & 1)).min(data.len())..];
+    }
+    Ok(chunks)
+}
+
+/**
+Get the chunks of RIFF file with form type
+
+The declared size is clamped to data, so the truncated files are read as is.
+ */
+pub(crate) fn riff_chunks<'a>(
+    data: &'a [u8],
+    form: &[u8; 4],
+    reason: &'static str,
+) -> Result<Vec<Chunk<'a>>> {
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != form {
+        return Err(invalid(reason));
+    }
+    let size = (u32_at(data, 4) as usize).min(data.len() - 8);
+    if size < 4 {
+        return Err(invalid("Truncated RIFF header"));
+    }
+    chunks(&data[12..8 + size])
+}
+
+/// Split list chunk to type and sub-chunks
+fn list(data: &[u8]) -> Result<([u8; 4], Vec<Chunk<'_>>)> {
+    if data.len() < 4 {
+        return Err(invalid("Invalid list chunk"));
+    }
+    Ok(([data[0], data[1], data[2], data[3]], chunks(&data[4..])?))
+}
+
+/// Get the name (`INAM`) from `INFO` list
+fn info_name(chunks: &[Chunk]) -> Result<Option<String>> {
+    for (id, data) in chunks {
+        if id == b"LIST" {
+            let (kind, info) = list(data)?;
+            if &kind == b"INFO" {
+                if let Some((_, name)) = info.iter().find(|(id, _)| id == b"INAM") {
+                    let len = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+                    return Ok(Some(
+                        String::from_utf8_lossy(&name[..len]).trim_end().into(),
+                    ));
+                }
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Get the table of records
+fn records(data: &[u8], offset: usize, count: usize, size: usize) -> Option<ChunksExact<'_, u8>> {
+    let end = count.checked_mul(size)?.checked_add(offset)?;
+    Some(data.get(offset..end)?.chunks_exact(size))
+}
+
+fn read_connections(data: &[u8], connections: &mut Vec<Connection>) -> Result<()> {
+    if data.len() < 8 {
+        return Err(invalid("Invalid articulation"));
+    }
+    let blocks = records(data, u32_at(data, 0) as usize, u32_at(data, 4) as usize, 12)
+        .ok_or_else(|| invalid("Invalid articulation"))?;
+    for block in blocks {
+        connections.push(Connection {
+            source: u16_at(block, 0),
+            control: u16_at(block, 2),
+            destination: u16_at(block, 4),
+            transform: u16_at(block, 6),
+            scale: u32_at(block, 8) as i32,
+        });
+    }
+    Ok(())
+}
+
+/// Read connections from `lart` or `lar2` lists
+fn read_articulation(chunks: &[Chunk]) -> Result<Vec<Connection>> {
+    let mut connections = Vec::new();
+    for (id, data) in chunks {
+        if id != b"LIST" {
+            continue;
+        }
+        let (kind, arts) = list(data)?;
+        if &kind == b"lart" || &kind == b"lar2" {
+            for (id, data) in arts {
+                if &id == b"art1" || &id == b"art2" {
+                    read_connections(data, &mut connections)?;
+                }
+            }
+        }
+    }
+    Ok(connections)
+}
+
+fn read_wave_sample(data: &[u8]) -> Result<WaveSample> {
+    if data.len() < 20 {
+        return Err(invalid("Invalid wave sample"));
+    }
+    let loops = records(
+        data,
+        u32_at(data, 0) as usize,
+        u32_at(data, 16) as usize,
+        16,
+    )
+    .ok_or_else(|| invalid("Invalid wave sample"))?;
+    Ok(WaveSample {
+        unity_note: u16_at(data, 4),
+        fine_tune: u16_at(data, 6) as i16,
+        attenuation: u32_at(data, 8) as i32,
+        options: u32_at(data, 12),
+        loops: loops
+            .map(|block| WaveLoop {
+                loop_type: u32_at(block, 4),
+                start: u32_at(block, 8),
+                length: u32_at(block, 12),
+            })
+            .collect(),
+    })
+}
+
+fn read_region(chunks: &[Chunk]) -> Result<Region> {
+    let mut region = Region {
+        connections: read_articulation(chunks)?,
+        ..Default::default()
+    };
+    let mut has_link = false;
+
+    for (id, data) in chunks {
+        match id {
+            b"rgnh" if data.len() >= 12 => {
+                region.keys = range(data, 0);
+                region.vels = range(data, 4);
+                region.options = u16_at(data, 8);
+                region.key_group = u16_at(data, 10);
+            }
+            b"wsmp" => region.wave_sample = Some(read_wave_sample(data)?),
+            b"wlnk" if data.len() >= 12 => {
+                region.channel = u32_at(data, 4);
+                region.wave = u32_at(data, 8);
+                has_link = true;
+            }
+            _ => (),
+        }
+    }
+
+    if !has_link {
+        return Err(invalid("Missing wave link"));
+    }
+    Ok(region)
+}
+
+fn range(data: &[u8], pos: usize) -> Range {
+    let clamp = |value: u16| value.min(127) as u8;
+    Range {
+        lo: clamp(u16_at(data, pos)),
+        hi: clamp(u16_at(data, pos + 2)),
+    }
+}
+
+fn read_instrument(chunks: &[Chunk]) -> Result<Instrument> {
+    let header = chunks
+        .iter()
+        .find(|(id, data)| id == b"insh" && data.len() >= 12)
+        .ok_or_else(|| invalid("Missing instrument header"))?
+        .1;
+    let mut instrument = Instrument {
+        name: info_name(chunks)?.unwrap_or_default(),
+        bank: u32_at(header, 4),
+        prog: u32_at(header, 8) & 0x7F,
+        connections: read_articulation(chunks)?,
+        ..Default::default()
+    };
+
+    for (id, data) in chunks {
+        if id != b"LIST" {
+            continue;
+        }
+        let (kind, regions) = list(data)?;
+        if &kind != b"lrgn" {
+            continue;
+        }
+        for (id, data) in regions {
+            if &id != b"LIST" {
+                continue;
+            }
+            let (kind, chunks) = list(data)?;
+            if &kind == b"rgn " || &kind == b"rgn2" {
+                instrument.regions.push(read_region(&chunks)?);
+            }
+        }
+    }
+
+    Ok(instrument)
+}
+
+fn read_wave(chunks: &[Chunk]) -> Result<Wave> {
+    let find = |name: &[u8; 4]| {
+        chunks
+            .iter()
+            .find(|(id, _)| id == name)
+            .map(|(_, data)| *data)
+    };
+    let fmt = find(b"fmt ").ok_or_else(|| invalid("Missing wave format"))?;
+    let data = find(b"data").ok_or_else(|| invalid("Missing wave data"))?;
+    let name = info_name(chunks)?.unwrap_or_default();
+
+    let mut pcm = SamplePcm::from_wave_chunks(fmt, data)?;
+    pcm.name = name.clone();
+    Ok(Wave {
+        name,
+        pcm,
+        wave_sample: find(b"wsmp").map(read_wave_sample).transpose()?,
+    })
+}
+
+impl Dls {
+    /**
+    Parse DLS collection from data
+     */
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let riff = riff_chunks(data, b"DLS ", "Not a DLS file")?;
+
+        let mut dls = Dls {
+            name: info_name(&riff)?.unwrap_or_default(),
+            ..Default::default()
+        };
+        let mut pool = None;
+        let mut cues = None;
+
+        for (id, data) in &riff {
+            match id {
+                b"LIST" => {
+                    let (kind, items) = list(data)?;
+                    match &kind {
+                        b"lins" => {
+                            for (id, data) in items {
+                                if &id != b"LIST" {
+                                    continue;
+                                }
+                                let (kind, chunks) = list(data)?;
+                                if &kind == b"ins " {
+                                    dls.instruments.push(read_instrument(&chunks)?);
+                                }
+                            }
+                        }
+                        // the cues are offsets from the list type
+                        b"wvpl" => pool = Some(&data[4..]),
+                        _ => (),
+                    }
+                }
+                b"ptbl" if data.len() >= 8 => {
+                    let table =
+                        records(data, u32_at(data, 0) as usize, u32_at(data, 4) as usize, 4)
+                            .ok_or_else(|| invalid("Invalid pool table"))?;
+                    cues = Some(table.map(|cue| u32_at(cue, 0) as usize).collect::<Vec<_>>());
+                }
+                _ => (),
+            }
+        }
+
+        let pool = pool.ok_or_else(|| invalid("Missing wave pool"))?;
+        let waves = match cues {
+            Some(cues) => cues
+                .into_iter()
+                .map(|cue| {
+                    let chunk = chunks(pool.get(cue..).unwrap_or(&[]))?
+                        .into_iter()
+                        .next()
+                        .ok_or_else(|| invalid("Invalid pool table"))?;
+                    Ok(chunk)
+                })
+                .collect::<Result<Vec<_>>>()?,
+            None => chunks(pool)?,
+        };
+        for (id, data) in waves {
+            let (kind, chunks) = list(data)?;
+            if &id != b"LIST" || &kind != b"wave" {
+                return Err(invalid("Invalid wave"));
+            }
+            dls.waves.push(read_wave(&chunks)?);
+        }
+
+        Ok(dls)
+    }
+
+    /// Load DLS collection using file API
+    pub fn load<F: FileApi, P: AsRef<Path>>(fileapi: &mut F, filename: P) -> Result<Self> {
+        Self::parse(&read_file(fileapi, filename.as_ref())?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// Make chunk
+    pub fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(data);
+        if data.len() & 1 != 0 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    /// Make list chunk
+    pub fn list(kind: &[u8; 4], items: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = kind.to_vec();
+        for item in items {
+            data.extend_from_slice(item);
+        }
+        chunk(b"LIST", &data)
+    }
+
+    fn words(values: &[u32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    fn wsmp(unity_note: u16, loops: &[(u32, u32, u32)]) -> Vec<u8> {
+        let mut data = words(&[20, unity_note as u32, 0, 0, loops.len() as u32]);
+        for (loop_type, start, length) in loops {
+            data.extend(words(&[16, *loop_type, *start, *length]));
+        }
+        chunk(b"wsmp", &data)
+    }
+
+    /// Make DLS file with piano and drum instruments
+    pub fn dls_data() -> Vec<u8> {
+        let period = 100;
+        let wave = |name: &str, note| {
+            let mut fmt = 1u16.to_le_bytes().to_vec();
+            fmt.extend_from_slice(&1u16.to_le_bytes());
+            fmt.extend(words(&[22050, 44100]));
+            fmt.extend_from_slice(&2u16.to_le_bytes());
+            fmt.extend_from_slice(&16u16.to_le_bytes());
+            let data = (0..period * 50)
+                .flat_map(|frame| {
+                    let phase = frame as f64 / period as f64 * std::f64::consts::TAU;
+                    ((phase.sin() * 16000.0) as i16).to_le_bytes()
+                })
+                .collect::<Vec<_>>();
+            list(
+                b"wave",
+                &[
+                    chunk(b"fmt ", &fmt),
+                    wsmp(note, &[(0, 0, period * 50)]),
+                    chunk(b"data", &data),
+                    list(b"INFO", &[chunk(b"INAM", name.as_bytes())]),
+                ],
+            )
+        };
+        let waves = [wave("Sine", 57), wave("Drum", 60)];
+        let mut cues = vec![0u32];
+        cues.push(waves[0].len() as u32);
+
+        let connection = |source: u16, destination: u16, scale: i32| {
+            let mut data = source.to_le_bytes().to_vec();
+            data.extend_from_slice(&0u16.to_le_bytes());
+            data.extend_from_slice(&destination.to_le_bytes());
+            data.extend_from_slice(&0u16.to_le_bytes());
+            data.extend_from_slice(&scale.to_le_bytes());
+            data
+        };
+        let art = |connections: &[Vec<u8>]| {
+            let mut data = words(&[8, connections.len() as u32]);
+            for connection in connections {
+                data.extend_from_slice(connection);
+            }
+            list(b"lart", &[chunk(b"art1", &data)])
+        };
+        let region = |lo: u16, hi: u16, wave: u32, extra: Vec<Vec<u8>>| {
+            let mut header = words(&[(hi as u32) << 16 | lo as u32, 127 << 16]);
+            header.extend(words(&[0]));
+            let mut items = vec![
+                chunk(b"rgnh", &header),
+                chunk(b"wlnk", &words(&[0, 0, wave])),
+            ];
+            items.extend(extra);
+            list(b"rgn ", &items)
+        };
+
+        let piano = list(
+            b"ins ",
+            &[
+                chunk(b"insh", &words(&[1, 0, 0])),
+                list(b"lrgn", &[region(0, 127, 0, Vec::new())]),
+                art(&[
+                    // release 1 second, sustain 50%, the LFO frequency 5 Hz
+                    connection(0, 0x209, 0),
+                    connection(0, 0x20A, 500 << 16),
+                    connection(0, 0x104, -7 << 16),
+                ]),
+                list(b"INFO", &[chunk(b"INAM", b"Piano\0")]),
+            ],
+        );
+        let drums = list(
+            b"ins ",
+            &[
+                chunk(b"insh", &words(&[1, DRUMS_FLAG, 0])),
+                list(
+                    b"lrgn",
+                    &[region(
+                        36,
+                        36,
+                        1,
+                        vec![
+                            wsmp(36, &[]),
+                            art(&[
+                                // the modulation wheel to attenuation
+                                connection(0x81, 1, -(100 << 16)),
+                            ]),
+                        ],
+                    )],
+                ),
+                list(b"INFO", &[chunk(b"INAM", b"Drums\0")]),
+            ],
+        );
+
+        let mut ptbl = words(&[8, 2]);
+        ptbl.extend(words(&cues));
+
+        let mut data = b"DLS ".to_vec();
+        data.extend(chunk(b"colh", &words(&[2])));
+        data.extend(list(b"lins", &[piano, drums]));
+        data.extend(chunk(b"ptbl", &ptbl));
+        data.extend(list(b"wvpl", &waves));
+        data.extend(list(b"INFO", &[chunk(b"INAM", b"Test\0")]));
+        chunk(b"RIFF", &data)
+    }
+
+    #[test]
+    fn parse_dls() {
+        let dls = Dls::parse(&dls_data()).unwrap();
+
+        assert_eq!(dls.name, "Test");
+        assert_eq!(dls.instruments.len(), 2);
+        assert_eq!(dls.waves.len(), 2);
+
+        let piano = &dls.instruments[0];
+        assert_eq!(piano.name, "Piano");
+        assert_eq!(piano.bank(), 0);
+        assert_eq!(
+            Instrument {
+                bank: 0x0102,
+                ..Default::default()
+            }
+            .bank(),
+            130
+        );
+        assert_eq!(piano.connections.len(), 3);
+        assert_eq!(piano.regions[0].keys, Range::ALL);
+
+        let drums = &dls.instruments[1];
+        assert!(drums.is_drums());
+        assert_eq!(drums.bank(), 128);
+        let region = &drums.regions[0];
+        assert_eq!(region.keys, Range { lo: 36, hi: 36 });
+        assert_eq!(region.wave, 1);
+        assert_eq!(region.wave_sample.as_ref().unwrap().unity_note, 36);
+        assert_eq!(region.connections[0].source, 0x81);
+
+        let wave = &dls.waves[1];
+        assert_eq!(wave.name, "Drum");
+        assert_eq!(wave.pcm.sample_rate, 22050);
+        assert_eq!(wave.pcm.frames(), 5000);
+        assert_eq!(wave.wave_sample.as_ref().unwrap().loops.len(), 1);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Dls::parse(b"RIFF\0\0\0\0sfbk").is_err());
+        assert!(Dls::parse(b"RIFF\0\0\0\0DLS ").is_err());
+        assert!(Dls::parse(b"RIFF\x02\0\0\0DLS ").is_err());
+
+        let mut data = dls_data();
+        // break the size of the first chunk
+        data[19] = 0x7F;
+        assert!(Dls::parse(&data).is_err());
+    }
+}
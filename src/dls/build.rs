@@ -0,0 +1,603 @@
+use super::{Connection, Dls, Region, WaveSample};
+use crate::{
+    sf2::{
+        Generator, Info, Instrument, Modulator, Preset, Range, Sample, SamplePcm, SampleType,
+        SoundFont, Zone,
+    },
+    Error, GenParam, Result,
+};
+use std::collections::BTreeMap;
+
+// The connection sources
+const SRC_NONE: u16 = 0x0000;
+const SRC_LFO: u16 = 0x0001;
+const SRC_KEY_ON_VELOCITY: u16 = 0x0002;
+const SRC_KEY_NUMBER: u16 = 0x0003;
+const SRC_EG2: u16 = 0x0005;
+const SRC_PITCH_WHEEL: u16 = 0x0006;
+const SRC_POLY_PRESSURE: u16 = 0x0007;
+const SRC_CHANNEL_PRESSURE: u16 = 0x0008;
+const SRC_VIBRATO: u16 = 0x0009;
+const SRC_CC1: u16 = 0x0081;
+const SRC_CC7: u16 = 0x0087;
+const SRC_CC10: u16 = 0x008A;
+const SRC_CC11: u16 = 0x008B;
+const SRC_CC91: u16 = 0x00DB;
+const SRC_CC93: u16 = 0x00DD;
+const SRC_RPN0: u16 = 0x0100;
+
+// The connection destinations
+const DST_ATTENUATION: u16 = 0x0001;
+const DST_PITCH: u16 = 0x0003;
+const DST_PAN: u16 = 0x0004;
+const DST_CHORUS: u16 = 0x0080;
+const DST_REVERB: u16 = 0x0081;
+const DST_LFO_FREQUENCY: u16 = 0x0104;
+const DST_LFO_START_DELAY: u16 = 0x0105;
+const DST_VIB_FREQUENCY: u16 = 0x0114;
+const DST_VIB_START_DELAY: u16 = 0x0115;
+const DST_EG1_ATTACK_TIME: u16 = 0x0206;
+const DST_EG1_DECAY_TIME: u16 = 0x0207;
+const DST_EG1_RELEASE_TIME: u16 = 0x0209;
+const DST_EG1_SUSTAIN_LEVEL: u16 = 0x020A;
+const DST_EG1_DELAY_TIME: u16 = 0x020B;
+const DST_EG1_HOLD_TIME: u16 = 0x020C;
+const DST_EG2_ATTACK_TIME: u16 = 0x030A;
+const DST_EG2_DECAY_TIME: u16 = 0x030B;
+const DST_EG2_RELEASE_TIME: u16 = 0x030D;
+const DST_EG2_SUSTAIN_LEVEL: u16 = 0x030E;
+const DST_EG2_DELAY_TIME: u16 = 0x030F;
+const DST_EG2_HOLD_TIME: u16 = 0x0310;
+const DST_FILTER_CUTOFF: u16 = 0x0500;
+const DST_FILTER_Q: u16 = 0x0501;
+
+/// The concave transform of DLS Level 1
+const TRN_CONCAVE: u16 = 0x0001;
+
+/// The connections which are covered by default modulators of SoundFont
+const DEFAULT_CONNECTIONS: [(u16, u16, u16); 8] = [
+    (SRC_KEY_ON_VELOCITY, SRC_NONE, DST_ATTENUATION),
+    (SRC_PITCH_WHEEL, SRC_RPN0, DST_PITCH),
+    (SRC_CC7, SRC_NONE, DST_ATTENUATION),
+    (SRC_CC11, SRC_NONE, DST_ATTENUATION),
+    (SRC_CC10, SRC_NONE, DST_PAN),
+    (SRC_CC91, SRC_NONE, DST_REVERB),
+    (SRC_CC93, SRC_NONE, DST_CHORUS),
+    (SRC_VIBRATO, SRC_CC1, DST_PITCH),
+];
+
+/// The pitch tracking of key number by default (in cents per 128 keys)
+const DEFAULT_KEY_TRACKING: i32 = 12800;
+
+/// The lowest value of time generators (1 ms)
+const MIN_TIMECENTS: i32 = -12000;
+
+/// The range of volume envelope sustain in centibels
+const SUSTAIN_RANGE: i32 = 960;
+
+/// The offsets in sample points are split to fine and coarse generators
+const COARSE_OFFSET: i32 = 32768;
+
+/// Get the generator which destination sets
+fn dest_gen(destination: u16) -> Option<GenParam> {
+    use GenParam::*;
+    Some(match destination {
+        DST_ATTENUATION => Attenuation,
+        DST_PITCH => FineTune,
+        DST_PAN => Pan,
+        DST_CHORUS => ChorussEnd,
+        DST_REVERB => ReverbsEnd,
+        DST_LFO_FREQUENCY => ModLfoFreq,
+        DST_LFO_START_DELAY => ModLfoDelay,
+        DST_VIB_FREQUENCY => VibLfoFreq,
+        DST_VIB_START_DELAY => Viblfodelay,
+        DST_EG1_DELAY_TIME => VolEnvDelay,
+        DST_EG1_ATTACK_TIME => VolEnvAttack,
+        DST_EG1_HOLD_TIME => VolEnvHold,
+        DST_EG1_DECAY_TIME => VolEnvDecay,
+        DST_EG1_SUSTAIN_LEVEL => VolEnvSustain,
+        DST_EG1_RELEASE_TIME => VolEnvRelease,
+        DST_EG2_DELAY_TIME => ModEnvDelay,
+        DST_EG2_ATTACK_TIME => ModEnvAttack,
+        DST_EG2_HOLD_TIME => ModEnvHold,
+        DST_EG2_DECAY_TIME => ModEnvDecay,
+        DST_EG2_SUSTAIN_LEVEL => ModEnvSustain,
+        DST_EG2_RELEASE_TIME => ModEnvRelease,
+        DST_FILTER_CUTOFF => FilterFc,
+        DST_FILTER_Q => FilterQ,
+        _ => return None,
+    })
+}
+
+/// Get the generator which modulation source sets
+fn mod_gen(source: u16, destination: u16) -> Option<GenParam> {
+    use GenParam::*;
+    Some(match (source, destination) {
+        (SRC_LFO, DST_PITCH) => ModLfoToPitch,
+        (SRC_LFO, DST_ATTENUATION) => ModLfoToVol,
+        (SRC_LFO, DST_FILTER_CUTOFF) => ModLfoToFilterFc,
+        (SRC_VIBRATO, DST_PITCH) => VibLfoToPitch,
+        (SRC_EG2, DST_PITCH) => ModEnvToPitch,
+        (SRC_EG2, DST_FILTER_CUTOFF) => ModEnvToFilterFc,
+        (SRC_KEY_NUMBER, DST_EG1_HOLD_TIME) => KeyToVolEnvHold,
+        (SRC_KEY_NUMBER, DST_EG1_DECAY_TIME) => KeyToVolEnvDecay,
+        (SRC_KEY_NUMBER, DST_EG2_HOLD_TIME) => KeyToModEnvHold,
+        (SRC_KEY_NUMBER, DST_EG2_DECAY_TIME) => KeyToModEnvDecay,
+        _ => return None,
+    })
+}
+
+/**
+Convert MIDI source to modulator source
+
+The transform bits of DLS Level 2 are used when `shift` is `10` for
+source and `4` for control.
+ */
+fn mod_source(source: u16, transform: u16, shift: u16) -> Option<u16> {
+    let (index, bipolar) = match source {
+        SRC_KEY_ON_VELOCITY => (2, false),
+        SRC_KEY_NUMBER => (3, false),
+        SRC_POLY_PRESSURE => (10, false),
+        SRC_CHANNEL_PRESSURE => (13, false),
+        SRC_PITCH_WHEEL => (14, true),
+        SRC_RPN0 => (16, false),
+        // the continuous controllers with CC flag
+        0x0080..=0x00FF => (source, false),
+        _ => return None,
+    };
+    let curve = if transform == TRN_CONCAVE && shift == 10 {
+        1
+    } else {
+        transform >> shift & 0xF
+    };
+    // the invert and bipolar flags are above the curve bits
+    let invert = transform >> (shift + 5) & 1;
+    let bipolar = bipolar as u16 | transform >> (shift + 4) & 1;
+    Some(index | invert << 8 | bipolar << 9 | curve.min(3) << 10)
+}
+
+/// Convert 16.16 fixed point to integer
+fn fixed(scale: i32) -> i32 {
+    (scale as f64 / 65536.0).round() as i32
+}
+
+/// The generators and modulators of zone
+#[derive(Default)]
+struct Articulation {
+    gens: BTreeMap<u16, i32>,
+    mods: Vec<Modulator>,
+}
+
+impl Articulation {
+    fn set(&mut self, param: GenParam, amount: i32) {
+        self.gens.insert(param as u16, amount);
+    }
+
+    /// Add amount to value (like tuning or attenuation)
+    fn add(&mut self, param: GenParam, amount: i32) {
+        *self.gens.entry(param as u16).or_insert(0) += amount;
+    }
+
+    fn connections(&mut self, connections: &[Connection]) {
+        for connection in connections {
+            self.connection(connection);
+        }
+    }
+
+    fn connection(&mut self, connection: &Connection) {
+        let Connection {
+            source,
+            control,
+            destination,
+            transform,
+            scale,
+        } = *connection;
+
+        if DEFAULT_CONNECTIONS.contains(&(source, control, destination)) {
+            return;
+        }
+
+        if source == SRC_NONE && control == SRC_NONE {
+            let value = if scale == i32::MIN {
+                // the zero time
+                MIN_TIMECENTS
+            } else {
+                fixed(scale)
+            };
+            match destination {
+                DST_ATTENUATION => self.add(GenParam::Attenuation, -value),
+                DST_PITCH => self.add(GenParam::FineTune, value),
+                // the level in 0.1 % to attenuation in centibels
+                DST_EG1_SUSTAIN_LEVEL => self.set(
+                    GenParam::VolEnvSustain,
+                    (1000 - value.clamp(0, 1000)) * SUSTAIN_RANGE / 1000,
+                ),
+                // the level in 0.1 % to decrease in 0.1 %
+                DST_EG2_SUSTAIN_LEVEL => {
+                    self.set(GenParam::ModEnvSustain, 1000 - value.clamp(0, 1000))
+                }
+                _ => {
+                    if let Some(param) = dest_gen(destination) {
+                        self.set(param, value);
+                    }
+                }
+            }
+            return;
+        }
+
+        let value = fixed(scale);
+        if control == SRC_NONE {
+            if source == SRC_KEY_NUMBER && destination == DST_PITCH {
+                if value != DEFAULT_KEY_TRACKING {
+                    // the cents per 128 keys to cents per key
+                    self.set(GenParam::ScaleTune, value / 128);
+                }
+                return;
+            }
+            if let Some(param) = mod_gen(source, destination) {
+                let value = if source == SRC_KEY_NUMBER {
+                    // the timecents per 128 keys to timecents per key
+                    value / 128
+                } else {
+                    value
+                };
+                self.set(param, value);
+                return;
+            }
+        }
+
+        // the modulation of internal source by controller
+        let (source, amount_source, dest) = match mod_gen(source, destination) {
+            Some(param) => (mod_source(control, transform, 4), 0, Some(param)),
+            None => (
+                mod_source(source, transform, 10),
+                if control == SRC_NONE {
+                    Some(0)
+                } else {
+                    mod_source(control, transform, 4)
+                }
+                .unwrap_or(0),
+                dest_gen(destination),
+            ),
+        };
+        if let (Some(src), Some(dest)) = (source, dest) {
+            let amount = if dest == GenParam::Attenuation {
+                -value
+            } else {
+                value
+            };
+            self.mods.push(Modulator {
+                src,
+                dest: dest as u16,
+                amount: clamp(amount),
+                amount_src: amount_source,
+                transform: 0,
+            });
+        }
+    }
+
+    fn into_zone(mut self) -> Zone {
+        // the fine tune beyond semitone is moved to coarse tune
+        let param = GenParam::FineTune as u16;
+        if let Some(cents) = self.gens.remove(&param) {
+            self.add(GenParam::CoarseTune, cents / 100);
+            if cents % 100 != 0 {
+                self.set(GenParam::FineTune, cents % 100);
+            }
+        }
+        let attenuation = GenParam::Attenuation as u16;
+        if let Some(value) = self.gens.get_mut(&attenuation) {
+            *value = (*value).clamp(0, 1440);
+        }
+
+        Zone {
+            gens: self
+                .gens
+                .into_iter()
+                .filter(|(oper, amount)| *amount != 0 || *oper != GenParam::CoarseTune as u16)
+                .map(|(oper, amount)| Generator {
+                    oper,
+                    amount: clamp(amount),
+                })
+                .collect(),
+            mods: self.mods,
+        }
+    }
+}
+
+fn clamp(amount: i32) -> i16 {
+    amount.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+fn range_gen(param: GenParam, range: Range) -> Generator {
+    Generator {
+        oper: param as u16,
+        amount: i16::from_le_bytes([range.lo, range.hi]),
+    }
+}
+
+/// Split offset to fine and coarse generators
+fn offset(art: &mut Articulation, fine: GenParam, coarse: GenParam, offset: i32) {
+    if offset % COARSE_OFFSET != 0 {
+        art.set(fine, offset % COARSE_OFFSET);
+    }
+    if offset / COARSE_OFFSET != 0 {
+        art.set(coarse, offset / COARSE_OFFSET);
+    }
+}
+
+/// Make zone of region
+fn region_zone(region: &Region, wave: &WaveSample, sample: u16, pcm: &SamplePcm) -> Zone {
+    let mut art = Articulation::default();
+    art.connections(&region.connections);
+
+    art.set(GenParam::OverrideRootKey, wave.unity_note.min(127) as i32);
+    art.add(GenParam::FineTune, wave.fine_tune as i32);
+    // the gain in 1/655360 dB to attenuation in centibels
+    art.add(GenParam::Attenuation, -fixed(wave.attenuation));
+
+    if region.key_group != 0 {
+        art.set(GenParam::ExclusiveClass, region.key_group.min(127) as i32);
+    }
+
+    // the invalid loops are ignored
+    let wave_loop = wave
+        .loops
+        .first()
+        .and_then(|wave_loop| {
+            let end = wave_loop.start.checked_add(wave_loop.length)?;
+            Some((wave_loop.start, end, wave_loop.loop_type))
+        })
+        .filter(|(start, end, _)| start < end && *end as usize <= pcm.frames());
+    if let Some((loop_start, loop_end, loop_type)) = wave_loop {
+        let (start, end) = pcm.loop_points.unwrap_or((0, 0));
+        offset(
+            &mut art,
+            GenParam::StartLoopAddOfs,
+            GenParam::Startloopaddrcoarseofs,
+            loop_start as i32 - start as i32,
+        );
+        offset(
+            &mut art,
+            GenParam::EndLoopAddrOfs,
+            GenParam::EndLoopAddrCoarseOfs,
+            loop_end as i32 - end as i32,
+        );
+        // the loop until release plays the rest of sample
+        art.set(GenParam::SampleMode, if loop_type == 1 { 3 } else { 1 });
+    }
+
+    let mut zone = art.into_zone();
+    let mut gens = Vec::new();
+    if region.keys != Range::ALL {
+        gens.push(range_gen(GenParam::Keyrange, region.keys));
+    }
+    if region.vels != Range::ALL {
+        gens.push(range_gen(GenParam::Velrange, region.vels));
+    }
+    gens.append(&mut zone.gens);
+    gens.push(Generator {
+        oper: GenParam::SampleId as u16,
+        amount: sample as i16,
+    });
+    zone.gens = gens;
+    zone
+}
+
+/**
+Conversion to SoundFont
+ */
+impl Dls {
+    /**
+    Convert to SoundFont with sample data
+
+    Each DLS instrument becomes a preset with its own instrument.
+    The instrument articulation goes to global zone and each region
+    becomes a zone. The multi-channel waves are reduced to the first channel.
+
+    The result can be written using [`SoundFont::write`].
+     */
+    pub fn to_soundfont(&self) -> Result<(SoundFont, Vec<SamplePcm>)> {
+        if self.waves.len() > u16::MAX as usize {
+            return Err(Error::Format {
+                reason: "Too many waves",
+            });
+        }
+
+        let mut samples = Vec::new();
+        let mut data = Vec::new();
+        for wave in &self.waves {
+            let mut pcm = wave.pcm.channel(0);
+            let wave_sample = wave.wave_sample.clone().unwrap_or_default();
+            // the tuning is applied by zones
+            pcm.root_key = wave_sample.unity_note.min(127) as u8;
+            pcm.correction = 0;
+            let frames = pcm.frames() as u32;
+            pcm.loop_points = wave_sample
+                .loops
+                .first()
+                .and_then(|wave_loop| {
+                    let end = wave_loop.start.checked_add(wave_loop.length)?;
+                    Some((wave_loop.start, end))
+                })
+                .filter(|(start, end)| start < end && *end <= frames);
+
+            samples.push(Sample {
+                name: wave.name.clone(),
+                sample_type: SampleType::MONO,
+                ..Default::default()
+            });
+            data.push(pcm);
+        }
+
+        let mut presets = Vec::new();
+        let mut instruments = Vec::new();
+        for instrument in &self.instruments {
+            let mut global = Articulation::default();
+            global.connections(&instrument.connections);
+            let global = global.into_zone();
+
+            let mut zones = Vec::new();
+            for region in &instrument.regions {
+                let index = region.wave as usize;
+                let wave = match self.waves.get(index) {
+                    Some(wave) => wave,
+                    None => continue,
+                };
+                let wave_sample = region
+                    .wave_sample
+                    .as_ref()
+                    .or(wave.wave_sample.as_ref())
+                    .cloned()
+                    .unwrap_or_default();
+                zones.push(region_zone(
+                    region,
+                    &wave_sample,
+                    index as u16,
+                    &data[index],
+                ));
+            }
+
+            presets.push(Preset {
+                name: instrument.name.clone(),
+                prog: instrument.prog as u16,
+                bank: instrument.bank(),
+                zones: vec![Zone {
+                    gens: vec![Generator {
+                        oper: GenParam::Instrument as u16,
+                        amount: instruments.len() as i16,
+                    }],
+                    mods: Vec::new(),
+                }],
+                ..Default::default()
+            });
+            instruments.push(Instrument {
+                name: instrument.name.clone(),
+                global: if global.gens.is_empty() && global.mods.is_empty() {
+                    None
+                } else {
+                    Some(global)
+                },
+                zones,
+            });
+        }
+
+        let font = SoundFont {
+            info: Info {
+                name: if self.name.is_empty() {
+                    "DLS".into()
+                } else {
+                    self.name.clone()
+                },
+                ..Default::default()
+            },
+            presets,
+            instruments,
+            samples,
+            sample_data: None,
+            sample_data_24: None,
+        };
+        Ok((font, data))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{test::dls_data, WaveLoop};
+    use super::*;
+    use crate::{sf2::validate_soundfont, FsFileApi, IsFont};
+
+    #[test]
+    fn convert() {
+        let dls = Dls::parse(&dls_data()).unwrap();
+        let (font, data) = dls.to_soundfont().unwrap();
+
+        assert_eq!(font.presets.len(), 2);
+        assert_eq!(font.presets[1].bank, 128);
+        assert_eq!(data[0].loop_points, Some((0, 5000)));
+        assert_eq!(data[0].root_key, 57);
+
+        let amount = |zone: &Zone, param| zone.gen(param).map(|gen| gen.amount);
+
+        let global = font.instruments[0].global.as_ref().unwrap();
+        assert_eq!(amount(global, GenParam::VolEnvRelease), Some(0));
+        assert_eq!(amount(global, GenParam::VolEnvSustain), Some(480));
+        assert_eq!(amount(global, GenParam::ModLfoFreq), Some(-7));
+
+        let zone = &font.instruments[0].zones[0];
+        assert_eq!(amount(zone, GenParam::OverrideRootKey), Some(57));
+        assert_eq!(amount(zone, GenParam::SampleMode), Some(1));
+        assert_eq!(zone.sample(), Some(0));
+
+        let zone = &font.instruments[1].zones[0];
+        assert_eq!(zone.key_range(), Some(Range { lo: 36, hi: 36 }));
+        assert_eq!(amount(zone, GenParam::OverrideRootKey), Some(36));
+        // the region parameters have no loops
+        assert_eq!(amount(zone, GenParam::SampleMode), None);
+        assert_eq!(
+            zone.mods,
+            vec![Modulator {
+                src: 0x81,
+                dest: GenParam::Attenuation as u16,
+                amount: 100,
+                amount_src: 0,
+                transform: 0,
+            }]
+        );
+
+        let mut buffer = Vec::new();
+        font.write(&mut buffer, &data).unwrap();
+
+        assert!(validate_soundfont(&buffer).is_valid());
+    }
+
+    #[test]
+    fn invalid_loops() {
+        let mut dls = Dls::parse(&dls_data()).unwrap();
+        let broken = WaveLoop {
+            loop_type: 0,
+            start: u32::MAX,
+            length: 2,
+        };
+        dls.waves[0].wave_sample.as_mut().unwrap().loops = vec![broken];
+        for instrument in &mut dls.instruments {
+            for region in &mut instrument.regions {
+                region.wave_sample = Some(WaveSample {
+                    loops: vec![broken],
+                    ..Default::default()
+                });
+            }
+        }
+
+        let (font, data) = dls.to_soundfont().unwrap();
+        assert_eq!(data[0].loop_points, None);
+        let zone = &font.instruments[0].zones[0];
+        assert!(zone.gen(GenParam::SampleMode).is_none());
+    }
+
+    #[test]
+    fn load_synth() {
+        let synth = crate::Synth::new(crate::Settings::new().unwrap()).unwrap();
+        let path = std::env::temp_dir().join(format!("fluidlite-test-{}.dls", std::process::id()));
+        std::fs::write(&path, dls_data()).unwrap();
+
+        let id = synth.sfload_dls(&mut FsFileApi, &path, true).unwrap();
+
+        let names = synth
+            .get_sfont_by_id(id)
+            .unwrap()
+            .preset_iter()
+            .map(|preset| preset.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Piano", "Drums"]);
+
+        let mut samples = [0f32; 4410 * 2];
+        synth.program_select(0, id, 0, 0).unwrap();
+        synth.note_on(0, 60, 127).unwrap();
+        synth.write(samples.as_mut()).unwrap();
+
+        assert!(samples.iter().any(|sample| *sample != 0.0));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,429 @@
+use crate::FileApi;
+use std::{
+    collections::HashMap,
+    io::{Error as IoError, ErrorKind, Read, Result as IoResult, Seek, SeekFrom},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// The shared file data
+pub(crate) type Blob = Arc<dyn AsRef<[u8]> + Send + Sync>;
+
+/**
+The file which data is located in memory
+ */
+#[derive(Clone)]
+pub struct MemoryFile {
+    data: Blob,
+    pos: u64,
+}
+
+impl MemoryFile {
+    pub(crate) fn new(data: Blob) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// Get the whole data of file
+    pub fn data(&self) -> &[u8] {
+        (*self.data).as_ref()
+    }
+}
+
+impl Read for MemoryFile {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let data = (*self.data).as_ref();
+        let start = (self.pos as usize).min(data.len());
+        let len = buf.len().min(data.len() - start);
+        buf[..len].copy_from_slice(&data[start..start + len]);
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl Seek for MemoryFile {
+    fn seek(&mut self, pos: SeekFrom) -> IoResult<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => {
+                self.pos = offset;
+                return Ok(offset);
+            }
+            SeekFrom::End(offset) => ((*self.data).as_ref().len() as u64, offset),
+            SeekFrom::Current(offset) => (self.pos, offset),
+        };
+        let pos = if offset < 0 {
+            base.checked_sub(offset.unsigned_abs())
+        } else {
+            base.checked_add(offset as u64)
+        }
+        .ok_or_else(|| IoError::new(ErrorKind::InvalidInput, "Invalid seek position"))?;
+        self.pos = pos;
+        Ok(pos)
+    }
+}
+
+/**
+The file API which serves named blobs from memory
+
+The fonts can be embedded into application using `include_bytes!`:
+
+```no_run
+use fluidlite::{Loader, MemoryFileApi, Settings, Synth};
+
+let loader = Loader::new_default().unwrap();
+loader.set_file_api(
+    MemoryFileApi::new().with("Boomwhacker.sf2", include_bytes!("../sf_/Boomwhacker.sf2")),
+);
+
+let synth = Synth::new(Settings::new().unwrap()).unwrap();
+synth.add_sfloader(loader);
+synth.sfload("Boomwhacker.sf2", true).unwrap();
+```
+ */
+#[derive(Clone, Default)]
+pub struct MemoryFileApi {
+    files: HashMap<PathBuf, Blob>,
+}
+
+impl MemoryFileApi {
+    /// Create empty file API
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+    Add the file with name
+
+    The data can be `Vec<u8>`, `&'static [u8]` or any other owned bytes.
+    The file with same name is replaced.
+     */
+    pub fn add<P, D>(&mut self, filename: P, data: D) -> &mut Self
+    where
+        P: Into<PathBuf>,
+        D: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        self.files.insert(filename.into(), Arc::new(data));
+        self
+    }
+
+    /// Add the file with name (the builder-style variant of [`MemoryFileApi::add`])
+    pub fn with<P, D>(mut self, filename: P, data: D) -> Self
+    where
+        P: Into<PathBuf>,
+        D: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        self.add(filename, data);
+        self
+    }
+
+    /// Remove the file with name
+    pub fn remove<P: AsRef<Path>>(&mut self, filename: P) -> bool {
+        self.files.remove(filename.as_ref()).is_some()
+    }
+
+    /// Check that the file with name exists
+    pub fn contains<P: AsRef<Path>>(&self, filename: P) -> bool {
+        self.files.contains_key(filename.as_ref())
+    }
+}
+
+impl FileApi for MemoryFileApi {
+    type File = MemoryFile;
+
+    fn open(&mut self, filename: &Path) -> IoResult<Self::File> {
+        self.files
+            .get(filename)
+            .cloned()
+            .map(MemoryFile::new)
+            .ok_or_else(|| ErrorKind::NotFound.into())
+    }
+
+    fn read(file: &mut Self::File, buf: &mut [u8]) -> IoResult<()> {
+        file.read_exact(buf)
+    }
+
+    fn seek(file: &mut Self::File, pos: SeekFrom) -> IoResult<u64> {
+        file.seek(pos)
+    }
+
+    fn tell(file: &mut Self::File) -> IoResult<u64> {
+        Ok(file.pos)
+    }
+}
+
+/**
+The file API which opens readers using function
+
+Adapts any `Read + Seek` source like archive entries or network streams:
+
+```no_run
+use fluidlite::{Loader, ReaderFileApi};
+use std::{fs::File, io::BufReader};
+
+let loader = Loader::new_default().unwrap();
+loader.set_file_api(ReaderFileApi::new(|filename| {
+    File::open(filename).map(BufReader::new)
+}));
+```
+ */
+pub struct ReaderFileApi<O, R> {
+    open: O,
+    reader: PhantomData<fn() -> R>,
+}
+
+impl<O, R> ReaderFileApi<O, R>
+where
+    O: FnMut(&Path) -> IoResult<R>,
+    R: Read + Seek,
+{
+    /// Create file API with function which opens readers
+    pub fn new(open: O) -> Self {
+        Self {
+            open,
+            reader: PhantomData,
+        }
+    }
+}
+
+impl<O, R> FileApi for ReaderFileApi<O, R>
+where
+    O: FnMut(&Path) -> IoResult<R>,
+    R: Read + Seek,
+{
+    type File = R;
+
+    fn open(&mut self, filename: &Path) -> IoResult<Self::File> {
+        (self.open)(filename)
+    }
+
+    fn read(file: &mut Self::File, buf: &mut [u8]) -> IoResult<()> {
+        file.read_exact(buf)
+    }
+
+    fn seek(file: &mut Self::File, pos: SeekFrom) -> IoResult<u64> {
+        file.seek(pos)
+    }
+
+    fn tell(file: &mut Self::File) -> IoResult<u64> {
+        file.stream_position()
+    }
+}
+
+/**
+The file API which reads files from file system
+
+It works like the default file API of library, but makes the files
+available to the Rust parsers like [`Sfz::load`](crate::sfz::Sfz::load).
+ */
+#[derive(Clone, Copy, Default)]
+pub struct FsFileApi;
+
+impl FileApi for FsFileApi {
+    type File = std::fs::File;
+
+    fn open(&mut self, filename: &Path) -> IoResult<Self::File> {
+        std::fs::File::open(filename)
+    }
+
+    fn read(file: &mut Self::File, buf: &mut [u8]) -> IoResult<()> {
+        file.read_exact(buf)
+    }
+
+    fn seek(file: &mut Self::File, pos: SeekFrom) -> IoResult<u64> {
+        file.seek(pos)
+    }
+
+    fn tell(file: &mut Self::File) -> IoResult<u64> {
+        file.stream_position()
+    }
+}
+
+/**
+The file API which maps files into memory
+
+The files are mapped using `memmap2` with `mmap` feature.
+
+The file stays mapped from opening until closing, which for the loader
+of synth means during [`Synth::sfload`](crate::Synth::sfload) only.
+ */
+#[cfg(feature = "mmap")]
+#[derive(Clone, Copy)]
+pub struct MmapFileApi {
+    _unsafe: (),
+}
+
+#[cfg(feature = "mmap")]
+impl MmapFileApi {
+    /**
+    Create the file API which maps files into memory
+
+    # Safety
+
+    The files must not be modified while mapped. The reading of mapped
+    file which is truncated is undefined behavior (the process is killed
+    by `SIGBUS` on Unix), so don't use it for the files which are rewritten
+    in place, like the fonts reloaded by `FontWatcher` with `watch` feature.
+     */
+    pub unsafe fn new() -> Self {
+        Self { _unsafe: () }
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl FileApi for MmapFileApi {
+    type File = MemoryFile;
+
+    fn open(&mut self, filename: &Path) -> IoResult<Self::File> {
+        let file = std::fs::File::open(filename)?;
+        // the mapped files are read-only as required by constructor
+        let data = unsafe { memmap2::Mmap::map(&file) }?;
+        Ok(MemoryFile::new(Arc::new(data)))
+    }
+
+    fn read(file: &mut Self::File, buf: &mut [u8]) -> IoResult<()> {
+        file.read_exact(buf)
+    }
+
+    fn seek(file: &mut Self::File, pos: SeekFrom) -> IoResult<u64> {
+        file.seek(pos)
+    }
+
+    fn tell(file: &mut Self::File) -> IoResult<u64> {
+        Ok(file.pos)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{loader::read_file, Error, Settings, Synth};
+    use std::{fs::File, io::BufReader};
+
+    #[test]
+    fn memory_file() {
+        const DATA: &[u8] = &[5, 6];
+
+        let mut fileapi = MemoryFileApi::new().with("a.bin", vec![1u8, 2, 3, 4]);
+        fileapi.add("b.bin", DATA);
+
+        assert!(fileapi.contains("a.bin"));
+        assert_eq!(
+            fileapi
+                .open(Path::new("c.bin"))
+                .err()
+                .map(|error| error.kind()),
+            Some(ErrorKind::NotFound)
+        );
+
+        let mut file = fileapi.open(Path::new("a.bin")).unwrap();
+        let mut buf = [0u8; 2];
+        assert_eq!(
+            MemoryFileApi::seek(&mut file, SeekFrom::End(-2)).unwrap(),
+            2
+        );
+        MemoryFileApi::read(&mut file, &mut buf).unwrap();
+        assert_eq!(buf, [3, 4]);
+        assert_eq!(MemoryFileApi::tell(&mut file).unwrap(), 4);
+        assert!(MemoryFileApi::read(&mut file, &mut buf).is_err());
+        assert!(MemoryFileApi::seek(&mut file, SeekFrom::Current(-5)).is_err());
+
+        assert_eq!(read_file(&mut fileapi, Path::new("b.bin")).unwrap(), [5, 6]);
+        assert!(fileapi.remove("b.bin"));
+        assert!(read_file(&mut fileapi, Path::new("b.bin")).is_err());
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap_file() {
+        let path = Path::new("sf_/Boomwhacker.sf2");
+        // the test fonts aren't modified
+        let mut fileapi = unsafe { MmapFileApi::new() };
+        let data = read_file(&mut fileapi, path).unwrap();
+        assert_eq!(data, std::fs::read(path).unwrap());
+
+        let synth = Synth::new(Settings::new().unwrap()).unwrap();
+        let loader = crate::Loader::new_default().unwrap();
+        loader.set_file_api(fileapi);
+        synth.add_sfloader(loader);
+        synth.sfload(path, true).unwrap();
+    }
+
+    #[test]
+    fn reader_file() {
+        let mut fileapi =
+            ReaderFileApi::new(|filename: &Path| File::open(filename).map(BufReader::new));
+        let data = read_file(&mut fileapi, Path::new("sf_/Boomwhacker.sf2")).unwrap();
+
+        assert_eq!(data, std::fs::read("sf_/Boomwhacker.sf2").unwrap());
+    }
+
+    #[test]
+    fn sfload_memory() {
+        let synth = Synth::new(Settings::new().unwrap()).unwrap();
+        let loader = crate::Loader::new_default().unwrap();
+        loader.set_file_api(
+            MemoryFileApi::new().with("Boomwhacker.sf2", include_bytes!("../sf_/Boomwhacker.sf2")),
+        );
+        synth.add_sfloader(loader);
+
+        synth.sfload("Boomwhacker.sf2", true).unwrap();
+
+        let data = std::fs::read("sf_/Boomwhacker.sf2").unwrap();
+        let id = synth.sfload_bytes("Bytes.sf2", data, true).unwrap();
+        assert_eq!(synth.sfcount(), 2);
+        synth.program_select(0, id, 0, 0).unwrap();
+    }
+
+    #[test]
+    fn free_unloaded_bytes() {
+        struct Shared(Arc<Vec<u8>>);
+
+        impl AsRef<[u8]> for Shared {
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        let synth = Synth::new(Settings::new().unwrap()).unwrap();
+        let data = Arc::new(std::fs::read("sf_/Boomwhacker.sf2").unwrap());
+
+        let first = synth
+            .sfload_bytes("First.sf2", Shared(data.clone()), true)
+            .unwrap();
+        let second = synth
+            .sfload_bytes("Second.sf2", Shared(data.clone()), true)
+            .unwrap();
+        assert_eq!(Arc::strong_count(&data), 3);
+
+        // the data is kept for reloading
+        assert_eq!(synth.sfreload(first).unwrap(), first);
+        synth.sfunload(first, true).unwrap();
+        assert_eq!(Arc::strong_count(&data), 2);
+        assert!(synth.sfload("First.sf2", true).is_err());
+
+        synth.sfunload(second, true).unwrap();
+        assert_eq!(Arc::strong_count(&data), 1);
+
+        // the failed font isn't kept
+        assert!(synth
+            .sfload_bytes("Broken.sf2", Shared(Arc::new(b"RIFF".to_vec())), true)
+            .is_err());
+    }
+
+    #[test]
+    fn missing_file_with_bytes() {
+        let synth = Synth::new(Settings::new().unwrap()).unwrap();
+        let missing = || {
+            matches!(
+                synth.sfload("sf_/NotExists.sf2", true),
+                Err(Error::FontLoad { error: Some(_), .. })
+            )
+        };
+        assert!(missing());
+
+        // the in-memory loader doesn't report the files which it doesn't serve
+        let data = std::fs::read("sf_/Boomwhacker.sf2").unwrap();
+        synth.sfload_bytes("Bytes.sf2", data, true).unwrap();
+        assert!(missing());
+    }
+}
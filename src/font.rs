@@ -1,150 +1,267 @@
-use crate::{ffi, Bank, FontId, PresetId};
-use std::{marker::PhantomData, ptr::NonNull};
+use crate::{ffi, option_from_ptr, private::HasHandle, Bank, FontId, IntoMidi, PresetId, Synth};
+use std::{ffi::CStr, marker::PhantomData, mem::MaybeUninit, os::raw::c_char, ptr::NonNull};
 
 /**
 The SoundFont interface
+
+The methods return `None` when the font is unloaded.
  */
 pub trait IsFont {
     fn get_id(&self) -> FontId;
-    fn get_name(&self) -> Option<&str>;
-    fn get_preset(&self, bank: Bank, num: PresetId) -> Option<PresetRef<'_>>;
+    fn get_name(&self) -> Option<String>;
+    fn get_preset<B: IntoMidi<Bank>>(&self, bank: B, num: PresetId) -> Option<PresetRef<'_>>;
+    fn preset_iter(&self) -> PresetIter<'_>;
+    /// Check that font is still loaded
+    fn is_loaded(&self) -> bool;
 }
 
 /**
 The SoundFont preset interface
+
+The methods return `None` when the font of preset is unloaded.
  */
 pub trait IsPreset {
-    fn get_name(&self) -> Option<&str>;
-    fn get_banknum(&self) -> Option<Bank>;
+    fn get_name(&self) -> Option<String>;
+    fn get_banknum(&self) -> Option<u32>;
     fn get_num(&self) -> Option<PresetId>;
 }
 
 /**
 Reference to SoundFont object
+
+The reference keeps the font ID and finds the font on each access,
+so it stays safe to use when the font is unloaded (see [`IsFont`]).
+The font IDs aren't reused by synth, so the reference never refers
+to another font which is loaded later.
  */
-#[repr(transparent)]
+#[derive(Clone, Copy)]
 pub struct FontRef<'a> {
-    handle: NonNull<ffi::fluid_sfont_t>,
-    phantom: PhantomData<&'a ()>,
+    synth: &'a Synth,
+    id: FontId,
 }
 
 impl<'a> FontRef<'a> {
-    pub(crate) unsafe fn from_ptr(handle: NonNull<ffi::fluid_sfont_t>) -> Self {
+    pub(crate) unsafe fn from_ptr(synth: &'a Synth, handle: NonNull<ffi::fluid_sfont_t>) -> Self {
         Self {
-            handle,
-            phantom: PhantomData,
+            synth,
+            id: (*handle.as_ptr()).id,
         }
     }
 
-    pub(crate) fn as_ptr(&self) -> *mut ffi::fluid_sfont_t {
-        self.handle.as_ptr()
+    /// Get the font if it is still loaded
+    pub(crate) fn as_ptr(&self) -> Option<NonNull<ffi::fluid_sfont_t>> {
+        font_by_id(self.synth, self.id)
     }
 }
 
+fn font_by_id(synth: &Synth, id: FontId) -> Option<NonNull<ffi::fluid_sfont_t>> {
+    option_from_ptr(unsafe { ffi::fluid_synth_get_sfont_by_id(synth.get_handle().as_ptr(), id) })
+}
+
 /**
 Reference to Preset object
+
+The reference keeps the font ID, the bank and the number of preset
+and finds the preset on each access, so it stays safe to use when
+the font is unloaded (see [`IsPreset`]).
  */
-#[repr(transparent)]
+#[derive(Clone, Copy)]
 pub struct PresetRef<'a> {
-    handle: NonNull<ffi::fluid_preset_t>,
-    phantom: PhantomData<&'a ()>,
+    synth: &'a Synth,
+    font_id: FontId,
+    bank: u32,
+    num: PresetId,
 }
 
 impl<'a> PresetRef<'a> {
-    pub(crate) unsafe fn from_ptr(handle: NonNull<ffi::fluid_preset_t>) -> Self {
+    /**
+    Refer to the preset which pointer is owned by synth
+
+    The preset is not freed.
+     */
+    pub(crate) unsafe fn from_ptr(synth: &'a Synth, handle: NonNull<ffi::fluid_preset_t>) -> Self {
+        let preset_c = &*handle.as_ptr();
+        let font_id = if preset_c.sfont.is_null() {
+            0
+        } else {
+            (*preset_c.sfont).id
+        };
+        let bank = preset_c.get_banknum.map_or(0, |get| get(handle.as_ptr()));
+        let num = preset_c.get_num.map_or(0, |get| get(handle.as_ptr()));
         Self {
-            handle,
-            phantom: PhantomData,
+            synth,
+            font_id,
+            bank: bank.max(0) as _,
+            num: num.max(0) as _,
         }
     }
-}
 
-mod private {
-    use crate::{
-        ffi, option_from_ptr, private::HasHandle, Bank, FontId, FontRef, IsFont, IsPreset,
-        PresetId, PresetRef,
-    };
-    use std::{ffi::CStr, ptr::NonNull};
-
-    impl<X> IsFont for X
-    where
-        X: HasHandle<Handle = ffi::fluid_sfont_t>,
-    {
-        fn get_id(&self) -> FontId {
-            let handle = self.get_handle().as_ptr();
-            let font_c = unsafe { &*handle };
-            font_c.id
-        }
+    /// Get the font ID of preset
+    pub fn font_id(&self) -> FontId {
+        self.font_id
+    }
 
-        fn get_name(&self) -> Option<&str> {
-            let handle = self.get_handle().as_ptr();
-            let font_c = unsafe { &*handle };
-            let get_name = font_c.get_name?;
-            let name = unsafe { (get_name)(handle) };
-            let name = unsafe { CStr::from_ptr(name) };
-            name.to_str().ok()
-        }
+    /**
+    Call function with the preset got from font
 
-        fn get_preset(&self, bank: Bank, num: PresetId) -> Option<PresetRef<'_>> {
-            let handle = self.get_handle().as_ptr();
-            let font_c = unsafe { &*handle };
-            let get_preset = font_c.get_preset?;
-            option_from_ptr(unsafe { (get_preset)(handle, bank, num) })
-                .map(|ptr| unsafe { PresetRef::from_ptr(ptr) })
-        }
-    }
+    The fonts allocate presets on request, so the preset is freed after call.
+     */
+    fn with_preset<T>(&self, func: impl FnOnce(*mut ffi::fluid_preset_t) -> T) -> Option<T> {
+        let font = font_by_id(self.synth, self.font_id)?.as_ptr();
+        let get_preset = unsafe { &*font }.get_preset?;
+        let preset = option_from_ptr(unsafe { (get_preset)(font, self.bank, self.num) })?;
+        let preset = preset.as_ptr();
 
-    impl<'a> HasHandle for FontRef<'a> {
-        type Handle = ffi::fluid_sfont_t;
+        let result = func(preset);
 
-        fn get_handle(&self) -> NonNull<Self::Handle> {
-            self.handle
+        if let Some(free) = unsafe { &*preset }.free {
+            unsafe { (free)(preset) };
         }
+        Some(result)
+    }
+}
+
+/// Copy the name returned by font
+fn name_from_ptr(name: *const c_char) -> Option<String> {
+    if name.is_null() {
+        None
+    } else {
+        Some(
+            unsafe { CStr::from_ptr(name) }
+                .to_string_lossy()
+                .into_owned(),
+        )
     }
+}
 
-    impl<X> IsPreset for X
-    where
-        X: HasHandle<Handle = ffi::fluid_preset_t>,
-    {
-        fn get_name(&self) -> Option<&str> {
-            let handle = self.get_handle().as_ptr();
-            let font_c = unsafe { &*handle };
-            let get_name = font_c.get_name?;
-            let name = unsafe { (get_name)(handle) };
-            let name = unsafe { CStr::from_ptr(name) };
-            name.to_str().ok()
-        }
+/**
+The summary of preset in SoundFont
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PresetInfo {
+    pub name: String,
+    pub bank: u32,
+    pub num: PresetId,
+}
 
-        fn get_banknum(&self) -> Option<Bank> {
-            let handle = self.get_handle().as_ptr();
-            let preset_c = unsafe { &*handle };
-            let get_banknum = preset_c.get_banknum?;
-            let num = unsafe { (get_banknum)(handle) };
-            if num < 0 {
-                None
-            } else {
-                Some(num as _)
-            }
-        }
+/**
+The iterator over presets of SoundFont
 
-        fn get_num(&self) -> Option<PresetId> {
-            let handle = self.get_handle().as_ptr();
-            let preset_c = unsafe { &*handle };
-            let get_num = preset_c.get_num?;
-            let num = unsafe { (get_num)(handle) };
-            if num < 0 {
-                None
-            } else {
-                Some(num as _)
+The presets are collected on creation, because the library keeps
+the single iteration cursor per font, so the iterators may be nested.
+ */
+pub struct PresetIter<'a> {
+    presets: std::vec::IntoIter<PresetInfo>,
+    font: PhantomData<FontRef<'a>>,
+}
+
+impl<'a> PresetIter<'a> {
+    fn new(font: FontRef<'a>) -> Self {
+        let mut presets = Vec::new();
+        if let Some(handle) = font.as_ptr() {
+            let font_c = unsafe { &*handle.as_ptr() };
+            if let (Some(iteration_start), Some(_)) =
+                (font_c.iteration_start, font_c.iteration_next)
+            {
+                unsafe { (iteration_start)(handle.as_ptr()) };
+                while let Some(preset) = next_preset(handle.as_ptr()) {
+                    presets.push(preset);
+                }
             }
         }
+        Self {
+            presets: presets.into_iter(),
+            font: PhantomData,
+        }
     }
+}
 
-    impl<'a> HasHandle for PresetRef<'a> {
-        type Handle = ffi::fluid_preset_t;
+/// Get the next preset of font iteration
+fn next_preset(handle: *mut ffi::fluid_sfont_t) -> Option<PresetInfo> {
+    let iteration_next = unsafe { &*handle }.iteration_next?;
 
-        fn get_handle(&self) -> NonNull<Self::Handle> {
-            self.handle
-        }
+    // the preset is filled by font and refers to its internal data only
+    let mut preset = unsafe { MaybeUninit::<ffi::fluid_preset_t>::zeroed().assume_init() };
+    preset.sfont = handle;
+
+    if unsafe { (iteration_next)(handle, &mut preset) } == 0 {
+        return None;
+    }
+
+    let preset_ptr: *mut ffi::fluid_preset_t = &mut preset;
+    let name = preset
+        .get_name
+        .and_then(|get_name| name_from_ptr(unsafe { (get_name)(preset_ptr) }))
+        .unwrap_or_default();
+    let bank = preset
+        .get_banknum
+        .map(|get_banknum| unsafe { (get_banknum)(preset_ptr) })
+        .unwrap_or(0);
+    let num = preset
+        .get_num
+        .map(|get_num| unsafe { (get_num)(preset_ptr) })
+        .unwrap_or(0);
+
+    Some(PresetInfo {
+        name,
+        bank: bank.max(0) as _,
+        num: num.max(0) as _,
+    })
+}
+
+impl<'a> Iterator for PresetIter<'a> {
+    type Item = PresetInfo;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.presets.next()
+    }
+}
+
+impl<'a> IsFont for FontRef<'a> {
+    fn get_id(&self) -> FontId {
+        self.id
+    }
+
+    fn get_name(&self) -> Option<String> {
+        let handle = self.as_ptr()?.as_ptr();
+        let get_name = unsafe { &*handle }.get_name?;
+        name_from_ptr(unsafe { (get_name)(handle) })
+    }
+
+    fn get_preset<B: IntoMidi<Bank>>(&self, bank: B, num: PresetId) -> Option<PresetRef<'_>> {
+        let preset = PresetRef {
+            synth: self.synth,
+            font_id: self.id,
+            bank: bank.into_midi().ok()?.get(),
+            num,
+        };
+        // check that preset exists
+        preset.with_preset(|_| ())?;
+        Some(preset)
+    }
+
+    fn preset_iter(&self) -> PresetIter<'_> {
+        PresetIter::new(*self)
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.as_ptr().is_some()
+    }
+}
+
+impl<'a> IsPreset for PresetRef<'a> {
+    fn get_name(&self) -> Option<String> {
+        self.with_preset(|handle| {
+            let get_name = unsafe { &*handle }.get_name?;
+            name_from_ptr(unsafe { (get_name)(handle) })
+        })?
+    }
+
+    fn get_banknum(&self) -> Option<u32> {
+        font_by_id(self.synth, self.font_id).map(|_| self.bank)
+    }
+
+    fn get_num(&self) -> Option<PresetId> {
+        font_by_id(self.synth, self.font_id).map(|_| self.num)
     }
 }
@@ -0,0 +1,505 @@
+use crate::{Bank, Ctrl, Key, Prog, Val};
+
+/**
+General MIDI program names
+
+Indexed by program number.
+ */
+pub const GM_PROGRAM_NAMES: [&str; 128] = [
+    // Piano
+    "Acoustic Grand Piano",
+    "Bright Acoustic Piano",
+    "Electric Grand Piano",
+    "Honky-tonk Piano",
+    "Electric Piano 1",
+    "Electric Piano 2",
+    "Harpsichord",
+    "Clavinet",
+    // Chromatic Percussion
+    "Celesta",
+    "Glockenspiel",
+    "Music Box",
+    "Vibraphone",
+    "Marimba",
+    "Xylophone",
+    "Tubular Bells",
+    "Dulcimer",
+    // Organ
+    "Drawbar Organ",
+    "Percussive Organ",
+    "Rock Organ",
+    "Church Organ",
+    "Reed Organ",
+    "Accordion",
+    "Harmonica",
+    "Tango Accordion",
+    // Guitar
+    "Acoustic Guitar (nylon)",
+    "Acoustic Guitar (steel)",
+    "Electric Guitar (jazz)",
+    "Electric Guitar (clean)",
+    "Electric Guitar (muted)",
+    "Overdriven Guitar",
+    "Distortion Guitar",
+    "Guitar Harmonics",
+    // Bass
+    "Acoustic Bass",
+    "Electric Bass (finger)",
+    "Electric Bass (pick)",
+    "Fretless Bass",
+    "Slap Bass 1",
+    "Slap Bass 2",
+    "Synth Bass 1",
+    "Synth Bass 2",
+    // Strings
+    "Violin",
+    "Viola",
+    "Cello",
+    "Contrabass",
+    "Tremolo Strings",
+    "Pizzicato Strings",
+    "Orchestral Harp",
+    "Timpani",
+    // Ensemble
+    "String Ensemble 1",
+    "String Ensemble 2",
+    "Synth Strings 1",
+    "Synth Strings 2",
+    "Choir Aahs",
+    "Voice Oohs",
+    "Synth Voice",
+    "Orchestra Hit",
+    // Brass
+    "Trumpet",
+    "Trombone",
+    "Tuba",
+    "Muted Trumpet",
+    "French Horn",
+    "Brass Section",
+    "Synth Brass 1",
+    "Synth Brass 2",
+    // Reed
+    "Soprano Sax",
+    "Alto Sax",
+    "Tenor Sax",
+    "Baritone Sax",
+    "Oboe",
+    "English Horn",
+    "Bassoon",
+    "Clarinet",
+    // Pipe
+    "Piccolo",
+    "Flute",
+    "Recorder",
+    "Pan Flute",
+    "Blown Bottle",
+    "Shakuhachi",
+    "Whistle",
+    "Ocarina",
+    // Synth Lead
+    "Lead 1 (square)",
+    "Lead 2 (sawtooth)",
+    "Lead 3 (calliope)",
+    "Lead 4 (chiff)",
+    "Lead 5 (charang)",
+    "Lead 6 (voice)",
+    "Lead 7 (fifths)",
+    "Lead 8 (bass + lead)",
+    // Synth Pad
+    "Pad 1 (new age)",
+    "Pad 2 (warm)",
+    "Pad 3 (polysynth)",
+    "Pad 4 (choir)",
+    "Pad 5 (bowed)",
+    "Pad 6 (metallic)",
+    "Pad 7 (halo)",
+    "Pad 8 (sweep)",
+    // Synth Effects
+    "FX 1 (rain)",
+    "FX 2 (soundtrack)",
+    "FX 3 (crystal)",
+    "FX 4 (atmosphere)",
+    "FX 5 (brightness)",
+    "FX 6 (goblins)",
+    "FX 7 (echoes)",
+    "FX 8 (sci-fi)",
+    // Ethnic
+    "Sitar",
+    "Banjo",
+    "Shamisen",
+    "Koto",
+    "Kalimba",
+    "Bag pipe",
+    "Fiddle",
+    "Shanai",
+    // Percussive
+    "Tinkle Bell",
+    "Agogo",
+    "Steel Drums",
+    "Woodblock",
+    "Taiko Drum",
+    "Melodic Tom",
+    "Synth Drum",
+    "Reverse Cymbal",
+    // Sound Effects
+    "Guitar Fret Noise",
+    "Breath Noise",
+    "Seashore",
+    "Bird Tweet",
+    "Telephone Ring",
+    "Helicopter",
+    "Applause",
+    "Gunshot",
+];
+
+/**
+General MIDI percussion key names
+
+Indexed by key number starting from [`GM_DRUM_FIRST_KEY`].
+ */
+pub const GM_DRUM_NAMES: [&str; 47] = [
+    "Acoustic Bass Drum",
+    "Bass Drum 1",
+    "Side Stick",
+    "Acoustic Snare",
+    "Hand Clap",
+    "Electric Snare",
+    "Low Floor Tom",
+    "Closed Hi-Hat",
+    "High Floor Tom",
+    "Pedal Hi-Hat",
+    "Low Tom",
+    "Open Hi-Hat",
+    "Low-Mid Tom",
+    "Hi-Mid Tom",
+    "Crash Cymbal 1",
+    "High Tom",
+    "Ride Cymbal 1",
+    "Chinese Cymbal",
+    "Ride Bell",
+    "Tambourine",
+    "Splash Cymbal",
+    "Cowbell",
+    "Crash Cymbal 2",
+    "Vibraslap",
+    "Ride Cymbal 2",
+    "Hi Bongo",
+    "Low Bongo",
+    "Mute Hi Conga",
+    "Open Hi Conga",
+    "Low Conga",
+    "High Timbale",
+    "Low Timbale",
+    "High Agogo",
+    "Low Agogo",
+    "Cabasa",
+    "Maracas",
+    "Short Whistle",
+    "Long Whistle",
+    "Short Guiro",
+    "Long Guiro",
+    "Claves",
+    "Hi Wood Block",
+    "Low Wood Block",
+    "Mute Cuica",
+    "Open Cuica",
+    "Mute Triangle",
+    "Open Triangle",
+];
+
+/// The first key of [`GM_DRUM_NAMES`]
+pub const GM_DRUM_FIRST_KEY: Key = Key::saturating(35);
+
+/// The percussion keys below the GM range which are defined by GS and XG
+const GS_DRUM_NAMES_LOW: [&str; 8] = [
+    "High Q",
+    "Slap",
+    "Scratch Push",
+    "Scratch Pull",
+    "Sticks",
+    "Square Click",
+    "Metronome Click",
+    "Metronome Bell",
+];
+
+/// The percussion keys above the GM range which are defined by GS and XG
+const GS_DRUM_NAMES_HIGH: [&str; 6] = [
+    "Shaker",
+    "Jingle Bell",
+    "Bell Tree",
+    "Castanets",
+    "Mute Surdo",
+    "Open Surdo",
+];
+
+/// GS drum kit names by program number
+const GS_DRUM_KITS: [(u8, &str); 9] = [
+    (0, "Standard Kit"),
+    (8, "Room Kit"),
+    (16, "Power Kit"),
+    (24, "Electronic Kit"),
+    (25, "TR-808 Kit"),
+    (32, "Jazz Kit"),
+    (40, "Brush Kit"),
+    (48, "Orchestra Kit"),
+    (56, "SFX Kit"),
+];
+
+/// XG drum kit names by program number
+const XG_DRUM_KITS: [(u8, &str); 9] = [
+    (0, "Standard Kit"),
+    (1, "Standard Kit 2"),
+    (8, "Room Kit"),
+    (16, "Rock Kit"),
+    (24, "Electro Kit"),
+    (25, "Analog Kit"),
+    (32, "Jazz Kit"),
+    (40, "Brush Kit"),
+    (48, "Classic Kit"),
+];
+
+/**
+General MIDI instrument family
+
+Each family covers 8 consecutive programs.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GmFamily {
+    Piano,
+    ChromaticPercussion,
+    Organ,
+    Guitar,
+    Bass,
+    Strings,
+    Ensemble,
+    Brass,
+    Reed,
+    Pipe,
+    SynthLead,
+    SynthPad,
+    SynthEffects,
+    Ethnic,
+    Percussive,
+    SoundEffects,
+}
+
+impl GmFamily {
+    /// All families in order of programs
+    pub const ALL: [Self; 16] = [
+        Self::Piano,
+        Self::ChromaticPercussion,
+        Self::Organ,
+        Self::Guitar,
+        Self::Bass,
+        Self::Strings,
+        Self::Ensemble,
+        Self::Brass,
+        Self::Reed,
+        Self::Pipe,
+        Self::SynthLead,
+        Self::SynthPad,
+        Self::SynthEffects,
+        Self::Ethnic,
+        Self::Percussive,
+        Self::SoundEffects,
+    ];
+
+    /// Get the family name
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Piano => "Piano",
+            Self::ChromaticPercussion => "Chromatic Percussion",
+            Self::Organ => "Organ",
+            Self::Guitar => "Guitar",
+            Self::Bass => "Bass",
+            Self::Strings => "Strings",
+            Self::Ensemble => "Ensemble",
+            Self::Brass => "Brass",
+            Self::Reed => "Reed",
+            Self::Pipe => "Pipe",
+            Self::SynthLead => "Synth Lead",
+            Self::SynthPad => "Synth Pad",
+            Self::SynthEffects => "Synth Effects",
+            Self::Ethnic => "Ethnic",
+            Self::Percussive => "Percussive",
+            Self::SoundEffects => "Sound Effects",
+        }
+    }
+
+    /// Get the programs of family
+    pub fn programs(self) -> impl Iterator<Item = Prog> {
+        let first = self as u32 * 8;
+        (first..first + 8).map(Prog::saturating)
+    }
+}
+
+impl Prog {
+    /// Get the General MIDI name of program
+    pub fn gm_name(self) -> &'static str {
+        GM_PROGRAM_NAMES[self.get() as usize]
+    }
+
+    /// Get the General MIDI family of program
+    pub fn gm_family(self) -> GmFamily {
+        GmFamily::ALL[self.get() as usize / 8]
+    }
+}
+
+impl Key {
+    /// Get the General MIDI percussion name of key
+    pub fn gm_drum_name(self) -> Option<&'static str> {
+        let index = self.get().checked_sub(GM_DRUM_FIRST_KEY.get())?;
+        GM_DRUM_NAMES.get(index as usize).copied()
+    }
+}
+
+/**
+The convention of bank numbers
+
+The basic General MIDI has a single melodic bank, and percussion is
+played on the channel 10. Roland GS and Yamaha XG extend it with
+variation banks selected via bank select controllers.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BankStyle {
+    /// General MIDI: bank select is ignored
+    Gm,
+    /// Roland GS: variations are selected by bank MSB (CC0)
+    #[default]
+    Gs,
+    /// Yamaha XG: variations are selected by bank LSB (CC32), MSB `127` selects drums
+    Xg,
+}
+
+impl BankStyle {
+    /// The bank MSB which selects drum kits in XG
+    pub const XG_DRUM_MSB: u32 = 127;
+
+    /**
+    Check that bank contains drum kits
+
+    The percussion bank [`Bank::DRUMS`] is recognized for every style.
+     */
+    pub fn is_drum_bank(self, bank: Bank) -> bool {
+        bank == Bank::DRUMS || (self == Self::Xg && bank.get() == Self::XG_DRUM_MSB)
+    }
+
+    /**
+    Get the controller values which select a variation bank
+
+    Returns the pairs of bank select MSB and LSB controllers.
+     */
+    pub fn variation_select(self, variation: u8) -> [(Ctrl, Val); 2] {
+        let variation = Val::saturating(variation as _);
+        let (msb, lsb) = match self {
+            Self::Gm => (Val::OFF, Val::OFF),
+            Self::Gs => (variation, Val::OFF),
+            Self::Xg => (Val::OFF, variation),
+        };
+        [(Ctrl::BANK_SELECT_MSB, msb), (Ctrl::BANK_SELECT_LSB, lsb)]
+    }
+
+    /**
+    Get the bank to fall back on when a variation is missing
+
+    GS and XG synths play the capital tone from bank `0` in that case.
+     */
+    pub fn capital_bank(self, bank: Bank) -> Bank {
+        if self.is_drum_bank(bank) {
+            bank
+        } else {
+            Bank::MIN
+        }
+    }
+
+    /// Get the name of percussion key
+    pub fn drum_name(self, key: Key) -> Option<&'static str> {
+        if let Some(name) = key.gm_drum_name() {
+            return Some(name);
+        }
+        if self == Self::Gm {
+            return None;
+        }
+        let key = key.get() as usize;
+        let first = GM_DRUM_FIRST_KEY.get() as usize;
+        let after = first + GM_DRUM_NAMES.len();
+        if key < first {
+            let index = key.checked_sub(first - GS_DRUM_NAMES_LOW.len())?;
+            GS_DRUM_NAMES_LOW.get(index).copied()
+        } else {
+            GS_DRUM_NAMES_HIGH.get(key - after).copied()
+        }
+    }
+
+    /// Get the name of drum kit
+    pub fn drum_kit_name(self, prog: Prog) -> Option<&'static str> {
+        let kits: &[(u8, &str)] = match self {
+            Self::Gm => &GS_DRUM_KITS[..1],
+            Self::Gs => &GS_DRUM_KITS,
+            Self::Xg => &XG_DRUM_KITS,
+        };
+        kits.iter()
+            .find(|(num, _)| prog == *num as u32)
+            .map(|(_, name)| *name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn program_names() {
+        assert_eq!(Prog::MIN.gm_name(), "Acoustic Grand Piano");
+        assert_eq!(Prog::MAX.gm_name(), "Gunshot");
+        assert_eq!(Prog::new(40).unwrap().gm_family(), GmFamily::Strings);
+        assert_eq!(Prog::MAX.gm_family(), GmFamily::SoundEffects);
+        assert_eq!(
+            GmFamily::Brass.programs().map(Prog::gm_name).next(),
+            Some("Trumpet")
+        );
+    }
+
+    #[test]
+    fn drum_names() {
+        assert_eq!(
+            Key::new(35).unwrap().gm_drum_name(),
+            Some("Acoustic Bass Drum")
+        );
+        assert_eq!(Key::new(81).unwrap().gm_drum_name(), Some("Open Triangle"));
+        assert_eq!(Key::new(34).unwrap().gm_drum_name(), None);
+        assert_eq!(Key::new(82).unwrap().gm_drum_name(), None);
+        assert_eq!(BankStyle::Gm.drum_name(Key::new(27).unwrap()), None);
+        assert_eq!(
+            BankStyle::Gs.drum_name(Key::new(27).unwrap()),
+            Some("High Q")
+        );
+        assert_eq!(BankStyle::Gs.drum_name(Key::new(26).unwrap()), None);
+        assert_eq!(
+            BankStyle::Xg.drum_name(Key::new(87).unwrap()),
+            Some("Open Surdo")
+        );
+        assert_eq!(BankStyle::Xg.drum_name(Key::new(88).unwrap()), None);
+        assert_eq!(
+            BankStyle::Gs.drum_kit_name(Prog::new(25).unwrap()),
+            Some("TR-808 Kit")
+        );
+        assert_eq!(BankStyle::Gm.drum_kit_name(Prog::new(25).unwrap()), None);
+    }
+
+    #[test]
+    fn bank_styles() {
+        assert!(BankStyle::Gm.is_drum_bank(Bank::DRUMS));
+        assert!(!BankStyle::Gs.is_drum_bank(Bank::new(127).unwrap()));
+        assert!(BankStyle::Xg.is_drum_bank(Bank::new(127).unwrap()));
+        assert_eq!(BankStyle::Gs.capital_bank(Bank::new(8).unwrap()), Bank::MIN);
+        assert_eq!(BankStyle::Gs.capital_bank(Bank::DRUMS), Bank::DRUMS);
+        assert_eq!(
+            BankStyle::Xg.variation_select(3),
+            [
+                (Ctrl::BANK_SELECT_MSB, Val::OFF),
+                (Ctrl::BANK_SELECT_LSB, Val::new(3).unwrap())
+            ]
+        );
+    }
+}
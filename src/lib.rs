@@ -1,20 +1,46 @@
 #![doc = include_str!("../README.md")]
 
+mod cache;
+pub mod dls;
+mod fileapi;
 mod font;
+mod gm;
 mod loader;
 mod log;
+mod midi;
+/** MIDI Tuning Standard messages */
+pub mod mts;
+mod pitch;
 mod private;
+pub mod rmid;
+mod scala;
 mod settings;
+pub mod sf2;
+pub mod sfz;
 mod synth;
+mod temperament;
+mod tuner;
 mod types;
 mod version;
+#[cfg(feature = "watch")]
+mod watch;
 
+pub use self::cache::*;
+pub use self::fileapi::*;
 pub use self::font::*;
+pub use self::gm::*;
 pub use self::loader::*;
 pub use self::log::*;
+pub use self::midi::*;
+pub use self::pitch::*;
+pub use self::scala::*;
 pub use self::settings::*;
 pub use self::synth::*;
+pub use self::temperament::*;
+pub use self::tuner::*;
 pub use self::types::*;
 pub use self::version::*;
+#[cfg(feature = "watch")]
+pub use self::watch::*;
 
 pub(crate) use fluidlite_sys as ffi;
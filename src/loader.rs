@@ -1,9 +1,13 @@
-use crate::{ffi, result_from_ptr, Result};
+use crate::{ffi, result_from_ptr, Error, Result};
 use std::{
+    cell::RefCell,
+    error::Error as StdError,
     ffi::CStr,
-    io::SeekFrom,
+    fmt::{Display, Formatter, Result as FmtResult},
+    io::{Error as IoError, ErrorKind, Read, Result as IoResult, Seek, SeekFrom},
     mem::MaybeUninit,
     os::raw::{c_char, c_int, c_long, c_void},
+    panic::{catch_unwind, AssertUnwindSafe},
     path::Path,
     ptr::{null_mut, NonNull},
     slice::from_raw_parts_mut,
@@ -14,22 +18,131 @@ The file reading API
 
 Application can provide its own File API to override default.
 For example, this may be useful in cases when soundfonts isn't located in the file system.
+
+The errors are passed to the caller of [`Synth::sfload`](crate::Synth::sfload),
+so the file which isn't served should be reported as [`ErrorKind::NotFound`].
+The panics are caught and reported as errors too.
 */
 pub trait FileApi {
     /// The type for file descriptor
     type File;
 
     /// Open file with specified name
-    fn open(&mut self, filename: &Path) -> Option<Self::File>;
+    fn open(&mut self, filename: &Path) -> IoResult<Self::File>;
 
-    /// Read binary data from file descriptor
-    fn read(file: &mut Self::File, buf: &mut [u8]) -> bool;
+    /// Read binary data from file descriptor (the whole buffer must be filled)
+    fn read(file: &mut Self::File, buf: &mut [u8]) -> IoResult<()>;
 
-    /// Seek current reading position
-    fn seek(file: &mut Self::File, pos: SeekFrom) -> bool;
+    /// Seek current reading position, returns the new position
+    fn seek(file: &mut Self::File, pos: SeekFrom) -> IoResult<u64>;
 
     /// Get current reading position from beginning of file
-    fn tell(file: &mut Self::File) -> Option<u64>;
+    fn tell(file: &mut Self::File) -> IoResult<u64>;
+}
+
+thread_local! {
+    /// The error of file API which caused the loading to fail
+    static FILEAPI_ERROR: RefCell<Option<IoError>> = const { RefCell::new(None) };
+}
+
+/**
+The error of internal file API which doesn't serve the file
+
+It isn't reported, so the error of missing file doesn't depend
+on the internal loaders of synth.
+ */
+#[derive(Debug)]
+pub(crate) struct NotServed;
+
+impl Display for NotServed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        "File isn't served".fmt(f)
+    }
+}
+
+impl StdError for NotServed {}
+
+impl From<NotServed> for IoError {
+    fn from(error: NotServed) -> Self {
+        IoError::new(ErrorKind::NotFound, error)
+    }
+}
+
+/// Keep the error of file API to report it by loading function
+fn set_fileapi_error(error: IoError) {
+    if matches!(error.get_ref(), Some(inner) if inner.is::<NotServed>()) {
+        return;
+    }
+    FILEAPI_ERROR.with(|last| {
+        let mut last = last.borrow_mut();
+        // the other loaders report the files which they don't serve
+        if !matches!(&*last, Some(last) if last.kind() != ErrorKind::NotFound) {
+            *last = Some(error);
+        }
+    });
+}
+
+/// Take the error of file API since the last call
+pub(crate) fn take_fileapi_error() -> Option<IoError> {
+    FILEAPI_ERROR.with(|last| last.borrow_mut().take())
+}
+
+/// Call the file API catching both errors and panics
+fn fileapi_call<T>(func: impl FnOnce() -> IoResult<T>) -> Option<T> {
+    match catch_unwind(AssertUnwindSafe(func)) {
+        Ok(Ok(value)) => Some(value),
+        Ok(Err(error)) => {
+            set_fileapi_error(error);
+            None
+        }
+        Err(_) => {
+            set_fileapi_error(IoError::other("File API panicked"));
+            None
+        }
+    }
+}
+
+/**
+The reader of file opened through [`FileApi`]
+
+Makes the files available to the Rust parsers like
+[`sf2::SoundFont::read`](crate::sf2::SoundFont::read).
+
+Because [`FileApi::read`] reads the whole buffer or fails, reading
+beyond the end of file fails instead of returning the rest of data.
+ */
+pub struct FileReader<F: FileApi> {
+    file: F::File,
+}
+
+impl<F: FileApi> FileReader<F> {
+    /// Open file using file API
+    pub fn open<P: AsRef<Path>>(fileapi: &mut F, filename: P) -> IoResult<Self> {
+        fileapi.open(filename.as_ref()).map(|file| Self { file })
+    }
+}
+
+impl<F: FileApi> Read for FileReader<F> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        F::read(&mut self.file, buf).map(|_| buf.len())
+    }
+}
+
+impl<F: FileApi> Seek for FileReader<F> {
+    fn seek(&mut self, pos: SeekFrom) -> IoResult<u64> {
+        F::seek(&mut self.file, pos)
+    }
+}
+
+/// Read whole file using file API
+pub(crate) fn read_file<F: FileApi>(fileapi: &mut F, path: &Path) -> Result<Vec<u8>> {
+    let mut reader = FileReader::open(fileapi, path).map_err(|error| Error::file(path, error))?;
+    let len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+
+    let mut data = vec![0u8; len as usize];
+    reader.read_exact(&mut data)?;
+    Ok(data)
 }
 
 /**
@@ -112,7 +225,9 @@ fn wrap_fileapi<F: FileApi>(fapi_rs: F) -> *mut ffi::fluid_fileapi_t {
 
 extern "C" fn free_wrapper<F: FileApi>(fapi_c: *mut ffi::fluid_fileapi_t) -> c_int {
     let fapi = unsafe { Box::from_raw(fapi_c) };
-    let _fapi_rs = unsafe { Box::from_raw(fapi.data as *mut F) };
+    let fapi_rs = unsafe { Box::from_raw(fapi.data as *mut F) };
+    // the panic in drop must not unwind into C
+    let _ = catch_unwind(AssertUnwindSafe(move || drop(fapi_rs)));
     ffi::FLUID_OK
 }
 
@@ -131,7 +246,7 @@ extern "C" fn open_wrapper<F: FileApi>(
 
     let filename = Path::new(filename);
 
-    if let Some(handle) = fapi_rs.open(filename) {
+    if let Some(handle) = fileapi_call(|| fapi_rs.open(filename)) {
         Box::into_raw(Box::new(handle)) as _
     } else {
         null_mut()
@@ -147,7 +262,7 @@ extern "C" fn read_wrapper<F: FileApi>(
 
     let buffer: &mut [u8] = unsafe { from_raw_parts_mut(buf as _, count as _) };
 
-    if F::read(handle, buffer) {
+    if fileapi_call(|| F::read(handle, buffer)).is_some() {
         ffi::FLUID_OK
     } else {
         ffi::FLUID_FAILED
@@ -170,7 +285,7 @@ extern "C" fn seek_wrapper<F: FileApi>(
         _ => return ffi::FLUID_FAILED,
     };
 
-    if F::seek(handle, pos) {
+    if fileapi_call(|| F::seek(handle, pos)).is_some() {
         ffi::FLUID_OK
     } else {
         ffi::FLUID_FAILED
@@ -180,7 +295,7 @@ extern "C" fn seek_wrapper<F: FileApi>(
 extern "C" fn tell_wrapper<F: FileApi>(handle: *mut c_void) -> c_long {
     let handle = unsafe { &mut *(handle as *mut F::File) };
 
-    if let Some(pos) = F::tell(handle) {
+    if let Some(pos) = fileapi_call(|| F::tell(handle)) {
         pos as _
     } else {
         ffi::FLUID_FAILED as _
@@ -188,17 +303,19 @@ extern "C" fn tell_wrapper<F: FileApi>(handle: *mut c_void) -> c_long {
 }
 
 extern "C" fn close_wrapper<F: FileApi>(handle: *mut c_void) -> c_int {
-    let _handle = unsafe { Box::from_raw(handle as *mut F::File) };
+    let handle = unsafe { Box::from_raw(handle as *mut F::File) };
+    // the panic in drop must not unwind into C
+    let _ = catch_unwind(AssertUnwindSafe(move || drop(handle)));
 
     ffi::FLUID_OK
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{FileApi, Loader, Settings, Synth};
+    use crate::{Error, FileApi, Loader, Settings, Synth};
     use std::{
         fs::File,
-        io::{Read, Seek, SeekFrom},
+        io::{Error as IoError, ErrorKind, Read, Result as IoResult, Seek, SeekFrom},
         path::Path,
     };
 
@@ -207,20 +324,48 @@ mod test {
     impl FileApi for TestFileApi {
         type File = File;
 
-        fn open(&mut self, filename: &Path) -> Option<Self::File> {
-            File::open(filename).ok()
+        fn open(&mut self, filename: &Path) -> IoResult<Self::File> {
+            File::open(filename)
         }
 
-        fn read(file: &mut Self::File, buf: &mut [u8]) -> bool {
-            file.read(buf).is_ok()
+        fn read(file: &mut Self::File, buf: &mut [u8]) -> IoResult<()> {
+            file.read_exact(buf)
         }
 
-        fn seek(file: &mut Self::File, pos: SeekFrom) -> bool {
-            file.seek(pos).is_ok()
+        fn seek(file: &mut Self::File, pos: SeekFrom) -> IoResult<u64> {
+            file.seek(pos)
         }
 
-        fn tell(file: &mut Self::File) -> Option<u64> {
-            file.seek(SeekFrom::Current(0)).ok()
+        fn tell(file: &mut Self::File) -> IoResult<u64> {
+            file.stream_position()
+        }
+    }
+
+    /// The file API which fails opening or panics
+    struct FailFileApi {
+        panic: bool,
+    }
+
+    impl FileApi for FailFileApi {
+        type File = File;
+
+        fn open(&mut self, _filename: &Path) -> IoResult<Self::File> {
+            if self.panic {
+                panic!("open");
+            }
+            Err(IoError::new(ErrorKind::PermissionDenied, "Denied"))
+        }
+
+        fn read(_file: &mut Self::File, _buf: &mut [u8]) -> IoResult<()> {
+            unreachable!()
+        }
+
+        fn seek(_file: &mut Self::File, _pos: SeekFrom) -> IoResult<u64> {
+            unreachable!()
+        }
+
+        fn tell(_file: &mut Self::File) -> IoResult<u64> {
+            unreachable!()
         }
     }
 
@@ -235,4 +380,36 @@ mod test {
 
         synth.sfload("sf_/Boomwhacker.sf3", true).unwrap();
     }
+
+    #[cfg(feature = "with-lewton")]
+    #[test]
+    fn sfload_sf3() {
+        let settings = Settings::new().unwrap();
+        let synth = Synth::new(settings).unwrap();
+
+        let id = synth
+            .sfload_sf3(&mut TestFileApi, "sf_/Boomwhacker.sf3", true)
+            .unwrap();
+        synth.program_select(0, id, 0, 0).unwrap();
+    }
+
+    #[test]
+    fn fileapi_errors() {
+        for (panic, kind) in [
+            (false, ErrorKind::PermissionDenied),
+            (true, ErrorKind::Other),
+        ] {
+            let synth = Synth::new(Settings::new().unwrap()).unwrap();
+            let loader = Loader::new_default().unwrap();
+
+            loader.set_file_api(FailFileApi { panic });
+            synth.add_sfloader(loader);
+
+            // the default loader fails too, but its error is unknown
+            assert!(matches!(
+                synth.sfload("sf_/NotExists.sf2", true),
+                Err(Error::Io { kind: error_kind, .. }) if error_kind == kind
+            ));
+        }
+    }
 }
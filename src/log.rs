@@ -3,6 +3,7 @@ use std::{
     ffi::{CStr, CString},
     fmt::{Display, Formatter, Result as FmtResult},
     os::raw,
+    panic::{catch_unwind, AssertUnwindSafe},
     ptr::null_mut,
 };
 
@@ -10,6 +11,8 @@ use std::{
  * Logging level
  */
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[repr(u32)]
 pub enum LogLevel {
     /**
@@ -201,6 +204,7 @@ impl Log {
     }
 }
 
+#[allow(clippy::arc_with_non_send_sync)]
 fn with_global_logger(func: impl FnOnce(&mut Option<Log>)) {
     use std::sync::{Arc, Mutex, Once};
 
@@ -305,11 +309,41 @@ extern "C" fn handler<T>(level: raw::c_int, message: *mut raw::c_char, data: *mu
 where
     T: Logger,
 {
-    assert!(!data.is_null());
+    let level = match LogLevel::from_ffi(level) {
+        Some(level) if !data.is_null() && !message.is_null() => level,
+        _ => return,
+    };
 
     let logger = unsafe { &mut *(data as *mut T) };
-    let level = LogLevel::from_ffi(level).unwrap();
-    let message = unsafe { CStr::from_ptr(message).to_str().unwrap() };
+    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+
+    // the panic must not unwind into C
+    let _ = catch_unwind(AssertUnwindSafe(|| logger.log(level, &message)));
+}
+
+#[cfg(test)]
+mod test {
+    use super::handler;
+    use crate::{FnLogger, LogLevel, Logger};
+    use std::{cell::Cell, ffi::CString};
+
+    /// Call the handler like the library does
+    fn log<T: Logger>(logger: &mut T, level: LogLevel, message: &str) {
+        let message = CString::new(message).unwrap();
+        handler::<T>(level as _, message.as_ptr() as _, logger as *mut T as _);
+    }
 
-    logger.log(level, message);
+    #[test]
+    fn logger_panic() {
+        let calls = Cell::new(0);
+        let mut logger = FnLogger::from(|_, message: &str| {
+            calls.set(calls.get() + 1);
+            panic!("{}", message)
+        });
+
+        // the panic doesn't unwind into C
+        log(&mut logger, LogLevel::Error, "Failed");
+        log(&mut logger, LogLevel::Error, "Failed again");
+        assert_eq!(calls.get(), 2);
+    }
 }
@@ -0,0 +1,320 @@
+use crate::{Error, NoteNames, Result};
+use std::{
+    convert::TryFrom,
+    fmt::{Display, Formatter, Result as FmtResult},
+    str::FromStr,
+};
+
+/**
+The trait for values which can be converted into checked MIDI values
+
+All MIDI methods of [`Synth`](crate::Synth) accept both checked values
+like [`Key`] and plain integers which are checked before use.
+ */
+pub trait IntoMidi<T> {
+    fn into_midi(self) -> Result<T>;
+}
+
+impl<T> IntoMidi<T> for T {
+    fn into_midi(self) -> Result<T> {
+        Ok(self)
+    }
+}
+
+macro_rules! midi_value {
+    ($(#[$meta:meta])* $type:ident($repr:ty) max $max:literal, |$val:ident| $error:expr) => {
+        midi_value!($(#[$meta])* $type($repr) max $max, |$val| $error;
+                    u8, u16, u32, u64, usize, i8, i16, i32, i64);
+    };
+
+    ($(#[$meta:meta])* $type:ident($repr:ty) max $max:literal, |$val:ident| $error:expr;
+     $($int:ty),*) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+        #[cfg_attr(
+            feature = "serde",
+            derive(serde::Serialize, serde::Deserialize),
+            serde(try_from = "u32", into = "u32")
+        )]
+        #[repr(transparent)]
+        pub struct $type($repr);
+
+        impl $type {
+            /// The minimum value
+            pub const MIN: Self = Self(0);
+
+            /// The maximum value
+            pub const MAX: Self = Self($max);
+
+            /// Create checked value
+            pub fn new($val: u32) -> Result<Self> {
+                if $val <= $max {
+                    Ok(Self($val as _))
+                } else {
+                    Err($error)
+                }
+            }
+
+            /// Get the raw value
+            pub const fn get(self) -> u32 {
+                self.0 as _
+            }
+
+            /// Create value clamping it to the valid range
+            #[allow(dead_code)]
+            pub(crate) const fn saturating(val: u32) -> Self {
+                Self(if val > $max { $max } else { val } as _)
+            }
+        }
+
+        impl From<$type> for u32 {
+            fn from(value: $type) -> Self {
+                value.get()
+            }
+        }
+
+        impl TryFrom<u32> for $type {
+            type Error = Error;
+
+            fn try_from(value: u32) -> Result<Self> {
+                Self::new(value)
+            }
+        }
+
+        impl PartialEq<u32> for $type {
+            fn eq(&self, other: &u32) -> bool {
+                self.get() == *other
+            }
+        }
+
+        impl Display for $type {
+            fn fmt(&self, f: &mut Formatter) -> FmtResult {
+                self.0.fmt(f)
+            }
+        }
+
+        $(
+            impl IntoMidi<$type> for $int {
+                fn into_midi(self) -> Result<$type> {
+                    // negative values always fail
+                    $type::new(u32::try_from(self).unwrap_or(u32::MAX))
+                }
+            }
+        )*
+    };
+}
+
+midi_value! {
+    /**
+    Channel number (`0..=255`)
+
+    The actual number of channels is checked by synth
+    (see [`Synth::count_midi_channels`](crate::Synth::count_midi_channels)).
+     */
+    Chan(u8) max 255, |chan| Error::InvalidChannel { chan, max: 255 }
+}
+
+midi_value! {
+    /// Key number (`0..=127`)
+    Key(u8) max 127, |key| Error::InvalidKey { key }
+}
+
+midi_value! {
+    /// Velocity value (`0..=127`)
+    Vel(u8) max 127, |vel| Error::InvalidVel { vel }
+}
+
+midi_value! {
+    /// Control number (`0..=127`)
+    Ctrl(u8) max 127, |ctrl| Error::InvalidCtrl { ctrl }
+}
+
+midi_value! {
+    /// Control value (`0..=127`)
+    Val(u8) max 127, |val| Error::InvalidValue { val, max: 127 }
+}
+
+midi_value! {
+    /// Program number (`0..=127`)
+    Prog(u8) max 127, |prog| Error::InvalidProg { prog }
+}
+
+midi_value! {
+    /// Bank number (`0..=16383`)
+    Bank(u16) max 16383, |bank| Error::InvalidBank { bank }
+}
+
+midi_value! {
+    /// Pitch bend value (`0..=16383`, center is `8192`)
+    PitchBend(u16) max 16383, |val| Error::InvalidValue { val, max: 16383 }
+}
+
+impl Key {
+    /// The middle C key (C4)
+    pub const MIDDLE_C: Self = Self(60);
+
+    /// The concert pitch key (A4)
+    pub const A4: Self = Self(69);
+}
+
+impl Key {
+    /**
+    Get the note name of the key like `C4` or `F#3`
+
+    The middle C (`60`) is `C4`. Use [`NoteNames`] for other conventions.
+     */
+    pub fn name(self) -> String {
+        NoteNames::default().format(self)
+    }
+}
+
+impl FromStr for Key {
+    type Err = Error;
+
+    /**
+    Parse key from number or note name like `C4`, `F#3` or `Bb-1`
+
+    The middle C (`60`) is `C4`. Use [`NoteNames`] for other conventions.
+     */
+    fn from_str(s: &str) -> Result<Self> {
+        NoteNames::default().parse(s)
+    }
+}
+
+impl Vel {
+    /// Note off velocity
+    pub const OFF: Self = Self(0);
+}
+
+impl Val {
+    /// Switch off value (`0`)
+    pub const OFF: Self = Self(0);
+
+    /// Switch on value (`127`)
+    pub const ON: Self = Self(127);
+
+    /// Center value (`64`)
+    pub const CENTER: Self = Self(64);
+}
+
+impl Prog {
+    /// Drum kit program (`0`) in bank [`Bank::DRUMS`]
+    pub const STANDARD_DRUMS: Self = Self(0);
+}
+
+impl Bank {
+    /// The percussion bank (`128`)
+    pub const DRUMS: Self = Self(128);
+}
+
+impl PitchBend {
+    /// The center value (no bend)
+    pub const CENTER: Self = Self(8192);
+
+    /// Create pitch bend from signed offset relative to center (`-8192..=8191`)
+    pub fn from_offset(offset: i32) -> Result<Self> {
+        match offset.checked_add(Self::CENTER.get() as i32) {
+            Some(val) if val >= 0 => Self::new(val as _),
+            _ => Err(Error::InvalidValue {
+                val: u32::MAX,
+                max: Self::MAX.get(),
+            }),
+        }
+    }
+
+    /// Get signed offset relative to center
+    pub const fn offset(self) -> i32 {
+        self.0 as i32 - Self::CENTER.0 as i32
+    }
+}
+
+/// Standard MIDI control numbers
+impl Ctrl {
+    pub const BANK_SELECT_MSB: Self = Self(0);
+    pub const MODULATION: Self = Self(1);
+    pub const BREATH: Self = Self(2);
+    pub const FOOT: Self = Self(4);
+    pub const PORTAMENTO_TIME: Self = Self(5);
+    pub const DATA_ENTRY_MSB: Self = Self(6);
+    pub const VOLUME: Self = Self(7);
+    pub const BALANCE: Self = Self(8);
+    pub const PAN: Self = Self(10);
+    pub const EXPRESSION: Self = Self(11);
+    pub const BANK_SELECT_LSB: Self = Self(32);
+    pub const DATA_ENTRY_LSB: Self = Self(38);
+    pub const SUSTAIN: Self = Self(64);
+    pub const PORTAMENTO: Self = Self(65);
+    pub const SOSTENUTO: Self = Self(66);
+    pub const SOFT_PEDAL: Self = Self(67);
+    pub const LEGATO: Self = Self(68);
+    pub const HOLD2: Self = Self(69);
+    pub const REVERB: Self = Self(91);
+    pub const TREMOLO: Self = Self(92);
+    pub const CHORUS: Self = Self(93);
+    pub const CELESTE: Self = Self(94);
+    pub const PHASER: Self = Self(95);
+    pub const DATA_INCREMENT: Self = Self(96);
+    pub const DATA_DECREMENT: Self = Self(97);
+    pub const NRPN_LSB: Self = Self(98);
+    pub const NRPN_MSB: Self = Self(99);
+    pub const RPN_LSB: Self = Self(100);
+    pub const RPN_MSB: Self = Self(101);
+    pub const ALL_SOUND_OFF: Self = Self(120);
+    pub const RESET_ALL_CONTROLLERS: Self = Self(121);
+    pub const LOCAL_CONTROL: Self = Self(122);
+    pub const ALL_NOTES_OFF: Self = Self(123);
+    pub const OMNI_OFF: Self = Self(124);
+    pub const OMNI_ON: Self = Self(125);
+    pub const MONO_ON: Self = Self(126);
+    pub const POLY_ON: Self = Self(127);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn checked_values() {
+        assert_eq!(Key::new(60).unwrap(), Key::MIDDLE_C);
+        assert_eq!(Key::new(128), Err(Error::InvalidKey { key: 128 }));
+        assert_eq!(Vel::MAX.get(), 127);
+        assert_eq!(Ctrl::SUSTAIN, 64);
+        assert_eq!(Bank::new(16384), Err(Error::InvalidBank { bank: 16384 }));
+        assert_eq!(PitchBend::CENTER.offset(), 0);
+        assert_eq!(PitchBend::from_offset(-8192).unwrap(), PitchBend::MIN);
+        assert_eq!(PitchBend::from_offset(8191).unwrap(), PitchBend::MAX);
+        assert!(PitchBend::from_offset(8192).is_err());
+        assert!(PitchBend::from_offset(i32::MAX).is_err());
+    }
+
+    #[test]
+    fn into_midi() {
+        assert_eq!(IntoMidi::<Key>::into_midi(60u8).unwrap(), Key::MIDDLE_C);
+        assert_eq!(IntoMidi::<Key>::into_midi(60i32).unwrap(), Key::MIDDLE_C);
+        assert_eq!(IntoMidi::<Key>::into_midi(60u64).unwrap(), Key::MIDDLE_C);
+        assert_eq!(
+            IntoMidi::<Key>::into_midi(1u64 << 32),
+            Err(Error::InvalidKey { key: u32::MAX })
+        );
+        assert_eq!(
+            IntoMidi::<Key>::into_midi(-1i32),
+            Err(Error::InvalidKey { key: u32::MAX })
+        );
+        assert_eq!(IntoMidi::<Key>::into_midi(Key::A4).unwrap(), 69);
+    }
+
+    #[test]
+    fn key_names() {
+        assert_eq!("C4".parse::<Key>().unwrap(), 60);
+        assert_eq!("C#4".parse::<Key>().unwrap(), 61);
+        assert_eq!("Db4".parse::<Key>().unwrap(), 61);
+        assert_eq!("A4".parse::<Key>().unwrap(), Key::A4);
+        assert_eq!("Bb-1".parse::<Key>().unwrap(), 10);
+        assert_eq!("C-1".parse::<Key>().unwrap(), 0);
+        assert_eq!("G9".parse::<Key>().unwrap(), 127);
+        assert_eq!("64".parse::<Key>().unwrap(), 64);
+        assert!("G#9".parse::<Key>().is_err());
+        assert!("Cb-1".parse::<Key>().is_err());
+        assert!("H2".parse::<Key>().is_err());
+    }
+}
@@ -0,0 +1,531 @@
+use crate::{Bank, Error, Key, Prog, Result};
+use std::convert::TryInto;
+
+/// The device id which addresses all devices
+pub const ALL_DEVICES: u8 = 0x7F;
+
+/// The tuning bank used for octave tunings applied to synth
+pub const OCTAVE_TUNING_BANK: Bank = Bank::saturating(127);
+
+/// The frequency data which means no change
+pub const NO_CHANGE: [u8; 3] = [0x7F, 0x7F, 0x7F];
+
+const SYSEX_START: u8 = 0xF0;
+const SYSEX_END: u8 = 0xF7;
+const NON_REALTIME: u8 = 0x7E;
+const REALTIME: u8 = 0x7F;
+const TUNING: u8 = 0x08;
+
+const BULK_DUMP_REQUEST: u8 = 0x00;
+const BULK_DUMP: u8 = 0x01;
+const NOTE_CHANGE: u8 = 0x02;
+const NOTE_CHANGE_BANK: u8 = 0x07;
+const OCTAVE_1BYTE: u8 = 0x08;
+const OCTAVE_2BYTE: u8 = 0x09;
+
+const NAME_LEN: usize = 16;
+
+/**
+Encode absolute pitch in cents to frequency data
+
+The frequency data is the semitone followed by its 14-bit fraction.
+The pitch is clamped to the representable range.
+ */
+pub fn encode_freq(cents: f64) -> [u8; 3] {
+    let fraction = (cents.max(0.0) / 100.0 * 16384.0).round() as u32;
+    // the highest value is reserved for no change
+    let fraction = fraction.min(0x1F_FFFE);
+    [
+        (fraction >> 14) as u8,
+        ((fraction >> 7) & 0x7F) as u8,
+        (fraction & 0x7F) as u8,
+    ]
+}
+
+/**
+Decode frequency data to absolute pitch in cents
+
+Returns `None` for [`NO_CHANGE`].
+ */
+pub fn decode_freq(data: [u8; 3]) -> Option<f64> {
+    if data == NO_CHANGE {
+        return None;
+    }
+    let fraction = (data[1] as u32 & 0x7F) << 7 | (data[2] as u32 & 0x7F);
+    Some((data[0] & 0x7F) as f64 * 100.0 + fraction as f64 * 100.0 / 16384.0)
+}
+
+/**
+The resolution of scale/octave tuning
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OctaveFormat {
+    /// 1 cent steps from -64 to +63 cents
+    OneByte,
+    /// 14-bit steps from -100 to +100 cents
+    TwoByte,
+}
+
+impl OctaveFormat {
+    fn encode(self, cents: f64, data: &mut Vec<u8>) {
+        match self {
+            OctaveFormat::OneByte => {
+                data.push((cents.round() + 64.0).clamp(0.0, 127.0) as u8);
+            }
+            OctaveFormat::TwoByte => {
+                let value = (cents * 8192.0 / 100.0 + 8192.0)
+                    .round()
+                    .clamp(0.0, 16383.0) as u32;
+                data.push((value >> 7) as u8);
+                data.push((value & 0x7F) as u8);
+            }
+        }
+    }
+
+    fn decode(self, data: &[u8]) -> f64 {
+        match self {
+            OctaveFormat::OneByte => (data[0] & 0x7F) as f64 - 64.0,
+            OctaveFormat::TwoByte => {
+                let value = (data[0] as u32 & 0x7F) << 7 | (data[1] as u32 & 0x7F);
+                (value as f64 - 8192.0) * 100.0 / 8192.0
+            }
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            OctaveFormat::OneByte => 1,
+            OctaveFormat::TwoByte => 2,
+        }
+    }
+}
+
+/**
+The MIDI Tuning Standard message
+
+The pitches use the units of tuning API of synth: the absolute
+pitches in cents for key-based tunings (as in
+[`Synth::create_key_tuning`](crate::Synth::create_key_tuning)) and
+the derivations in cents for octave-based tunings (as in
+[`Synth::create_octave_tuning`](crate::Synth::create_octave_tuning)).
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// Bulk tuning dump request
+    BulkDumpRequest { device: u8, prog: Prog },
+    /// Bulk tuning dump (keys without change get the standard tuning)
+    BulkDump {
+        device: u8,
+        prog: Prog,
+        name: String,
+        pitch: Box<[f64; 128]>,
+    },
+    /**
+    Single note tuning change
+
+    The message without bank is available in real-time only.
+    The keys without change are skipped.
+     */
+    NoteChange {
+        device: u8,
+        realtime: bool,
+        bank: Option<Bank>,
+        prog: Prog,
+        notes: Vec<(Key, f64)>,
+    },
+    /**
+    Scale/octave tuning
+
+    The `channels` is the bit mask of channels `0..16`.
+     */
+    OctaveChange {
+        device: u8,
+        realtime: bool,
+        channels: u16,
+        format: OctaveFormat,
+        derivation: [f64; 12],
+    },
+}
+
+impl Message {
+    /// Encode message to SysEx bytes including start and end markers
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = vec![SYSEX_START];
+
+        match self {
+            Message::BulkDumpRequest { device, prog } => {
+                data.extend(&[NON_REALTIME, *device, TUNING, BULK_DUMP_REQUEST]);
+                data.push(prog.get() as _);
+            }
+            Message::BulkDump {
+                device,
+                prog,
+                name,
+                pitch,
+            } => {
+                data.extend(&[NON_REALTIME, *device, TUNING, BULK_DUMP]);
+                data.push(prog.get() as _);
+                let name = name
+                    .bytes()
+                    .filter(u8::is_ascii)
+                    .chain(std::iter::repeat(b' '));
+                data.extend(name.take(NAME_LEN));
+                for cents in pitch.iter() {
+                    data.extend(&encode_freq(*cents));
+                }
+                data.push(checksum(&data[1..]));
+            }
+            Message::NoteChange {
+                device,
+                realtime,
+                bank,
+                prog,
+                notes,
+            } => match bank {
+                Some(bank) => {
+                    data.extend(&[sub_id(*realtime), *device, TUNING, NOTE_CHANGE_BANK]);
+                    data.push(bank.get().min(0x7F) as _);
+                    encode_notes(&mut data, *prog, notes);
+                }
+                None if *realtime => {
+                    data.extend(&[REALTIME, *device, TUNING, NOTE_CHANGE]);
+                    encode_notes(&mut data, *prog, notes);
+                }
+                None => {
+                    data.extend(&[NON_REALTIME, *device, TUNING, NOTE_CHANGE_BANK, 0]);
+                    encode_notes(&mut data, *prog, notes);
+                }
+            },
+            Message::OctaveChange {
+                device,
+                realtime,
+                channels,
+                format,
+                derivation,
+            } => {
+                let form = match format {
+                    OctaveFormat::OneByte => OCTAVE_1BYTE,
+                    OctaveFormat::TwoByte => OCTAVE_2BYTE,
+                };
+                data.extend(&[sub_id(*realtime), *device, TUNING, form]);
+                data.extend(&[
+                    ((channels >> 14) & 0x03) as u8,
+                    ((channels >> 7) & 0x7F) as u8,
+                    (channels & 0x7F) as u8,
+                ]);
+                for cents in derivation {
+                    format.encode(*cents, &mut data);
+                }
+            }
+        }
+
+        data.push(SYSEX_END);
+        data
+    }
+
+    /**
+    Decode message from SysEx bytes
+
+    The start and end markers are optional.
+     */
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let data = data.strip_prefix(&[SYSEX_START]).unwrap_or(data);
+        let data = data.strip_suffix(&[SYSEX_END]).unwrap_or(data);
+
+        let (header, body) = split(data, 4)?;
+        let realtime = match header[0] {
+            NON_REALTIME => false,
+            REALTIME => true,
+            _ => return Err(invalid("not a universal SysEx message")),
+        };
+        let device = header[1];
+        if header[2] != TUNING {
+            return Err(invalid("not a tuning message"));
+        }
+
+        Ok(match (realtime, header[3]) {
+            (false, BULK_DUMP_REQUEST) => Message::BulkDumpRequest {
+                device,
+                prog: decode_prog(body)?,
+            },
+            (false, BULK_DUMP) => {
+                let (payload, sum) = split(body, 1 + NAME_LEN + 128 * 3)?;
+                let (sum, _) = split(sum, 1)?;
+                if sum[0] != checksum(&data[..header.len() + payload.len()]) {
+                    return Err(invalid("checksum mismatch"));
+                }
+                let (prog, payload) = split(payload, 1)?;
+                let (name, freqs) = split(payload, NAME_LEN)?;
+                let mut pitch = Box::new([0.0; 128]);
+                for (key, (pitch, freq)) in pitch.iter_mut().zip(freqs.chunks(3)).enumerate() {
+                    *pitch = decode_freq(freq.try_into().unwrap()).unwrap_or(key as f64 * 100.0);
+                }
+                Message::BulkDump {
+                    device,
+                    prog: decode_prog(prog)?,
+                    name: string(name),
+                    pitch,
+                }
+            }
+            (true, NOTE_CHANGE) => {
+                let (prog, notes) = decode_notes(body)?;
+                Message::NoteChange {
+                    device,
+                    realtime,
+                    bank: None,
+                    prog,
+                    notes,
+                }
+            }
+            (_, NOTE_CHANGE_BANK) => {
+                let (bank, body) = split(body, 1)?;
+                let (prog, notes) = decode_notes(body)?;
+                Message::NoteChange {
+                    device,
+                    realtime,
+                    bank: Some(Bank::saturating(bank[0] as _)),
+                    prog,
+                    notes,
+                }
+            }
+            (_, OCTAVE_1BYTE) | (_, OCTAVE_2BYTE) => {
+                let format = if header[3] == OCTAVE_1BYTE {
+                    OctaveFormat::OneByte
+                } else {
+                    OctaveFormat::TwoByte
+                };
+                let (mask, values) = split(body, 3)?;
+                let channels = (mask[0] as u16 & 0x03) << 14
+                    | (mask[1] as u16 & 0x7F) << 7
+                    | (mask[2] as u16 & 0x7F);
+                let (values, _) = split(values, 12 * format.size())?;
+                let mut derivation = [0.0; 12];
+                for (cents, value) in derivation.iter_mut().zip(values.chunks(format.size())) {
+                    *cents = format.decode(value);
+                }
+                Message::OctaveChange {
+                    device,
+                    realtime,
+                    channels,
+                    format,
+                    derivation,
+                }
+            }
+            _ => return Err(invalid("unsupported tuning message")),
+        })
+    }
+}
+
+fn sub_id(realtime: bool) -> u8 {
+    if realtime {
+        REALTIME
+    } else {
+        NON_REALTIME
+    }
+}
+
+/// The XOR of all bytes from sub-id to the last data byte
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum ^ byte) & 0x7F
+}
+
+fn invalid(reason: &'static str) -> Error {
+    Error::Format { reason }
+}
+
+fn split(data: &[u8], len: usize) -> Result<(&[u8], &[u8])> {
+    if data.len() < len {
+        Err(invalid("message is too short"))
+    } else {
+        Ok(data.split_at(len))
+    }
+}
+
+fn decode_prog(data: &[u8]) -> Result<Prog> {
+    let (prog, _) = split(data, 1)?;
+    Prog::new(prog[0] as _).map_err(|_| invalid("invalid tuning program"))
+}
+
+/// Decode the name padded with spaces or zeros
+fn string(data: &[u8]) -> String {
+    let len = data.iter().position(|c| *c == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..len]).trim_end().into()
+}
+
+fn encode_notes(data: &mut Vec<u8>, prog: Prog, notes: &[(Key, f64)]) {
+    let notes = &notes[..notes.len().min(0x7F)];
+    data.push(prog.get() as _);
+    data.push(notes.len() as _);
+    for (key, cents) in notes {
+        data.push(key.get() as _);
+        data.extend(&encode_freq(*cents));
+    }
+}
+
+fn decode_notes(data: &[u8]) -> Result<(Prog, Vec<(Key, f64)>)> {
+    let (prog, data) = split(data, 1)?;
+    let (count, data) = split(data, 1)?;
+    let (data, _) = split(data, count[0] as usize * 4)?;
+    let notes = data
+        .chunks(4)
+        .filter_map(|note| {
+            let key = Key::new(note[0] as u32 & 0x7F).ok()?;
+            let cents = decode_freq(note[1..].try_into().unwrap())?;
+            Some((key, cents))
+        })
+        .collect();
+    Ok((decode_prog(prog)?, notes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    macro_rules! assert_eqf {
+        ($a:expr, $b:expr) => {{
+            let (a, b): (f64, f64) = ($a, $b);
+            assert!((a - b).abs() < 0.01, "{} != {}", a, b);
+        }};
+    }
+
+    #[test]
+    fn freq_data() {
+        assert_eq!(encode_freq(6000.0), [60, 0, 0]);
+        assert_eq!(encode_freq(6050.0), [60, 0x40, 0]);
+        assert_eq!(encode_freq(-10.0), [0, 0, 0]);
+        assert_eq!(encode_freq(20000.0), [0x7F, 0x7F, 0x7E]);
+        assert_eq!(decode_freq([60, 0x40, 0]), Some(6050.0));
+        assert_eq!(decode_freq(NO_CHANGE), None);
+        assert_eqf!(decode_freq(encode_freq(6912.34)).unwrap(), 6912.34);
+    }
+
+    #[test]
+    fn note_change() {
+        let message = Message::NoteChange {
+            device: ALL_DEVICES,
+            realtime: true,
+            bank: None,
+            prog: Prog::new(3).unwrap(),
+            notes: vec![(Key::A4, 6910.0)],
+        };
+        let data = message.encode();
+
+        assert_eq!(&data[..8], &[0xF0, 0x7F, 0x7F, 0x08, 0x02, 3, 1, 69]);
+        assert_eq!(*data.last().unwrap(), 0xF7);
+
+        match Message::decode(&data).unwrap() {
+            Message::NoteChange {
+                bank: None, notes, ..
+            } => {
+                assert_eq!(notes[0].0, Key::A4);
+                assert_eqf!(notes[0].1, 6910.0);
+            }
+            message => panic!("unexpected {:?}", message),
+        }
+
+        let message = Message::NoteChange {
+            device: 0,
+            realtime: false,
+            bank: Some(Bank::new(2).unwrap()),
+            prog: Prog::MIN,
+            notes: vec![],
+        };
+
+        assert_eq!(Message::decode(&message.encode()).unwrap(), message);
+    }
+
+    #[test]
+    fn bulk_dump() {
+        let mut pitch = Box::new([0.0; 128]);
+        for (key, pitch) in pitch.iter_mut().enumerate() {
+            *pitch = key as f64 * 100.0 + 25.0;
+        }
+        let message = Message::BulkDump {
+            device: 0x10,
+            prog: Prog::new(5).unwrap(),
+            name: "Shifted".into(),
+            pitch,
+        };
+        let mut data = message.encode();
+
+        assert_eq!(data.len(), 408);
+        assert_eq!(Message::decode(&data).unwrap(), message);
+
+        // the name may be padded with zeros
+        let len = data.len();
+        data[9..22].fill(0);
+        data[len - 2] = checksum(&data[1..len - 2]);
+        match Message::decode(&data).unwrap() {
+            Message::BulkDump { name, .. } => assert_eq!(name, "Shi"),
+            message => panic!("unexpected message {:?}", message),
+        }
+
+        data[30] ^= 1;
+
+        assert_eq!(
+            Message::decode(&data),
+            Err(Error::Format {
+                reason: "checksum mismatch"
+            })
+        );
+    }
+
+    #[test]
+    fn octave_change() {
+        let derivation = [
+            0.0, -25.0, 50.0, -50.0, 25.0, 0.0, -25.0, 50.0, 0.0, 25.0, -25.0, 0.0,
+        ];
+        for format in &[OctaveFormat::OneByte, OctaveFormat::TwoByte] {
+            let message = Message::OctaveChange {
+                device: ALL_DEVICES,
+                realtime: true,
+                channels: 0b1000_0000_0000_0001,
+                format: *format,
+                derivation,
+            };
+            let data = message.encode();
+
+            assert_eq!(&data[5..8], &[0x02, 0x00, 0x01]);
+            assert_eq!(Message::decode(&data).unwrap(), message);
+        }
+
+        assert!(Message::decode(&[0xF0, 0x7E, 0x7F, 0x08, 0x08, 0, 0, 1, 64]).is_err());
+    }
+
+    #[test]
+    fn apply() {
+        use crate::{Settings, Synth};
+
+        let synth = Synth::new(Settings::new().unwrap()).unwrap();
+        let message = Message::NoteChange {
+            device: ALL_DEVICES,
+            realtime: false,
+            bank: Some(Bank::MIN),
+            prog: Prog::MIN,
+            notes: vec![(Key::A4, 6950.0)],
+        };
+
+        synth.create_key_tuning(0, 0, "Test", &[0.0; 128]).unwrap();
+        synth.apply_mts(&message).unwrap();
+
+        assert_eqf!(synth.tuning_dump_pitch(0, 0).unwrap()[69], 6950.0);
+
+        let message = Message::OctaveChange {
+            device: ALL_DEVICES,
+            realtime: true,
+            channels: 0b10,
+            format: OctaveFormat::OneByte,
+            derivation: [10.0; 12],
+        };
+
+        synth.apply_mts(&message).unwrap();
+
+        assert_eq!(
+            synth.tuning_iter().collect::<Vec<_>>(),
+            [
+                (Bank::MIN, Prog::MIN),
+                (OCTAVE_TUNING_BANK, Prog::new(1).unwrap())
+            ]
+        );
+    }
+}
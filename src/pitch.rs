@@ -0,0 +1,271 @@
+use crate::{Error, Key, Result};
+
+/// The frequency of A4 used by synth as the reference of tuning cents
+pub const REFERENCE_A4: f64 = 440.0;
+
+/**
+Convert frequency in Hz to the absolute pitch in cents
+
+This is the pitch scale used by tuning tables of synth
+(see [`Synth::create_key_tuning`](crate::Synth::create_key_tuning)),
+where the key `n` of the standard tuning sounds at `n * 100` cents.
+ */
+pub fn hz_to_cents(hz: f64) -> f64 {
+    Key::A4.get() as f64 * 100.0 + 1200.0 * (hz / REFERENCE_A4).log2()
+}
+
+/**
+Convert absolute pitch in cents to frequency in Hz
+
+See [`hz_to_cents`].
+ */
+pub fn cents_to_hz(cents: f64) -> f64 {
+    REFERENCE_A4 * ((cents - Key::A4.get() as f64 * 100.0) / 1200.0).exp2()
+}
+
+/**
+Convert the table of frequencies in Hz to the table of absolute pitches in cents
+ */
+pub fn hz_to_cents_table<const N: usize>(hz: &[f64; N]) -> [f64; N] {
+    let mut cents = [0.0; N];
+    for (cents, hz) in cents.iter_mut().zip(hz.iter()) {
+        *cents = hz_to_cents(*hz);
+    }
+    cents
+}
+
+/**
+The concert pitch (frequency of A4)
+
+Used to convert between keys and frequencies in the equal temperament.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConcertPitch {
+    /// Frequency of A4 in Hz
+    pub a4: f64,
+}
+
+impl Default for ConcertPitch {
+    fn default() -> Self {
+        Self::new(REFERENCE_A4)
+    }
+}
+
+impl ConcertPitch {
+    pub fn new(a4: f64) -> Self {
+        Self { a4 }
+    }
+
+    /// Get the frequency of key in Hz
+    pub fn key_to_hz(&self, key: Key) -> f64 {
+        self.a4 * ((key.get() as f64 - Key::A4.get() as f64) / 12.0).exp2()
+    }
+
+    /// Get the absolute pitch of key in cents
+    pub fn key_to_cents(&self, key: Key) -> f64 {
+        hz_to_cents(self.key_to_hz(key))
+    }
+
+    /**
+    Get the nearest key to frequency in Hz
+
+    Returns the key with deviation of frequency from it in cents.
+     */
+    pub fn hz_to_key(&self, hz: f64) -> Option<(Key, f64)> {
+        let pitch = Key::A4.get() as f64 + 12.0 * (hz / self.a4).log2();
+        let key = pitch.round();
+        if (0.0..=127.0).contains(&key) {
+            Some((Key::new(key as _).ok()?, (pitch - key) * 100.0))
+        } else {
+            None
+        }
+    }
+
+    /// Get the table of absolute pitches of all keys in cents
+    pub fn cents_table(&self) -> [f64; 128] {
+        let mut cents = [0.0; 128];
+        for (key, cents) in cents.iter_mut().enumerate() {
+            *cents = self.key_to_cents(Key::saturating(key as _));
+        }
+        cents
+    }
+}
+
+const SHARP_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+const FLAT_NAMES: [&str; 12] = [
+    "C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B",
+];
+
+/**
+The convention of note names
+
+Scientific pitch notation names the middle C (key `60`) as `C4`.
+Some vendors use `C3` or `C5` instead.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NoteNames {
+    /// The octave number of middle C
+    pub middle_c_octave: i32,
+    /// Format black keys using flats instead of sharps
+    pub flats: bool,
+}
+
+impl Default for NoteNames {
+    fn default() -> Self {
+        Self::SCIENTIFIC
+    }
+}
+
+impl NoteNames {
+    /// Scientific pitch notation (middle C is `C4`)
+    pub const SCIENTIFIC: Self = Self {
+        middle_c_octave: 4,
+        flats: false,
+    };
+
+    /// Yamaha convention (middle C is `C3`)
+    pub const YAMAHA: Self = Self {
+        middle_c_octave: 3,
+        flats: false,
+    };
+
+    pub fn new(middle_c_octave: i32) -> Self {
+        Self {
+            middle_c_octave,
+            flats: false,
+        }
+    }
+
+    /// Use flats for black keys
+    pub fn with_flats(mut self) -> Self {
+        self.flats = true;
+        self
+    }
+
+    /**
+    Parse key from number or note name like `C4`, `F#3` or `Bb-1`
+
+    Both `#`/`b` and `♯`/`♭` accidentals are supported and may be repeated.
+     */
+    pub fn parse(&self, name: &str) -> Result<Key> {
+        let name = name.trim();
+        if let Ok(key) = name.parse::<u32>() {
+            return Key::new(key);
+        }
+
+        let invalid = Error::InvalidKey { key: u32::MAX };
+        let mut chars = name.chars();
+
+        let step = match chars.next().map(|c| c.to_ascii_uppercase()) {
+            Some('C') => 0,
+            Some('D') => 2,
+            Some('E') => 4,
+            Some('F') => 5,
+            Some('G') => 7,
+            Some('A') => 9,
+            Some('B') => 11,
+            _ => return Err(invalid),
+        };
+
+        let rest = chars.as_str();
+        let accidentals = rest
+            .find(|c: char| c == '-' || c.is_ascii_digit())
+            .ok_or_else(|| invalid.clone())?;
+        let (accidentals, octave) = rest.split_at(accidentals);
+
+        let alter = accidentals.chars().try_fold(0i32, |alter, c| match c {
+            '#' | '♯' => Some(alter + 1),
+            'b' | '♭' => Some(alter - 1),
+            _ => None,
+        });
+        let alter = alter.ok_or_else(|| invalid.clone())?;
+        let octave: i32 = octave.parse().map_err(|_| invalid.clone())?;
+
+        let key = octave
+            .checked_sub(self.middle_c_octave)
+            .and_then(|octave| octave.checked_add(5))
+            .and_then(|octave| octave.checked_mul(12))
+            .and_then(|key| key.checked_add(step + alter))
+            .filter(|key| *key >= 0)
+            .ok_or(invalid)?;
+        Key::new(key as _)
+    }
+
+    /// Format key as note name
+    pub fn format(&self, key: Key) -> String {
+        let key = key.get() as i32;
+        let names = if self.flats {
+            &FLAT_NAMES
+        } else {
+            &SHARP_NAMES
+        };
+        let step = (key % 12) as usize;
+        let octave = key / 12 - 5 + self.middle_c_octave;
+        format!("{}{}", names[step], octave)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    macro_rules! assert_eqf {
+        ($a:expr, $b:expr) => {{
+            let (a, b): (f64, f64) = ($a, $b);
+            assert!((a - b).abs() < 1.0e-6, "{} != {}", a, b);
+        }};
+    }
+
+    #[test]
+    fn note_names() {
+        let names = NoteNames::default();
+
+        assert_eq!(names.parse("C4").unwrap(), Key::MIDDLE_C);
+        assert_eq!(names.parse("C#4").unwrap(), 61);
+        assert_eq!(names.parse("Bb-1").unwrap(), 10);
+        assert_eq!(names.parse("Cbb4").unwrap(), 58);
+        assert!(names.parse("C2000000000").is_err());
+        assert!(names.parse("C-2000000000").is_err());
+        assert_eq!(names.format(Key::MIDDLE_C), "C4");
+        assert_eq!(names.format(Key::new(61).unwrap()), "C#4");
+        assert_eq!(names.format(Key::MIN), "C-1");
+        assert_eq!(names.with_flats().format(Key::new(70).unwrap()), "Bb4");
+
+        let yamaha = NoteNames::YAMAHA;
+
+        assert_eq!(yamaha.parse("C3").unwrap(), Key::MIDDLE_C);
+        assert_eq!(yamaha.parse("C-2").unwrap(), Key::MIN);
+        assert_eq!(yamaha.format(Key::MAX), "G8");
+
+        for key in 0..=127 {
+            let key = Key::new(key).unwrap();
+            assert_eq!(names.parse(&names.format(key)).unwrap(), key);
+            assert_eq!(yamaha.parse(&yamaha.format(key)).unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eqf!(hz_to_cents(440.0), 6900.0);
+        assert_eqf!(hz_to_cents(880.0), 8100.0);
+        assert_eqf!(cents_to_hz(6900.0), 440.0);
+        assert_eqf!(cents_to_hz(hz_to_cents(123.4)), 123.4);
+
+        let pitch = ConcertPitch::default();
+
+        assert_eqf!(pitch.key_to_hz(Key::A4), 440.0);
+        assert_eqf!(pitch.key_to_cents(Key::MIDDLE_C), 6000.0);
+        assert_eq!(pitch.hz_to_key(440.0).unwrap().0, Key::A4);
+
+        let baroque = ConcertPitch::new(415.0);
+
+        assert_eqf!(baroque.key_to_hz(Key::A4), 415.0);
+        let (key, cents) = baroque.hz_to_key(440.0).unwrap();
+        assert_eq!(key, 70);
+        assert!(cents.abs() < 2.0);
+        assert_eqf!(baroque.cents_table()[69], hz_to_cents(415.0));
+    }
+}
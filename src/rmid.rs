@@ -0,0 +1,228 @@
+/*!
+RIFF MIDI files
+
+The reader of RMID files (`.rmi`) which wrap Standard MIDI File data
+with `INFO` metadata and optionally embedded DLS or SoundFont bank.
+
+The embedded bank is loaded by [`Synth::load_rmid_bank`](crate::Synth::load_rmid_bank)
+on top of the font stack, so its presets take priority over presets of
+other fonts. The bank stays loaded while the returned [`RmidBank`] is alive,
+so it should be kept until playback of the file ends.
+
+```no_run
+use fluidlite::{rmid::Rmid, Settings, Synth};
+
+let rmid = Rmid::parse(&std::fs::read("song.rmi").unwrap()).unwrap();
+println!("{}: {} bytes of MIDI", rmid.name().unwrap_or("Untitled"), rmid.midi.len());
+
+let synth = Synth::new(Settings::new().unwrap()).unwrap();
+let bank = synth.load_rmid_bank(&rmid).unwrap();
+
+// play rmid.midi here
+
+// the embedded bank is unloaded
+drop(bank);
+```
+ */
+
+use crate::{
+    dls::{chunks, riff_chunks, Dls},
+    loader::read_file,
+    Error, FileApi, FontId, Result, Synth,
+};
+use std::{collections::BTreeMap, path::Path};
+
+/**
+The bank embedded into RMID file
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmbeddedBank {
+    /// The DLS collection (the whole `RIFF` chunk)
+    Dls(Vec<u8>),
+    /// The SoundFont (the whole `RIFF` chunk)
+    Sf2(Vec<u8>),
+}
+
+/**
+The parsed RMID file
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Rmid {
+    /// The Standard MIDI File data
+    pub midi: Vec<u8>,
+    /// The metadata by `INFO` chunk identifiers (like `INAM` or `ICOP`)
+    pub info: BTreeMap<String, String>,
+    pub bank: Option<EmbeddedBank>,
+    /// The bank offset (`DBNK`) which embedded bank is loaded with
+    pub bank_offset: Option<u16>,
+}
+
+fn invalid(reason: &'static str) -> Error {
+    Error::Format { reason }
+}
+
+impl Rmid {
+    /**
+    Parse RMID file from data
+     */
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let riff = riff_chunks(data, b"RMID", "Not a RMID file")?;
+
+        let mut rmid = Rmid::default();
+        let mut has_midi = false;
+
+        for (id, body) in riff {
+            match &id {
+                b"data" => {
+                    if body.len() < 4 || &body[..4] != b"MThd" {
+                        return Err(invalid("Invalid MIDI data"));
+                    }
+                    rmid.midi = body.to_vec();
+                    has_midi = true;
+                }
+                b"LIST" if body.len() >= 4 && &body[..4] == b"INFO" => {
+                    for (id, value) in chunks(&body[4..])? {
+                        let len = value.iter().position(|c| *c == 0).unwrap_or(value.len());
+                        rmid.info.insert(
+                            String::from_utf8_lossy(&id).into(),
+                            String::from_utf8_lossy(&value[..len]).trim_end().into(),
+                        );
+                    }
+                }
+                b"DBNK" if body.len() >= 2 => {
+                    rmid.bank_offset = Some(u16::from_le_bytes([body[0], body[1]]));
+                }
+                b"RIFF" if body.len() >= 4 => {
+                    // keep the chunk header, so the bank is complete file
+                    let mut bank = b"RIFF".to_vec();
+                    bank.extend_from_slice(&(body.len() as u32).to_le_bytes());
+                    bank.extend_from_slice(body);
+                    rmid.bank = match &body[..4] {
+                        b"DLS " => Some(EmbeddedBank::Dls(bank)),
+                        b"sfbk" => Some(EmbeddedBank::Sf2(bank)),
+                        _ => rmid.bank,
+                    };
+                }
+                _ => (),
+            }
+        }
+
+        if !has_midi {
+            return Err(invalid("Missing MIDI data"));
+        }
+        Ok(rmid)
+    }
+
+    /// Load RMID file using file API
+    pub fn load<F: FileApi, P: AsRef<Path>>(fileapi: &mut F, filename: P) -> Result<Self> {
+        Self::parse(&read_file(fileapi, filename.as_ref())?)
+    }
+
+    /// Get the title (`INAM`)
+    pub fn name(&self) -> Option<&str> {
+        self.info.get("INAM").map(String::as_str)
+    }
+
+    /// Get the bank converted to SoundFont data
+    pub(crate) fn bank_data(&self) -> Result<Option<Vec<u8>>> {
+        Ok(match &self.bank {
+            Some(EmbeddedBank::Sf2(data)) => Some(data.clone()),
+            Some(EmbeddedBank::Dls(data)) => {
+                let (font, data) = Dls::parse(data)?.to_soundfont()?;
+                let mut buffer = Vec::new();
+                font.write(&mut buffer, &data)?;
+                Some(buffer)
+            }
+            None => None,
+        })
+    }
+}
+
+/**
+The embedded bank of RMID file loaded into synth
+
+The bank is unloaded and its data is freed on drop.
+ */
+pub struct RmidBank<'a> {
+    pub(crate) synth: &'a Synth,
+    pub(crate) id: FontId,
+}
+
+impl<'a> RmidBank<'a> {
+    /// Get the identifier of loaded font
+    pub fn id(&self) -> FontId {
+        self.id
+    }
+}
+
+impl<'a> Drop for RmidBank<'a> {
+    fn drop(&mut self) {
+        let _ = self.synth.sfunload(self.id, true);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dls::test::{chunk, dls_data, list};
+
+    /// Make RMID file with embedded DLS
+    fn rmid_data() -> Vec<u8> {
+        let mut midi = b"MThd".to_vec();
+        midi.extend_from_slice(&[0, 0, 0, 6, 0, 0, 0, 1, 0, 96]);
+        let track = [
+            0x00, 0x90, 60, 100, 0x60, 0x80, 60, 0, 0x00, 0xFF, 0x2F, 0x00,
+        ];
+        midi.extend_from_slice(b"MTrk");
+        midi.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        midi.extend_from_slice(&track);
+
+        let mut data = b"RMID".to_vec();
+        data.extend(chunk(b"data", &midi));
+        data.extend(list(
+            b"INFO",
+            &[chunk(b"INAM", b"Song\0"), chunk(b"ICOP", b"Nobody\0")],
+        ));
+        data.extend(chunk(b"DBNK", &2u16.to_le_bytes()));
+        data.extend(dls_data());
+        chunk(b"RIFF", &data)
+    }
+
+    #[test]
+    fn parse_rmid() {
+        let rmid = Rmid::parse(&rmid_data()).unwrap();
+
+        assert_eq!(&rmid.midi[..4], b"MThd");
+        assert_eq!(rmid.name(), Some("Song"));
+        assert_eq!(rmid.info["ICOP"], "Nobody");
+        assert_eq!(rmid.bank_offset, Some(2));
+        assert_eq!(rmid.bank, Some(EmbeddedBank::Dls(dls_data())));
+
+        let bank = rmid.bank_data().unwrap().unwrap();
+        assert_eq!(&bank[8..12], b"sfbk");
+
+        assert!(Rmid::parse(&dls_data()).is_err());
+        assert!(Rmid::parse(b"RIFF\0\0\0\0RMID").is_err());
+    }
+
+    #[test]
+    fn load_bank() {
+        let synth = Synth::new(crate::Settings::new().unwrap()).unwrap();
+        synth.sfload("sf_/Boomwhacker.sf2", true).unwrap();
+
+        let rmid = Rmid::parse(&rmid_data()).unwrap();
+        {
+            let bank = synth.load_rmid_bank(&rmid).unwrap().unwrap();
+
+            assert_eq!(synth.sfcount(), 2);
+            assert_eq!(synth.get_bank_offset(bank.id()).unwrap(), 2);
+
+            synth.program_select(0, bank.id(), 2, 0).unwrap();
+        }
+
+        assert_eq!(synth.sfcount(), 1);
+
+        let rmid = Rmid { bank: None, ..rmid };
+        assert!(synth.load_rmid_bank(&rmid).unwrap().is_none());
+    }
+}
@@ -0,0 +1,531 @@
+use crate::{cents_to_hz, hz_to_cents, Error, Key, Result, REFERENCE_A4};
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    str::FromStr,
+};
+
+/**
+The pitch of Scala scale degree
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScalaPitch {
+    /// The interval in cents
+    Cents(f64),
+    /// The interval as frequency ratio (numerator, denominator)
+    Ratio(u64, u64),
+}
+
+impl ScalaPitch {
+    /// Get the interval in cents
+    pub fn cents(&self) -> f64 {
+        match *self {
+            ScalaPitch::Cents(cents) => cents,
+            ScalaPitch::Ratio(num, den) => 1200.0 * (num as f64 / den as f64).log2(),
+        }
+    }
+}
+
+impl Display for ScalaPitch {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            // the decimal point is required to distinguish cents from ratios
+            ScalaPitch::Cents(cents) => write!(f, "{:.6}", cents),
+            ScalaPitch::Ratio(num, den) => write!(f, "{}/{}", num, den),
+        }
+    }
+}
+
+impl FromStr for ScalaPitch {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.contains('.') {
+            return s
+                .parse()
+                .map(ScalaPitch::Cents)
+                .map_err(|_| "invalid cents value");
+        }
+        let (num, den) = match s.find('/') {
+            Some(pos) => (&s[..pos], &s[pos + 1..]),
+            None => (s, "1"),
+        };
+        let num: u64 = num.trim().parse().map_err(|_| "invalid ratio")?;
+        let den: u64 = den.trim().parse().map_err(|_| "invalid ratio")?;
+        if num == 0 || den == 0 {
+            return Err("invalid ratio");
+        }
+        Ok(ScalaPitch::Ratio(num, den))
+    }
+}
+
+/**
+The Scala scale (`.scl`)
+
+The pitches are the degrees above the implicit unison (`1/1`).
+The last pitch is the period of scale (usually the octave `2/1`).
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scale {
+    pub description: String,
+    pub pitches: Vec<ScalaPitch>,
+}
+
+impl Scale {
+    /// Create the equal temperament scale which divides the octave into `notes` steps
+    pub fn equal_temperament(notes: usize) -> Self {
+        Self {
+            description: format!("{}-tone equal temperament", notes),
+            pitches: (1..=notes)
+                .map(|step| ScalaPitch::Cents(1200.0 * step as f64 / notes as f64))
+                .collect(),
+        }
+    }
+
+    /// Get the period of scale in cents
+    pub fn period(&self) -> f64 {
+        self.pitches.last().map_or(0.0, ScalaPitch::cents)
+    }
+
+    /**
+    Get the interval of scale degree above the unison in cents
+
+    The degrees beyond the scale repeat with its period.
+     */
+    pub fn degree_cents(&self, degree: i32) -> f64 {
+        let notes = self.pitches.len() as i32;
+        if notes == 0 {
+            return 0.0;
+        }
+        let step = degree.rem_euclid(notes);
+        let period = degree.div_euclid(notes);
+        let cents = if step == 0 {
+            0.0
+        } else {
+            self.pitches[step as usize - 1].cents()
+        };
+        period as f64 * self.period() + cents
+    }
+
+    /**
+    Compute the tuning table for all keys
+
+    The result contains absolute pitches in cents as expected by
+    [`Synth::create_key_tuning`](crate::Synth::create_key_tuning).
+    The keys which are not mapped keep the standard tuning.
+     */
+    pub fn key_tuning(&self, mapping: &KeyboardMapping) -> Result<[f64; 128]> {
+        let reference = mapping
+            .key_cents(self, mapping.reference_key)
+            .ok_or(Error::Format {
+                reason: "reference key is not mapped",
+            })?;
+        let reference = hz_to_cents(mapping.reference_freq) - reference;
+
+        let mut pitch = [0.0; 128];
+        for (key, pitch) in pitch.iter_mut().enumerate() {
+            let key = Key::saturating(key as _);
+            *pitch = match mapping.key_cents(self, key) {
+                Some(cents) if key >= mapping.first_key && key <= mapping.last_key => {
+                    reference + cents
+                }
+                _ => key.get() as f64 * 100.0,
+            };
+        }
+        Ok(pitch)
+    }
+
+    /**
+    Create the scale with keyboard mapping from the tuning table
+
+    The table is represented as a single non-repeating scale
+    which maps every key to its own degree.
+     */
+    pub fn from_key_tuning<S: Into<String>>(
+        name: S,
+        pitch: &[f64; 128],
+    ) -> (Self, KeyboardMapping) {
+        let scale = Self {
+            description: name.into(),
+            pitches: pitch[1..]
+                .iter()
+                .map(|cents| ScalaPitch::Cents(cents - pitch[0]))
+                .collect(),
+        };
+        let mapping = KeyboardMapping {
+            middle_key: Key::MIN,
+            reference_key: Key::MIN,
+            reference_freq: cents_to_hz(pitch[0]),
+            ..KeyboardMapping::default()
+        };
+        (scale, mapping)
+    }
+}
+
+impl FromStr for Scale {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut lines = Lines::new(s);
+
+        let (_, description) = lines.next("missing description")?;
+        let (line, notes) = lines.next("missing number of notes")?;
+        let notes: usize = first_word(notes).parse().map_err(|_| Error::Parse {
+            line,
+            reason: "invalid number of notes",
+        })?;
+
+        let pitches = (0..notes)
+            .map(|_| {
+                let (line, pitch) = lines.next("missing pitch")?;
+                first_word(pitch)
+                    .parse()
+                    .map_err(|reason| Error::Parse { line, reason })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            description: description.trim().into(),
+            pitches,
+        })
+    }
+}
+
+impl Display for Scale {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        writeln!(f, "!")?;
+        writeln!(f, "{}", self.description)?;
+        writeln!(f, " {}", self.pitches.len())?;
+        writeln!(f, "!")?;
+        for pitch in &self.pitches {
+            writeln!(f, " {}", pitch)?;
+        }
+        Ok(())
+    }
+}
+
+/**
+The Scala keyboard mapping (`.kbm`)
+
+Maps the keys to the scale degrees. The `mapping` repeats every
+`mapping.len()` keys starting from `middle_key`, which plays the unison.
+The empty mapping means linear mapping of keys to consecutive degrees.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardMapping {
+    /// The first key to retune
+    pub first_key: Key,
+    /// The last key to retune
+    pub last_key: Key,
+    /// The key which plays the first mapped degree
+    pub middle_key: Key,
+    /// The key which frequency is given
+    pub reference_key: Key,
+    /// The frequency of reference key in Hz
+    pub reference_freq: f64,
+    /// The scale degree which is the formal octave of mapping (`0` means the scale period)
+    pub octave_degree: usize,
+    /// The scale degrees of keys (`None` for unmapped)
+    pub mapping: Vec<Option<usize>>,
+}
+
+impl Default for KeyboardMapping {
+    /// The linear mapping with A4 at 440 Hz
+    fn default() -> Self {
+        Self {
+            first_key: Key::MIN,
+            last_key: Key::MAX,
+            middle_key: Key::MIDDLE_C,
+            reference_key: Key::A4,
+            reference_freq: REFERENCE_A4,
+            octave_degree: 0,
+            mapping: Vec::new(),
+        }
+    }
+}
+
+impl KeyboardMapping {
+    /**
+    Get the pitch of key relative to the middle key in cents
+
+    Returns `None` when the key is not mapped.
+     */
+    pub fn key_cents(&self, scale: &Scale, key: Key) -> Option<f64> {
+        let offset = key.get() as i32 - self.middle_key.get() as i32;
+        if self.mapping.is_empty() {
+            return Some(scale.degree_cents(offset));
+        }
+
+        let size = self.mapping.len() as i32;
+        let degree = self.mapping[offset.rem_euclid(size) as usize]?;
+        let octave = if self.octave_degree == 0 {
+            scale.period()
+        } else {
+            scale.degree_cents(self.octave_degree as _)
+        };
+        Some(offset.div_euclid(size) as f64 * octave + scale.degree_cents(degree as _))
+    }
+}
+
+impl FromStr for KeyboardMapping {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut lines = Lines::new(s);
+
+        // the map is never longer than the keyboard
+        let (line, size) = lines.next("missing map size")?;
+        let size = first_word(size)
+            .parse::<usize>()
+            .ok()
+            .filter(|size| *size <= 128)
+            .ok_or(Error::Parse {
+                line,
+                reason: "invalid map size",
+            })?;
+        let mut key = |missing| -> Result<Key> {
+            let (line, value) = lines.next(missing)?;
+            first_word(value)
+                .parse::<u32>()
+                .ok()
+                .and_then(|key| Key::new(key).ok())
+                .ok_or(Error::Parse {
+                    line,
+                    reason: "invalid key",
+                })
+        };
+        let first_key = key("missing first key")?;
+        let last_key = key("missing last key")?;
+        let middle_key = key("missing middle key")?;
+        let reference_key = key("missing reference key")?;
+
+        let (line, freq) = lines.next("missing reference frequency")?;
+        let reference_freq = first_word(freq).parse().map_err(|_| Error::Parse {
+            line,
+            reason: "invalid reference frequency",
+        })?;
+
+        let (line, octave) = lines.next("missing octave degree")?;
+        let octave_degree = first_word(octave).parse().map_err(|_| Error::Parse {
+            line,
+            reason: "invalid octave degree",
+        })?;
+
+        // the missing trailing entries are unmapped
+        let mut mapping = vec![None; size];
+        for entry in mapping.iter_mut() {
+            let (line, degree) = match lines.next("") {
+                Ok(value) => value,
+                Err(_) => break,
+            };
+            let degree = first_word(degree);
+            *entry = if degree.eq_ignore_ascii_case("x") {
+                None
+            } else {
+                Some(degree.parse().map_err(|_| Error::Parse {
+                    line,
+                    reason: "invalid scale degree",
+                })?)
+            };
+        }
+
+        Ok(Self {
+            first_key,
+            last_key,
+            middle_key,
+            reference_key,
+            reference_freq,
+            octave_degree,
+            mapping,
+        })
+    }
+}
+
+impl Display for KeyboardMapping {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        writeln!(f, "! Size of map:")?;
+        writeln!(f, "{}", self.mapping.len())?;
+        writeln!(f, "! First MIDI note number to retune:")?;
+        writeln!(f, "{}", self.first_key)?;
+        writeln!(f, "! Last MIDI note number to retune:")?;
+        writeln!(f, "{}", self.last_key)?;
+        writeln!(
+            f,
+            "! Middle note where the first entry of the mapping is mapped to:"
+        )?;
+        writeln!(f, "{}", self.middle_key)?;
+        writeln!(f, "! Reference note for which frequency is given:")?;
+        writeln!(f, "{}", self.reference_key)?;
+        writeln!(f, "! Frequency to tune the above note to:")?;
+        writeln!(f, "{:.6}", self.reference_freq)?;
+        writeln!(f, "! Scale degree to consider as formal octave:")?;
+        writeln!(f, "{}", self.octave_degree)?;
+        writeln!(f, "! Mapping:")?;
+        for degree in &self.mapping {
+            match degree {
+                Some(degree) => writeln!(f, "{}", degree)?,
+                None => writeln!(f, "x")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The iterator over significant lines of Scala file
+struct Lines<'a> {
+    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+    /// The line after the last one where missing lines are reported
+    end: usize,
+}
+
+impl<'a> Lines<'a> {
+    fn new(s: &'a str) -> Self {
+        Self {
+            lines: s.lines().enumerate(),
+            end: s.lines().count() + 1,
+        }
+    }
+
+    /// Get the next non-comment line with its number
+    fn next(&mut self, missing: &'static str) -> Result<(usize, &'a str)> {
+        self.lines
+            .find(|(_, line)| !line.starts_with('!'))
+            .map(|(index, line)| (index + 1, line))
+            .ok_or(Error::Parse {
+                line: self.end,
+                reason: missing,
+            })
+    }
+}
+
+fn first_word(line: &str) -> &str {
+    line.split_whitespace().next().unwrap_or("")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MEANTONE: &str = "! meanquar.scl
+!
+1/4-comma meantone scale. Pietro Aaron's temperament (1523)
+ 12
+!
+ 76.04900
+ 193.15686
+ 310.26471
+ 5/4
+ 503.42157
+ 579.47057
+ 696.57843
+ 25/16
+ 889.73529
+ 1006.84314
+ 1082.89214
+ 2/1
+";
+
+    const WHITE_KEYS: &str = "! white.kbm
+7
+0
+127
+60
+69
+440.0
+7
+! Mapping:
+0
+x
+1
+";
+
+    macro_rules! assert_eqf {
+        ($a:expr, $b:expr) => {{
+            let (a, b): (f64, f64) = ($a, $b);
+            assert!((a - b).abs() < 1.0e-6, "{} != {}", a, b);
+        }};
+    }
+
+    #[test]
+    fn parse_scale() {
+        let scale: Scale = MEANTONE.parse().unwrap();
+
+        assert_eq!(
+            scale.description,
+            "1/4-comma meantone scale. Pietro Aaron's temperament (1523)"
+        );
+        assert_eq!(scale.pitches.len(), 12);
+        assert_eq!(scale.pitches[0], ScalaPitch::Cents(76.049));
+        assert_eq!(scale.pitches[3], ScalaPitch::Ratio(5, 4));
+        assert_eqf!(scale.period(), 1200.0);
+        assert_eqf!(scale.degree_cents(-12), -1200.0);
+
+        let written: Scale = scale.to_string().parse().unwrap();
+
+        assert_eq!(written.description, scale.description);
+        for (a, b) in written.pitches.iter().zip(&scale.pitches) {
+            assert_eqf!(a.cents(), b.cents());
+        }
+
+        assert!(matches!(
+            "title\n 2\n 100.0\n".parse::<Scale>(),
+            Err(Error::Parse {
+                line: 4,
+                reason: "missing pitch",
+            })
+        ));
+        assert!(matches!(
+            "title\n 1\n 0/1\n".parse::<Scale>(),
+            Err(Error::Parse { line: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn parse_mapping() {
+        let mapping: KeyboardMapping = WHITE_KEYS.parse().unwrap();
+
+        assert_eq!(mapping.middle_key, Key::MIDDLE_C);
+        assert_eq!(mapping.octave_degree, 7);
+        assert_eq!(
+            mapping.mapping,
+            [Some(0), None, Some(1), None, None, None, None]
+        );
+        assert_eq!(
+            mapping.to_string().parse::<KeyboardMapping>().unwrap(),
+            mapping
+        );
+
+        assert!(matches!(
+            "4000000000\n0\n127\n60\n69\n440.0\n12\n".parse::<KeyboardMapping>(),
+            Err(Error::Parse {
+                line: 1,
+                reason: "invalid map size",
+            })
+        ));
+    }
+
+    #[test]
+    fn key_tuning() {
+        let pitch = Scale::equal_temperament(12)
+            .key_tuning(&KeyboardMapping::default())
+            .unwrap();
+
+        for (key, pitch) in pitch.iter().enumerate() {
+            assert_eqf!(*pitch, key as f64 * 100.0);
+        }
+
+        let scale: Scale = MEANTONE.parse().unwrap();
+        let pitch = scale.key_tuning(&KeyboardMapping::default()).unwrap();
+
+        assert_eqf!(pitch[69], 6900.0);
+        assert_eqf!(pitch[81] - pitch[69], 1200.0);
+        assert_eqf!(pitch[64] - pitch[60], 1200.0 * (5.0f64 / 4.0).log2());
+
+        let (scale, mapping) = Scale::from_key_tuning("Meantone", &pitch);
+
+        assert_eq!(scale.key_tuning(&mapping).unwrap().len(), 128);
+        for (a, b) in scale.key_tuning(&mapping).unwrap().iter().zip(&pitch) {
+            assert_eqf!(*a, *b);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{ffi, result_from_ptr, Result};
+use crate::{ffi, result_from_ptr, Error, Result, Status};
 use bitflags::bitflags;
 use std::{
     ffi::{CStr, CString},
@@ -271,6 +271,16 @@ where
     pub fn is_realtime(&self) -> bool {
         0 < unsafe { ffi::fluid_settings_is_realtime(self.handle.as_ptr(), self.name_ptr()) }
     }
+
+    fn status(&self, done: bool) -> Status {
+        if done {
+            Ok(())
+        } else {
+            Err(Error::Setting {
+                name: self.name.to_string_lossy().into(),
+            })
+        }
+    }
 }
 
 impl<'a> Setting<'a, str> {
@@ -291,6 +301,15 @@ impl<'a> Setting<'a, str> {
         }
     }
 
+    /**
+    Set the value of a string setting
+
+    Returns `Error::Setting` if the value cannot be set
+     */
+    pub fn try_set<S: Into<String>>(&self, value: S) -> Status {
+        self.status(self.set(value))
+    }
+
     /**
     Get the value of a string setting
 
@@ -398,6 +417,15 @@ impl<'a> Setting<'a, f64> {
         0 < unsafe { ffi::fluid_settings_setnum(self.handle.as_ptr(), self.name_ptr(), value) }
     }
 
+    /**
+    Set the value of a numeric setting
+
+    Returns `Error::Setting` if the value cannot be set
+     */
+    pub fn try_set(&self, value: f64) -> Status {
+        self.status(self.set(value))
+    }
+
     /**
     Get the value of a numeric setting
 
@@ -454,6 +482,15 @@ impl<'a> Setting<'a, i32> {
         0 < unsafe { ffi::fluid_settings_setint(self.handle.as_ptr(), self.name_ptr(), value) }
     }
 
+    /**
+    Set the value of a integer setting
+
+    Returns `Error::Setting` if the value cannot be set
+     */
+    pub fn try_set(&self, value: i32) -> Status {
+        self.status(self.set(value))
+    }
+
     /**
     Get the value of a integer setting
 
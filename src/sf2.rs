@@ -0,0 +1,342 @@
+/*!
+SoundFont 2 file structure
+
+The pure-Rust parser of SoundFont 2 (and SF3) files which gives access
+to the metadata and the articulation data: presets, instruments and
+samples with their zones, generators and modulators.
+
+The sample data itself is not loaded, only its location in the file
+is recorded. So the compressed samples of SF3 files are never decoded.
+
+```no_run
+use fluidlite::sf2::SoundFont;
+
+let font = SoundFont::load("sf_/Boomwhacker.sf2").unwrap();
+
+for preset in &font.presets {
+    println!("{:03}:{:03} {}", preset.bank, preset.prog, preset.name);
+    for (_zone, instrument) in font.preset_instruments(preset) {
+        println!("  {}", instrument.name);
+    }
+}
+```
+ */
+
+mod read;
+mod sample;
+mod validate;
+mod write;
+
+pub use self::sample::SamplePcm;
+pub use self::validate::*;
+
+use crate::GenParam;
+use bitflags::bitflags;
+
+/**
+The version of file format or ROM
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Version {
+    pub major: u16,
+    pub minor: u16,
+}
+
+/**
+The metadata from `INFO` chunk
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Info {
+    /// The format version (`ifil`), `3` for SF3 files
+    pub version: Version,
+    /// The target sound engine (`isng`)
+    pub sound_engine: String,
+    /// The bank name (`INAM`)
+    pub name: String,
+    /// The sound ROM name (`irom`)
+    pub rom_name: Option<String>,
+    /// The sound ROM version (`iver`)
+    pub rom_version: Option<Version>,
+    /// The creation date (`ICRD`)
+    pub creation_date: Option<String>,
+    /// The sound designers and engineers (`IENG`)
+    pub engineer: Option<String>,
+    /// The intended product (`IPRD`)
+    pub product: Option<String>,
+    /// The copyright message (`ICOP`)
+    pub copyright: Option<String>,
+    /// The comments (`ICMT`)
+    pub comment: Option<String>,
+    /// The tools used to create and modify the file (`ISFT`)
+    pub software: Option<String>,
+}
+
+/**
+The range of keys or velocities
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Range {
+    pub lo: u8,
+    pub hi: u8,
+}
+
+impl Range {
+    /// The full range
+    pub const ALL: Self = Self { lo: 0, hi: 127 };
+
+    /// Check that value is in range
+    pub fn contains(&self, value: u8) -> bool {
+        self.lo <= value && value <= self.hi
+    }
+}
+
+impl Default for Range {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+/**
+The generator record
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Generator {
+    /// The generator number
+    pub oper: u16,
+    /// The raw amount
+    pub amount: i16,
+}
+
+impl Generator {
+    /// Get the generator type when it is known
+    pub fn param(&self) -> Option<GenParam> {
+        GenParam::ALL
+            .iter()
+            .copied()
+            .filter(|param| *param != GenParam::Pitch)
+            .find(|param| *param as u32 == self.oper as u32)
+    }
+
+    /// Get the amount as unsigned value (like an index)
+    pub fn unsigned(&self) -> u16 {
+        self.amount as u16
+    }
+
+    /// Get the amount as range
+    pub fn range(&self) -> Range {
+        let [lo, hi] = self.amount.to_le_bytes();
+        Range { lo, hi }
+    }
+}
+
+/**
+The modulator record
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Modulator {
+    /// The source operator
+    pub src: u16,
+    /// The destination generator number
+    pub dest: u16,
+    /// The modulation amount
+    pub amount: i16,
+    /// The amount source operator
+    pub amount_src: u16,
+    /// The transform operator
+    pub transform: u16,
+}
+
+/**
+The zone of preset or instrument
+
+The preset zones refer to instruments and the instrument zones
+refer to samples by the last generator.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Zone {
+    pub gens: Vec<Generator>,
+    pub mods: Vec<Modulator>,
+}
+
+impl Zone {
+    /// Find generator of type
+    pub fn gen(&self, param: GenParam) -> Option<&Generator> {
+        self.gens.iter().find(|gen| gen.oper as u32 == param as u32)
+    }
+
+    /// Get the key range (when defined)
+    pub fn key_range(&self) -> Option<Range> {
+        self.gen(GenParam::Keyrange).map(Generator::range)
+    }
+
+    /// Get the velocity range (when defined)
+    pub fn vel_range(&self) -> Option<Range> {
+        self.gen(GenParam::Velrange).map(Generator::range)
+    }
+
+    /// Get the instrument index of preset zone
+    pub fn instrument(&self) -> Option<usize> {
+        self.gen(GenParam::Instrument)
+            .map(|gen| gen.unsigned() as usize)
+    }
+
+    /// Get the sample index of instrument zone
+    pub fn sample(&self) -> Option<usize> {
+        self.gen(GenParam::SampleId)
+            .map(|gen| gen.unsigned() as usize)
+    }
+}
+
+/**
+The preset
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Preset {
+    pub name: String,
+    /// The MIDI program number
+    pub prog: u16,
+    /// The MIDI bank number (`128` for percussion)
+    pub bank: u16,
+    pub library: u32,
+    pub genre: u32,
+    pub morphology: u32,
+    /// The zone which applies to all other zones
+    pub global: Option<Zone>,
+    pub zones: Vec<Zone>,
+}
+
+/**
+The instrument
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Instrument {
+    pub name: String,
+    /// The zone which applies to all other zones
+    pub global: Option<Zone>,
+    pub zones: Vec<Zone>,
+}
+
+bitflags! {
+    /**
+    The sample type and channel
+     */
+    #[derive(Default)]
+    pub struct SampleType: u16 {
+        const MONO = 0x0001;
+        const RIGHT = 0x0002;
+        const LEFT = 0x0004;
+        const LINKED = 0x0008;
+        /// The sample is compressed with Ogg Vorbis (SF3)
+        const COMPRESSED = 0x0010;
+        /// The sample is located in sound ROM
+        const ROM = 0x8000;
+    }
+}
+
+/**
+The sample header
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Sample {
+    pub name: String,
+    /**
+    The start and end of sample data
+
+    In sample points, or in bytes of compressed data for SF3.
+     */
+    pub start: u32,
+    pub end: u32,
+    /// The loop points in sample points (relative to decoded data for SF3)
+    pub start_loop: u32,
+    pub end_loop: u32,
+    pub sample_rate: u32,
+    /// The MIDI key recorded
+    pub original_key: u8,
+    /// The pitch correction in cents
+    pub correction: i8,
+    /// The index of linked sample (the other channel of stereo)
+    pub link: u16,
+    pub sample_type: SampleType,
+}
+
+impl Sample {
+    /// Check that sample data is compressed
+    pub fn is_compressed(&self) -> bool {
+        self.sample_type.contains(SampleType::COMPRESSED)
+    }
+
+    /// Check that sample is located in sound ROM
+    pub fn is_rom(&self) -> bool {
+        self.sample_type.contains(SampleType::ROM)
+    }
+}
+
+/**
+The location of chunk data in file
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DataChunk {
+    /// The offset of data from file start
+    pub offset: u64,
+    /// The size of data in bytes
+    pub size: u32,
+}
+
+/**
+The parsed SoundFont file
+
+Use [`SoundFont::load`], [`SoundFont::read`] or [`SoundFont::parse`]
+to get it. The zones refer to instruments and samples by indices, use
+[`SoundFont::preset_instruments`] and [`SoundFont::instrument_samples`]
+to follow them.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SoundFont {
+    pub info: Info,
+    pub presets: Vec<Preset>,
+    pub instruments: Vec<Instrument>,
+    pub samples: Vec<Sample>,
+    /// The 16-bit sample data (`smpl`) or compressed data for SF3
+    pub sample_data: Option<DataChunk>,
+    /// The lower 8 bits of 24-bit sample data (`sm24`)
+    pub sample_data_24: Option<DataChunk>,
+}
+
+impl SoundFont {
+    /// Check that the samples are compressed (SF3)
+    pub fn is_sf3(&self) -> bool {
+        self.info.version.major == 3
+    }
+
+    /// Get the instrument referred by preset zone
+    pub fn instrument(&self, zone: &Zone) -> Option<&Instrument> {
+        self.instruments.get(zone.instrument()?)
+    }
+
+    /// Get the sample referred by instrument zone
+    pub fn sample(&self, zone: &Zone) -> Option<&Sample> {
+        self.samples.get(zone.sample()?)
+    }
+
+    /// Iterate over zones of preset with their instruments
+    pub fn preset_instruments<'a>(
+        &'a self,
+        preset: &'a Preset,
+    ) -> impl Iterator<Item = (&'a Zone, &'a Instrument)> + 'a {
+        preset
+            .zones
+            .iter()
+            .filter_map(move |zone| Some((zone, self.instrument(zone)?)))
+    }
+
+    /// Iterate over zones of instrument with their samples
+    pub fn instrument_samples<'a>(
+        &'a self,
+        instrument: &'a Instrument,
+    ) -> impl Iterator<Item = (&'a Zone, &'a Sample)> + 'a {
+        instrument
+            .zones
+            .iter()
+            .filter_map(move |zone| Some((zone, self.sample(zone)?)))
+    }
+}
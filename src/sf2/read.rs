@@ -0,0 +1,508 @@
+use super::{
+    DataChunk, Generator, Info, Instrument, Modulator, Preset, Sample, SampleType, SoundFont,
+    Version, Zone,
+};
+use crate::{Error, GenParam, Result, Status};
+use std::{
+    fs::File,
+    io::{BufReader, Cursor, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+pub(super) type Tag = [u8; 4];
+
+pub(super) const NAME_LEN: usize = 20;
+pub(super) const PHDR_LEN: usize = 38;
+pub(super) const BAG_LEN: usize = 4;
+pub(super) const MOD_LEN: usize = 10;
+pub(super) const GEN_LEN: usize = 4;
+pub(super) const INST_LEN: usize = 22;
+pub(super) const SHDR_LEN: usize = 46;
+
+fn invalid(reason: &'static str) -> Error {
+    Error::Format { reason }
+}
+
+/// The chunk header
+pub(super) struct Chunk {
+    pub(super) id: Tag,
+    pub(super) size: u32,
+    /// The offset of chunk data
+    pub(super) offset: u64,
+}
+
+impl Chunk {
+    pub(super) fn read<R: Read + Seek>(reader: &mut R) -> Result<Self> {
+        let id = read_tag(reader)?;
+        let mut size = [0u8; 4];
+        reader.read_exact(&mut size)?;
+        Ok(Self {
+            id,
+            size: u32::from_le_bytes(size),
+            offset: reader.stream_position()?,
+        })
+    }
+
+    /// The offset of next chunk (chunks are padded to even size)
+    pub(super) fn end(&self) -> u64 {
+        self.offset + self.size as u64 + (self.size & 1) as u64
+    }
+
+    pub(super) fn data<R: Read + Seek>(&self, reader: &mut R) -> Result<Vec<u8>> {
+        let mut data = vec![0u8; self.size as usize];
+        reader.seek(SeekFrom::Start(self.offset))?;
+        reader.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    /// Call function for each sub-chunk of `LIST` chunk
+    fn for_each<R, F>(&self, reader: &mut R, mut func: F) -> Status
+    where
+        R: Read + Seek,
+        F: FnMut(&mut R, &Chunk) -> Status,
+    {
+        // skip the list type
+        let mut offset = self.offset + 4;
+        let end = self.offset + self.size as u64;
+        while offset + 8 <= end {
+            reader.seek(SeekFrom::Start(offset))?;
+            let chunk = Chunk::read(reader)?;
+            if chunk.offset + chunk.size as u64 > end {
+                return Err(invalid("Chunk exceeds its parent"));
+            }
+            func(reader, &chunk)?;
+            offset = chunk.end();
+        }
+        Ok(())
+    }
+}
+
+pub(super) fn read_tag<R: Read>(reader: &mut R) -> Result<Tag> {
+    let mut tag = [0u8; 4];
+    reader.read_exact(&mut tag)?;
+    Ok(tag)
+}
+
+/// Decode zero-terminated string
+fn string(data: &[u8]) -> String {
+    let len = data.iter().position(|c| *c == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..len]).trim_end().into()
+}
+
+pub(super) fn u16_at(data: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([data[pos], data[pos + 1]])
+}
+
+pub(super) fn u32_at(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
+
+fn version(data: &[u8]) -> Result<Version> {
+    if data.len() != 4 {
+        return Err(invalid("Invalid version chunk size"));
+    }
+    Ok(Version {
+        major: u16_at(data, 0),
+        minor: u16_at(data, 2),
+    })
+}
+
+fn read_info<R: Read + Seek>(reader: &mut R, list: &Chunk) -> Result<Info> {
+    let mut info = Info::default();
+    let mut has_version = false;
+
+    list.for_each(reader, |reader, chunk| {
+        let data = chunk.data(reader)?;
+        match &chunk.id {
+            b"ifil" => {
+                info.version = version(&data)?;
+                has_version = true;
+            }
+            b"isng" => info.sound_engine = string(&data),
+            b"INAM" => info.name = string(&data),
+            b"irom" => info.rom_name = Some(string(&data)),
+            b"iver" => info.rom_version = Some(version(&data)?),
+            b"ICRD" => info.creation_date = Some(string(&data)),
+            b"IENG" => info.engineer = Some(string(&data)),
+            b"IPRD" => info.product = Some(string(&data)),
+            b"ICOP" => info.copyright = Some(string(&data)),
+            b"ICMT" => info.comment = Some(string(&data)),
+            b"ISFT" => info.software = Some(string(&data)),
+            _ => {}
+        }
+        Ok(())
+    })?;
+
+    if !has_version {
+        return Err(invalid("Missing version chunk"));
+    }
+    Ok(info)
+}
+
+/// Split chunk data into records checking the terminal record
+fn records<'a>(
+    data: &'a Option<Vec<u8>>,
+    len: usize,
+    missing: &'static str,
+) -> Result<Vec<&'a [u8]>> {
+    let data = data.as_deref().ok_or_else(|| invalid(missing))?;
+    if data.len() % len != 0 {
+        return Err(invalid("Invalid preset data chunk size"));
+    }
+    if data.is_empty() {
+        return Err(invalid("Missing terminal record"));
+    }
+    Ok(data.chunks(len).collect())
+}
+
+/// The raw preset data (`pdta` chunk)
+#[derive(Default)]
+pub(super) struct Hydra {
+    phdr: Option<Vec<u8>>,
+    pbag: Option<Vec<u8>>,
+    pmod: Option<Vec<u8>>,
+    pgen: Option<Vec<u8>>,
+    inst: Option<Vec<u8>>,
+    ibag: Option<Vec<u8>>,
+    imod: Option<Vec<u8>>,
+    igen: Option<Vec<u8>>,
+    shdr: Option<Vec<u8>>,
+}
+
+impl Hydra {
+    fn read<R: Read + Seek>(reader: &mut R, list: &Chunk) -> Result<Self> {
+        let mut hydra = Self::default();
+        list.for_each(reader, |reader, chunk| {
+            let field = match &chunk.id {
+                b"phdr" => &mut hydra.phdr,
+                b"pbag" => &mut hydra.pbag,
+                b"pmod" => &mut hydra.pmod,
+                b"pgen" => &mut hydra.pgen,
+                b"inst" => &mut hydra.inst,
+                b"ibag" => &mut hydra.ibag,
+                b"imod" => &mut hydra.imod,
+                b"igen" => &mut hydra.igen,
+                b"shdr" => &mut hydra.shdr,
+                _ => return Ok(()),
+            };
+            *field = Some(chunk.data(reader)?);
+            Ok(())
+        })?;
+        Ok(hydra)
+    }
+
+    /// The sub-chunks with their record sizes
+    pub(super) const CHUNKS: [(&'static Tag, usize); 9] = [
+        (b"phdr", PHDR_LEN),
+        (b"pbag", BAG_LEN),
+        (b"pmod", MOD_LEN),
+        (b"pgen", GEN_LEN),
+        (b"inst", INST_LEN),
+        (b"ibag", BAG_LEN),
+        (b"imod", MOD_LEN),
+        (b"igen", GEN_LEN),
+        (b"shdr", SHDR_LEN),
+    ];
+
+    fn build(&self, font: &mut SoundFont) -> Status {
+        let pbags = bags(&self.pbag, "Missing pbag chunk")?;
+        let pmods = mods(&self.pmod, "Missing pmod chunk")?;
+        let pgens = gens(&self.pgen, "Missing pgen chunk")?;
+        let phdrs = records(&self.phdr, PHDR_LEN, "Missing phdr chunk")?;
+
+        font.presets = phdrs
+            .windows(2)
+            .map(|phdr| {
+                let (global, zones) = zones(
+                    &pbags,
+                    u16_at(phdr[0], 24) as usize..u16_at(phdr[1], 24) as usize,
+                    &pgens,
+                    &pmods,
+                    GenParam::Instrument,
+                )?;
+                Ok(Preset {
+                    name: string(&phdr[0][..NAME_LEN]),
+                    prog: u16_at(phdr[0], 20),
+                    bank: u16_at(phdr[0], 22),
+                    library: u32_at(phdr[0], 26),
+                    genre: u32_at(phdr[0], 30),
+                    morphology: u32_at(phdr[0], 34),
+                    global,
+                    zones,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let ibags = bags(&self.ibag, "Missing ibag chunk")?;
+        let imods = mods(&self.imod, "Missing imod chunk")?;
+        let igens = gens(&self.igen, "Missing igen chunk")?;
+        let insts = records(&self.inst, INST_LEN, "Missing inst chunk")?;
+
+        font.instruments = insts
+            .windows(2)
+            .map(|inst| {
+                let (global, zones) = zones(
+                    &ibags,
+                    u16_at(inst[0], 20) as usize..u16_at(inst[1], 20) as usize,
+                    &igens,
+                    &imods,
+                    GenParam::SampleId,
+                )?;
+                Ok(Instrument {
+                    name: string(&inst[0][..NAME_LEN]),
+                    global,
+                    zones,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let shdrs = records(&self.shdr, SHDR_LEN, "Missing shdr chunk")?;
+
+        font.samples = shdrs[..shdrs.len() - 1]
+            .iter()
+            .map(|shdr| Sample {
+                name: string(&shdr[..NAME_LEN]),
+                start: u32_at(shdr, 20),
+                end: u32_at(shdr, 24),
+                start_loop: u32_at(shdr, 28),
+                end_loop: u32_at(shdr, 32),
+                sample_rate: u32_at(shdr, 36),
+                original_key: shdr[40],
+                correction: shdr[41] as i8,
+                link: u16_at(shdr, 42),
+                sample_type: SampleType::from_bits_truncate(u16_at(shdr, 44)),
+            })
+            .collect();
+
+        Ok(())
+    }
+}
+
+/// Decode bags as (generator index, modulator index) including terminal
+fn bags(data: &Option<Vec<u8>>, missing: &'static str) -> Result<Vec<(usize, usize)>> {
+    Ok(records(data, BAG_LEN, missing)?
+        .iter()
+        .map(|bag| (u16_at(bag, 0) as usize, u16_at(bag, 2) as usize))
+        .collect())
+}
+
+/// Decode modulators excluding terminal
+fn mods(data: &Option<Vec<u8>>, missing: &'static str) -> Result<Vec<Modulator>> {
+    // some editors omit the terminal record when there is no modulators
+    if matches!(data.as_deref(), Some([])) {
+        return Ok(Vec::new());
+    }
+    let mods = records(data, MOD_LEN, missing)?;
+    Ok(mods[..mods.len() - 1]
+        .iter()
+        .map(|data| Modulator {
+            src: u16_at(data, 0),
+            dest: u16_at(data, 2),
+            amount: u16_at(data, 4) as i16,
+            amount_src: u16_at(data, 6),
+            transform: u16_at(data, 8),
+        })
+        .collect())
+}
+
+/// Decode generators excluding terminal
+fn gens(data: &Option<Vec<u8>>, missing: &'static str) -> Result<Vec<Generator>> {
+    let gens = records(data, GEN_LEN, missing)?;
+    Ok(gens[..gens.len() - 1]
+        .iter()
+        .map(|data| Generator {
+            oper: u16_at(data, 0),
+            amount: u16_at(data, 2) as i16,
+        })
+        .collect())
+}
+
+/// Collect zones of bags range splitting the global zone
+fn zones(
+    bags: &[(usize, usize)],
+    range: std::ops::Range<usize>,
+    gens: &[Generator],
+    mods: &[Modulator],
+    link: GenParam,
+) -> Result<(Option<Zone>, Vec<Zone>)> {
+    if range.start > range.end || range.end >= bags.len() {
+        return Err(invalid("Invalid bag index"));
+    }
+
+    let mut global = None;
+    let mut zones = Vec::with_capacity(range.len());
+
+    for bag in range.clone() {
+        let ((gen_start, mod_start), (gen_end, mod_end)) = (bags[bag], bags[bag + 1]);
+        if gen_start > gen_end || gen_end > gens.len() {
+            return Err(invalid("Invalid generator index"));
+        }
+        if mod_start > mod_end || mod_end > mods.len() {
+            return Err(invalid("Invalid modulator index"));
+        }
+        let zone = Zone {
+            gens: gens[gen_start..gen_end].to_vec(),
+            mods: mods[mod_start..mod_end].to_vec(),
+        };
+        if bag == range.start && zone.gen(link).is_none() {
+            global = Some(zone);
+        } else {
+            zones.push(zone);
+        }
+    }
+
+    Ok((global, zones))
+}
+
+/**
+Parsing
+ */
+impl SoundFont {
+    /// Load SoundFont from file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Parse SoundFont from data in memory
+    pub fn parse<D: AsRef<[u8]>>(data: D) -> Result<Self> {
+        Self::read(&mut Cursor::new(data.as_ref()))
+    }
+
+    /**
+    Read SoundFont from the stream
+
+    The sample data is skipped, so only its location is recorded.
+     */
+    pub fn read<R: Read + Seek>(reader: &mut R) -> Result<Self> {
+        let start = reader.stream_position()?;
+        let len = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(start))?;
+
+        let mut riff = Chunk::read(reader)?;
+        if &riff.id != b"RIFF" || &read_tag(reader)? != b"sfbk" {
+            return Err(invalid("Not a SoundFont file"));
+        }
+        // the sub-chunks are checked against parent, so the data never exceeds stream
+        riff.size = riff.size.min(len.saturating_sub(riff.offset) as u32);
+
+        let mut font = Self::default();
+        let mut has_info = false;
+        let mut hydra = None;
+
+        riff.for_each(reader, |reader, chunk| {
+            if &chunk.id != b"LIST" {
+                return Ok(());
+            }
+            match &read_tag(reader)? {
+                b"INFO" => {
+                    font.info = read_info(reader, chunk)?;
+                    has_info = true;
+                }
+                b"sdta" => chunk.for_each(reader, |_, chunk| {
+                    let data = DataChunk {
+                        offset: chunk.offset,
+                        size: chunk.size,
+                    };
+                    match &chunk.id {
+                        b"smpl" => font.sample_data = Some(data),
+                        b"sm24" => font.sample_data_24 = Some(data),
+                        _ => {}
+                    }
+                    Ok(())
+                })?,
+                b"pdta" => hydra = Some(Hydra::read(reader, chunk)?),
+                _ => {}
+            }
+            Ok(())
+        })?;
+
+        if !has_info {
+            return Err(invalid("Missing INFO chunk"));
+        }
+        hydra
+            .ok_or_else(|| invalid("Missing pdta chunk"))?
+            .build(&mut font)?;
+
+        Ok(font)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::*;
+    use crate::{Error, GenParam};
+
+    fn check_boomwhacker(font: &SoundFont) {
+        assert_eq!(font.info.name, "Boomwhacker");
+        assert_eq!(font.info.sound_engine, "EMU8000");
+        assert_eq!(font.info.software.as_deref(), Some("Polyphone"));
+
+        assert_eq!(font.presets.len(), 1);
+        let preset = &font.presets[0];
+        assert_eq!(preset.name, "Boomwhacker");
+        assert_eq!((preset.bank, preset.prog), (0, 0));
+        assert_eq!(preset.zones.len(), 1);
+        assert_eq!(preset.zones[0].key_range(), Some(Range::ALL));
+
+        let instruments = font.preset_instruments(preset).collect::<Vec<_>>();
+        assert_eq!(instruments.len(), 1);
+        assert_eq!(instruments[0].1.name, "Boomwhacker");
+
+        let samples = font
+            .instrument_samples(instruments[0].1)
+            .collect::<Vec<_>>();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].0.gen(GenParam::Pan).unwrap().amount, -500);
+        assert_eq!(samples[0].1.name, "BoomwhackerL");
+        assert_eq!(samples[0].1.original_key, 60);
+        assert_eq!(samples[0].1.link, 1);
+        assert!(samples[0].1.sample_type.contains(SampleType::LEFT));
+        assert!(samples[1].1.sample_type.contains(SampleType::RIGHT));
+    }
+
+    #[test]
+    fn parse_sf2() {
+        let font = SoundFont::load("sf_/Boomwhacker.sf2").unwrap();
+
+        check_boomwhacker(&font);
+        assert!(!font.is_sf3());
+        assert_eq!(font.info.version, Version { major: 2, minor: 1 });
+        assert_eq!(font.sample_data.unwrap().size, 301796);
+        assert_eq!(font.samples[1].end, 150852);
+        assert!(!font.samples[0].is_compressed());
+    }
+
+    #[test]
+    fn parse_sf3() {
+        let data = std::fs::read("sf_/Boomwhacker.sf3").unwrap();
+        let font = SoundFont::parse(&data).unwrap();
+
+        check_boomwhacker(&font);
+        assert!(font.is_sf3());
+        assert!(font.samples.iter().all(Sample::is_compressed));
+        assert_eq!(font.samples[0].end_loop, 75403);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(matches!(
+            SoundFont::parse(b"RIFF\x04\x00\x00\x00WAVE"),
+            Err(Error::Format { .. })
+        ));
+
+        let mut data = std::fs::read("sf_/Boomwhacker.sf2").unwrap();
+        data.truncate(data.len() - 10);
+        assert!(SoundFont::parse(&data).is_err());
+
+        // the sizes beyond the end of data aren't allocated
+        assert!(matches!(
+            SoundFont::parse(b"RIFF\x10\x00\x00\x00sfbkLIST\xff\xff\xff\x7f"),
+            Err(Error::Format { .. })
+        ));
+
+        assert!(matches!(
+            SoundFont::load("sf_/missing.sf2"),
+            Err(Error::Io { .. })
+        ));
+    }
+}
@@ -0,0 +1,723 @@
+use super::{
+    read::{u16_at, u32_at},
+    SampleType, SoundFont, Version,
+};
+use crate::{Error, Result};
+use std::{
+    collections::HashSet,
+    convert::TryFrom,
+    fs::File,
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+/// The first version which supports 24-bit samples
+const SM24_VERSION: super::Version = super::Version { major: 2, minor: 4 };
+
+/// The unity note used when original key is unspecified
+const DEFAULT_KEY: u8 = 60;
+
+/**
+The decoded sample data
+
+Stereo pairs of samples are interleaved to single stereo sample.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SamplePcm {
+    pub name: String,
+    pub channels: u16,
+    /// The bits per sample point (`16` or `24`)
+    pub bits: u16,
+    pub sample_rate: u32,
+    /// The interleaved sample points
+    pub data: Vec<i32>,
+    /// The MIDI key recorded
+    pub root_key: u8,
+    /// The pitch correction in cents
+    pub correction: i8,
+    /// The loop start and end in frames (end is exclusive)
+    pub loop_points: Option<(u32, u32)>,
+}
+
+impl SamplePcm {
+    /// Get the number of frames
+    pub fn frames(&self) -> usize {
+        self.data.len() / self.channels.max(1) as usize
+    }
+
+    /**
+    Write sample as WAV file
+
+    The root key, pitch correction and loop points are stored in `smpl` chunk.
+     */
+    pub fn write_wav<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let bytes = self.bits as u32 / 8;
+        let block_align = bytes * self.channels as u32;
+        let loops = self.loop_points.iter().count() as u32;
+        let smpl_len = 36 + loops * 24;
+        let too_large = || invalid("Sample is too large for WAV file");
+        let data_len =
+            u32::try_from(self.data.len() as u64 * bytes as u64).map_err(|_| too_large())?;
+        let byte_rate = u32::try_from(self.sample_rate as u64 * block_align as u64)
+            .map_err(|_| invalid("Invalid sample rate"))?;
+        // the padding of odd-sized data
+        let pad = data_len & 1;
+        let riff_len = (4 + (8 + 16) + (8 + smpl_len) + 8) as u64 + data_len as u64 + pad as u64;
+        let riff_len = u32::try_from(riff_len).map_err(|_| too_large())?;
+
+        writer.write_all(b"RIFF")?;
+        write_u32(writer, riff_len)?;
+        writer.write_all(b"WAVE")?;
+
+        writer.write_all(b"fmt ")?;
+        write_u32(writer, 16)?;
+        // PCM format
+        write_u16(writer, 1)?;
+        write_u16(writer, self.channels)?;
+        write_u32(writer, self.sample_rate)?;
+        write_u32(writer, byte_rate)?;
+        write_u16(writer, block_align as u16)?;
+        write_u16(writer, self.bits)?;
+
+        // the correction is applied on playback, so the recorded pitch is opposite
+        let cents = -(self.correction as i32);
+        let (key, cents) = if cents >= 0 {
+            (self.root_key as u32, cents)
+        } else if self.root_key > 0 {
+            (self.root_key as u32 - 1, cents + 100)
+        } else {
+            // the pitch below key 0 can't be stored
+            (0, 0)
+        };
+
+        writer.write_all(b"smpl")?;
+        write_u32(writer, smpl_len)?;
+        // manufacturer and product
+        write_u32(writer, 0)?;
+        write_u32(writer, 0)?;
+        // sample period in nanoseconds
+        write_u32(writer, 1_000_000_000 / self.sample_rate.max(1))?;
+        write_u32(writer, key)?;
+        // the fraction of semitone
+        write_u32(writer, (((cents as u64) << 32) / 100) as u32)?;
+        // SMPTE format and offset
+        write_u32(writer, 0)?;
+        write_u32(writer, 0)?;
+        write_u32(writer, loops)?;
+        // sampler data
+        write_u32(writer, 0)?;
+        if let Some((start, end)) = self.loop_points {
+            // cue point id, forward loop
+            write_u32(writer, 0)?;
+            write_u32(writer, 0)?;
+            write_u32(writer, start)?;
+            // the end is inclusive
+            write_u32(writer, end.saturating_sub(1))?;
+            // fraction and infinite play count
+            write_u32(writer, 0)?;
+            write_u32(writer, 0)?;
+        }
+
+        writer.write_all(b"data")?;
+        write_u32(writer, data_len)?;
+        for point in &self.data {
+            writer.write_all(&point.to_le_bytes()[..bytes as usize])?;
+        }
+        if pad != 0 {
+            writer.write_all(&[0])?;
+        }
+
+        Ok(())
+    }
+
+    /**
+    Read sample from WAV file
+
+    The PCM data of 8, 16, 24 and 32 bits and the float data are supported.
+    The 8 bits data is widened to 16 bits and the 32 bits and float data
+    is narrowed to 24 bits. The root key, pitch correction and first loop
+    are read from `smpl` chunk when present.
+     */
+    pub fn read_wav<R: Read + Seek>(reader: &mut R) -> Result<Self> {
+        let mut header = [0u8; 12];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != b"RIFF" || &header[8..] != b"WAVE" {
+            return Err(invalid("Not a WAV file"));
+        }
+        let len = reader.seek(SeekFrom::End(0))?;
+        let end = (u32_at(&header, 4) as u64 + 8).min(len);
+
+        let mut fmt = None;
+        let mut data = None;
+        let mut smpl = None;
+        let mut offset = 12;
+
+        while offset + 8 <= end {
+            let mut chunk = [0u8; 8];
+            reader.seek(SeekFrom::Start(offset))?;
+            if reader.read_exact(&mut chunk).is_err() {
+                break;
+            }
+            let size = u32_at(&chunk, 4) as usize;
+            match &chunk[..4] {
+                b"fmt " => fmt = Some(read_bytes(reader, offset + 8, size, len)?),
+                b"data" => data = Some(read_bytes(reader, offset + 8, size, len)?),
+                b"smpl" if size >= 36 => smpl = Some(read_bytes(reader, offset + 8, size, len)?),
+                _ => (),
+            }
+            offset += 8 + size as u64 + (size as u64 & 1);
+        }
+
+        let fmt = fmt.ok_or_else(|| invalid("Missing WAV format"))?;
+        let data = data.ok_or_else(|| invalid("Missing WAV data"))?;
+        let mut pcm = Self::from_wave_chunks(&fmt, &data)?;
+
+        if let Some(smpl) = smpl {
+            let key = u32_at(&smpl, 12);
+            let cents = ((u32_at(&smpl, 16) as u64 * 100) >> 32) as i8;
+            if key < 128 {
+                pcm.root_key = key as u8;
+                pcm.correction = -cents;
+            }
+            if u32_at(&smpl, 28) > 0 && smpl.len() >= 60 {
+                // the end is inclusive
+                let frames = pcm.frames() as u32;
+                let start = u32_at(&smpl, 44);
+                pcm.loop_points = u32_at(&smpl, 48)
+                    .checked_add(1)
+                    .map(|end| (start, end))
+                    .filter(|(start, end)| start < end && *end <= frames);
+            }
+        }
+
+        Ok(pcm)
+    }
+
+    /**
+    Decode sample from the data of `fmt ` and `data` chunks
+
+    The WAV files and DLS waves share these chunks.
+     */
+    pub(crate) fn from_wave_chunks(fmt: &[u8], data: &[u8]) -> Result<Self> {
+        if fmt.len() < 16 {
+            return Err(invalid("Invalid WAV format"));
+        }
+        let mut tag = u16_at(fmt, 0);
+        if tag == WAVE_FORMAT_EXTENSIBLE && fmt.len() >= 26 {
+            // the format of sub-type GUID
+            tag = u16_at(fmt, 24);
+        }
+        let channels = u16_at(fmt, 2);
+        if channels == 0 {
+            return Err(invalid("Invalid WAV format"));
+        }
+
+        let (bits, data) = match (tag, u16_at(fmt, 14)) {
+            (WAVE_FORMAT_PCM, 8) => (16, data.iter().map(|b| (*b as i32 - 128) << 8).collect()),
+            (WAVE_FORMAT_PCM, 16) => (
+                16,
+                data.chunks_exact(2)
+                    .map(|p| i16::from_le_bytes([p[0], p[1]]) as i32)
+                    .collect(),
+            ),
+            (WAVE_FORMAT_PCM, 24) => (
+                24,
+                data.chunks_exact(3)
+                    .map(|p| i32::from_le_bytes([0, p[0], p[1], p[2]]) >> 8)
+                    .collect(),
+            ),
+            (WAVE_FORMAT_PCM, 32) => (
+                24,
+                data.chunks_exact(4)
+                    .map(|p| i32::from_le_bytes([p[0], p[1], p[2], p[3]]) >> 8)
+                    .collect(),
+            ),
+            (WAVE_FORMAT_FLOAT, 32) => (
+                24,
+                data.chunks_exact(4)
+                    .map(|p| float_point(f32::from_le_bytes([p[0], p[1], p[2], p[3]]) as f64))
+                    .collect(),
+            ),
+            (WAVE_FORMAT_FLOAT, 64) => (
+                24,
+                data.chunks_exact(8)
+                    .map(|p| {
+                        let mut bytes = [0u8; 8];
+                        bytes.copy_from_slice(p);
+                        float_point(f64::from_le_bytes(bytes))
+                    })
+                    .collect(),
+            ),
+            _ => return Err(invalid("Unsupported WAV format")),
+        };
+
+        Ok(SamplePcm {
+            channels,
+            bits,
+            sample_rate: u32_at(fmt, 4),
+            data,
+            root_key: DEFAULT_KEY,
+            ..Default::default()
+        })
+    }
+
+    /// Extract single channel as mono sample
+    pub fn channel(&self, channel: u16) -> SamplePcm {
+        let channels = self.channels.max(1) as usize;
+        SamplePcm {
+            channels: 1,
+            data: self
+                .data
+                .iter()
+                .skip(channel as usize)
+                .step_by(channels)
+                .copied()
+                .collect(),
+            name: self.name.clone(),
+            loop_points: self.loop_points,
+            ..*self
+        }
+    }
+}
+
+/// The PCM data format
+const WAVE_FORMAT_PCM: u16 = 1;
+/// The IEEE float data format
+const WAVE_FORMAT_FLOAT: u16 = 3;
+/// The format which is defined by sub-type
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+fn float_point(value: f64) -> i32 {
+    (value.clamp(-1.0, 1.0) * 0x7F_FFFF as f64) as i32
+}
+
+fn write_u16<W: Write>(writer: &mut W, value: u16) -> Result<()> {
+    Ok(writer.write_all(&value.to_le_bytes())?)
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> Result<()> {
+    Ok(writer.write_all(&value.to_le_bytes())?)
+}
+
+/// Read the chunk data which must fit into the stream of `stream_len` bytes
+fn read_bytes<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+    len: usize,
+    stream_len: u64,
+) -> Result<Vec<u8>> {
+    if offset + len as u64 > stream_len {
+        return Err(invalid("Chunk exceeds file"));
+    }
+    let mut data = vec![0u8; len];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut data)?;
+    Ok(data)
+}
+
+fn invalid(reason: &'static str) -> Error {
+    Error::Format { reason }
+}
+
+/**
+Decode Ogg Vorbis stream of SF3 sample
+
+Uses the pure-Rust _lewton_ decoder built with `with-lewton` feature.
+ */
+#[cfg(feature = "with-lewton")]
+fn decode_vorbis(data: &[u8]) -> Result<Vec<i16>> {
+    use lewton::inside_ogg::OggStreamReader;
+
+    let mut reader = OggStreamReader::new(std::io::Cursor::new(data))
+        .map_err(|_| invalid("Invalid Vorbis data"))?;
+    let channels = (reader.ident_hdr.audio_channels as usize).max(1);
+    let mut pcm = Vec::new();
+    while let Some(points) = reader
+        .read_dec_packet_itl()
+        .map_err(|_| invalid("Invalid Vorbis data"))?
+    {
+        // SF3 samples are mono, so take the first channel only
+        pcm.extend(points.into_iter().step_by(channels));
+    }
+    Ok(pcm)
+}
+
+/**
+Decode Ogg Vorbis stream of SF3 sample
+
+Uses the _stb_vorbis_ decoder of the bundled C library
+built with `with-sf3` and `with-stb` features.
+ */
+#[cfg(not(feature = "with-lewton"))]
+fn decode_vorbis(data: &[u8]) -> Result<Vec<i16>> {
+    if !crate::ffi::HAS_STB_VORBIS {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Decoding of compressed samples requires with-lewton or with-sf3 and with-stb features",
+        )
+        .into());
+    }
+    let (points, channels) =
+        crate::ffi::stb_vorbis_decode(data).ok_or_else(|| invalid("Invalid Vorbis data"))?;
+    // SF3 samples are mono, so take the first channel only
+    Ok(points.into_iter().step_by(channels).collect())
+}
+
+/**
+Sample data
+ */
+impl SoundFont {
+    /**
+    Read the data of single sample
+
+    The data is read from the same file which font was parsed from.
+    The 24-bit data is used when the font has `sm24` chunk.
+     */
+    pub fn read_sample<R: Read + Seek>(&self, reader: &mut R, index: usize) -> Result<SamplePcm> {
+        let sample = self
+            .samples
+            .get(index)
+            .ok_or_else(|| invalid("Invalid sample index"))?;
+        if sample.is_rom() {
+            return Err(invalid("ROM samples have no data"));
+        }
+        let smpl = self
+            .sample_data
+            .ok_or_else(|| invalid("Missing sample data"))?;
+        // the compressed samples are addressed in bytes
+        let point_size = if sample.is_compressed() { 1 } else { 2 };
+        if sample.end < sample.start || sample.end as u64 * point_size > smpl.size as u64 {
+            return Err(invalid("Sample is outside of sample data"));
+        }
+        let len = (sample.end - sample.start) as usize;
+        let stream_len = reader.seek(SeekFrom::End(0))?;
+
+        let (bits, data, loop_offset) = if sample.is_compressed() {
+            let data = read_bytes(reader, smpl.offset + sample.start as u64, len, stream_len)?;
+            let data = decode_vorbis(&data)?;
+            // the loop points are relative to decoded data
+            (16, data.into_iter().map(i32::from).collect(), 0)
+        } else {
+            let data = read_bytes(
+                reader,
+                smpl.offset + sample.start as u64 * 2,
+                len * 2,
+                stream_len,
+            )?;
+            let mut data = data
+                .chunks(2)
+                .map(|point| i16::from_le_bytes([point[0], point[1]]) as i32)
+                .collect::<Vec<_>>();
+
+            let sm24 = self
+                .sample_data_24
+                .filter(|sm24| self.info.version >= SM24_VERSION && sm24.size >= smpl.size / 2);
+            let bits = if let Some(sm24) = sm24 {
+                let low = read_bytes(reader, sm24.offset + sample.start as u64, len, stream_len)?;
+                for (point, low) in data.iter_mut().zip(low) {
+                    *point = *point << 8 | low as i32;
+                }
+                24
+            } else {
+                16
+            };
+            (bits, data, sample.start)
+        };
+
+        let loop_points = Some((
+            sample.start_loop.wrapping_sub(loop_offset),
+            sample.end_loop.wrapping_sub(loop_offset),
+        ))
+        .filter(|(start, end)| start < end && *end as usize <= data.len());
+
+        Ok(SamplePcm {
+            name: sample.name.clone(),
+            channels: 1,
+            bits,
+            sample_rate: sample.sample_rate,
+            data,
+            root_key: if sample.original_key > 127 {
+                DEFAULT_KEY
+            } else {
+                sample.original_key
+            },
+            correction: sample.correction,
+            loop_points,
+        })
+    }
+
+    /// Get the right channel of stereo sample which is linked both ways
+    fn stereo_pair(&self, index: usize) -> Option<usize> {
+        let sample = &self.samples[index];
+        let link = sample.link as usize;
+        let other = self.samples.get(link)?;
+        if sample.sample_type.contains(SampleType::LEFT)
+            && other.sample_type.contains(SampleType::RIGHT)
+            && other.link as usize == index
+        {
+            Some(link)
+        } else {
+            None
+        }
+    }
+
+    /**
+    Read the stereo sample from the left channel sample
+
+    The mono sample is returned when sample isn't linked.
+     */
+    pub fn read_stereo_sample<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        index: usize,
+    ) -> Result<SamplePcm> {
+        let mut left = self.read_sample(reader, index)?;
+        let right = match self.stereo_pair(index) {
+            Some(right) => self.read_sample(reader, right)?,
+            None => return Ok(left),
+        };
+
+        let frames = left.data.len().max(right.data.len());
+        let bits = left.bits.max(right.bits);
+        let scale = |sample: &SamplePcm, point: i32| point << (bits - sample.bits);
+
+        let mut data = Vec::with_capacity(frames * 2);
+        for frame in 0..frames {
+            data.push(scale(&left, left.data.get(frame).copied().unwrap_or(0)));
+            data.push(scale(&right, right.data.get(frame).copied().unwrap_or(0)));
+        }
+
+        left.channels = 2;
+        left.bits = bits;
+        left.data = data;
+        Ok(left)
+    }
+
+    /**
+    Decode all samples of font
+
+    Returns the copy of font which samples aren't compressed with the
+    sample data, so SF3 font can be written as SF2 (see [`SoundFont::write`]).
+    The compressed samples are decoded in Rust with `with-lewton` feature
+    or by the bundled C library with `with-sf3` and `with-stb` features.
+     */
+    pub fn decompress<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+    ) -> Result<(SoundFont, Vec<SamplePcm>)> {
+        let data = (0..self.samples.len())
+            .map(|index| self.read_sample(reader, index))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut font = self.clone();
+        for sample in &mut font.samples {
+            sample.sample_type.remove(SampleType::COMPRESSED);
+        }
+        font.sample_data = None;
+        font.sample_data_24 = None;
+        if font.info.version.major > 2 {
+            font.info.version = Version { major: 2, minor: 1 };
+        }
+        Ok((font, data))
+    }
+
+    /**
+    Export all samples to WAV files in directory
+
+    The linked stereo samples are merged into stereo files.
+    The samples in ROM are skipped. Returns the paths of written files.
+
+    The compressed samples of SF3 font are decoded with `with-lewton` feature
+    or with `with-sf3` and `with-stb` features (the bundled C library only),
+    otherwise the export of SF3 font fails.
+
+    ```no_run
+    use fluidlite::sf2::SoundFont;
+    use std::{fs::File, io::BufReader};
+
+    let mut reader = BufReader::new(File::open("sf_/Boomwhacker.sf2").unwrap());
+    let font = SoundFont::read(&mut reader).unwrap();
+
+    for path in font.export_wav(&mut reader, "samples").unwrap() {
+        println!("{}", path.display());
+    }
+    ```
+     */
+    pub fn export_wav<R, P>(&self, reader: &mut R, dir: P) -> Result<Vec<PathBuf>>
+    where
+        R: Read + Seek,
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+
+        let merged = (0..self.samples.len())
+            .filter_map(|index| self.stereo_pair(index))
+            .collect::<HashSet<_>>();
+        let mut names = HashSet::new();
+        let mut paths = Vec::new();
+
+        for (index, sample) in self.samples.iter().enumerate() {
+            if sample.is_rom() || merged.contains(&index) {
+                continue;
+            }
+            let pcm = self.read_stereo_sample(reader, index)?;
+
+            let mut name = file_name(&sample.name);
+            if !names.insert(name.clone()) {
+                name = format!("{}_{}", name, index);
+                names.insert(name.clone());
+            }
+            let path = dir.join(name).with_extension("wav");
+
+            let mut writer = BufWriter::new(File::create(&path)?);
+            pcm.write_wav(&mut writer)?;
+            writer.flush()?;
+            paths.push(path);
+        }
+
+        Ok(paths)
+    }
+}
+
+/// Replace characters which aren't safe in file names
+fn file_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if name.trim().is_empty() {
+        "sample".into()
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{convert::TryInto, io::Cursor};
+
+    #[test]
+    fn read_stereo() {
+        let data = std::fs::read("sf_/Boomwhacker.sf2").unwrap();
+        let mut reader = Cursor::new(&data);
+        let font = SoundFont::read(&mut reader).unwrap();
+
+        let left = font.read_sample(&mut reader, 0).unwrap();
+
+        assert_eq!(left.channels, 1);
+        assert_eq!(left.bits, 16);
+        assert_eq!(left.frames(), 75403);
+        assert_eq!(left.root_key, 60);
+
+        let stereo = font.read_stereo_sample(&mut reader, 0).unwrap();
+
+        assert_eq!(stereo.channels, 2);
+        assert_eq!(stereo.frames(), 75403);
+        assert_eq!(stereo.data[0], left.data[0]);
+    }
+
+    #[cfg(feature = "with-lewton")]
+    #[test]
+    fn decompress_sf3() {
+        let data = std::fs::read("sf_/Boomwhacker.sf3").unwrap();
+        let mut reader = Cursor::new(&data);
+        let (font, data) = SoundFont::read(&mut reader)
+            .unwrap()
+            .decompress(&mut reader)
+            .unwrap();
+
+        assert!(!font.is_sf3());
+        assert!(!font.samples[0].is_compressed());
+        assert_eq!(data.len(), 2);
+        assert_eq!(data[0].bits, 16);
+        assert!(data[0].frames() >= 75403);
+        assert_eq!(data[0].loop_points.map(|(_, end)| end), Some(75403));
+
+        let mut buffer = Vec::new();
+        font.write(&mut buffer, &data).unwrap();
+        assert!(!SoundFont::parse(&buffer).unwrap().is_sf3());
+    }
+
+    #[test]
+    fn read_broken_wav() {
+        let pcm = SamplePcm {
+            channels: 1,
+            bits: 16,
+            sample_rate: 44100,
+            data: vec![0, 1000, -1000],
+            loop_points: Some((1, 3)),
+            ..Default::default()
+        };
+        let mut wav = Vec::new();
+        pcm.write_wav(&mut wav).unwrap();
+
+        // the loop end which overflows is ignored
+        let smpl = wav.windows(4).position(|id| id == b"smpl").unwrap() + 8;
+        wav[smpl + 48..smpl + 52].copy_from_slice(&u32::MAX.to_le_bytes());
+        let read = SamplePcm::read_wav(&mut Cursor::new(&wav)).unwrap();
+        assert_eq!(read.data, pcm.data);
+        assert_eq!(read.loop_points, None);
+
+        // the data size which exceeds file isn't allocated
+        let data = wav.windows(4).position(|id| id == b"data").unwrap() + 4;
+        wav[data..data + 4].copy_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+        assert!(SamplePcm::read_wav(&mut Cursor::new(&wav)).is_err());
+    }
+
+    #[test]
+    fn write_wav() {
+        let pcm = SamplePcm {
+            name: "Test".into(),
+            channels: 1,
+            bits: 16,
+            sample_rate: 44100,
+            data: vec![0, 1000, -1000],
+            root_key: 69,
+            correction: -50,
+            loop_points: Some((1, 3)),
+        };
+        let mut wav = Vec::new();
+        pcm.write_wav(&mut wav).unwrap();
+
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(
+            u32::from_le_bytes(wav[4..8].try_into().unwrap()) as usize,
+            wav.len() - 8
+        );
+        let smpl = wav.windows(4).position(|id| id == b"smpl").unwrap() + 8;
+        // unity note and pitch fraction of 50 cents
+        assert_eq!(wav[smpl + 12], 69);
+        assert_eq!(&wav[smpl + 16..smpl + 20], &0x8000_0000u32.to_le_bytes());
+        // the inclusive loop end
+        assert_eq!(wav[smpl + 44], 1);
+        assert_eq!(wav[smpl + 48], 2);
+
+        let data = wav.windows(4).position(|id| id == b"data").unwrap() + 8;
+        assert_eq!(&wav[data + 2..data + 4], &1000i16.to_le_bytes());
+
+        // the pitch below key 0 is clamped
+        let low = SamplePcm {
+            root_key: 0,
+            correction: 50,
+            ..pcm.clone()
+        };
+        let mut wav = Vec::new();
+        low.write_wav(&mut wav).unwrap();
+        let smpl = wav.windows(4).position(|id| id == b"smpl").unwrap() + 8;
+        assert_eq!(&wav[smpl + 12..smpl + 20], &[0; 8]);
+
+        let fast = SamplePcm {
+            sample_rate: u32::MAX,
+            ..pcm
+        };
+        assert!(fast.write_wav(&mut Vec::new()).is_err());
+    }
+}
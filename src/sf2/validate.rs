@@ -0,0 +1,1037 @@
+use super::{
+    read::{u16_at, Chunk, Hydra, Tag},
+    Generator, Range, SampleType, SoundFont, Zone,
+};
+use crate::{GenParam, Result};
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    fs::File,
+    io::{BufReader, Cursor, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// The maximum length of INFO strings
+const INFO_MAX_LEN: u32 = 256;
+
+/// The generators which aren't allowed in preset zones
+const INSTRUMENT_ONLY: [GenParam; 13] = [
+    GenParam::StartAddrOfs,
+    GenParam::EndAddrOfs,
+    GenParam::StartLoopAddOfs,
+    GenParam::EndLoopAddrOfs,
+    GenParam::StartAddrCoarseOfs,
+    GenParam::EndAddrCoarseOfs,
+    GenParam::Startloopaddrcoarseofs,
+    GenParam::EndLoopAddrCoarseOfs,
+    GenParam::Keynum,
+    GenParam::Velocity,
+    GenParam::SampleMode,
+    GenParam::ExclusiveClass,
+    GenParam::OverrideRootKey,
+];
+
+/// The valid ranges of generator amounts in instrument zones
+const GEN_RANGES: [(GenParam, i16, i16); 40] = [
+    (GenParam::ModLfoToPitch, -12000, 12000),
+    (GenParam::VibLfoToPitch, -12000, 12000),
+    (GenParam::ModEnvToPitch, -12000, 12000),
+    (GenParam::FilterFc, 1500, 13500),
+    (GenParam::FilterQ, 0, 960),
+    (GenParam::ModLfoToFilterFc, -12000, 12000),
+    (GenParam::ModEnvToFilterFc, -12000, 12000),
+    (GenParam::ModLfoToVol, -960, 960),
+    (GenParam::ChorussEnd, 0, 1000),
+    (GenParam::ReverbsEnd, 0, 1000),
+    (GenParam::Pan, -500, 500),
+    (GenParam::ModLfoDelay, -12000, 5000),
+    (GenParam::ModLfoFreq, -16000, 4500),
+    (GenParam::Viblfodelay, -12000, 5000),
+    (GenParam::VibLfoFreq, -16000, 4500),
+    (GenParam::ModEnvDelay, -12000, 5000),
+    (GenParam::ModEnvAttack, -12000, 8000),
+    (GenParam::ModEnvHold, -12000, 5000),
+    (GenParam::ModEnvDecay, -12000, 8000),
+    (GenParam::ModEnvSustain, 0, 1000),
+    (GenParam::ModEnvRelease, -12000, 8000),
+    (GenParam::KeyToModEnvHold, -1200, 1200),
+    (GenParam::KeyToModEnvDecay, -1200, 1200),
+    (GenParam::VolEnvDelay, -12000, 5000),
+    (GenParam::VolEnvAttack, -12000, 8000),
+    (GenParam::VolEnvHold, -12000, 5000),
+    (GenParam::VolEnvDecay, -12000, 8000),
+    (GenParam::VolEnvSustain, 0, 1440),
+    (GenParam::VolEnvRelease, -12000, 8000),
+    (GenParam::KeyToVolEnvHold, -1200, 1200),
+    (GenParam::KeyToVolEnvDecay, -1200, 1200),
+    (GenParam::Keynum, -1, 127),
+    (GenParam::Velocity, -1, 127),
+    (GenParam::Attenuation, 0, 1440),
+    (GenParam::CoarseTune, -120, 120),
+    (GenParam::FineTune, -99, 99),
+    (GenParam::SampleMode, 0, 3),
+    (GenParam::ScaleTune, 0, 1200),
+    (GenParam::ExclusiveClass, 0, 127),
+    (GenParam::OverrideRootKey, -1, 127),
+];
+
+/**
+The severity of diagnostic
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    /// Notable but valid content (like layered zones)
+    Info,
+    /// The content which is likely to sound wrong
+    Warning,
+    /// The file cannot be loaded or the data will be ignored
+    Error,
+}
+
+/**
+The location of diagnostic in file
+
+The indices are the positions in [`SoundFont`] lists. The zone index
+is `None` for the global zone.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Location {
+    File,
+    Chunk(Tag),
+    Preset(usize),
+    PresetZone(usize, Option<usize>),
+    Instrument(usize),
+    InstrumentZone(usize, Option<usize>),
+    Sample(usize),
+}
+
+/**
+The single problem found
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub location: Location,
+    pub message: String,
+}
+
+/**
+The result of SoundFont validation
+
+See [`validate_soundfont`].
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Report {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/**
+The SoundFont to validate
+
+Converted from paths or file contents.
+ */
+#[derive(Debug, Clone, Copy)]
+pub enum FontSource<'a> {
+    Path(&'a Path),
+    Bytes(&'a [u8]),
+}
+
+impl<'a> From<&'a Path> for FontSource<'a> {
+    fn from(path: &'a Path) -> Self {
+        FontSource::Path(path)
+    }
+}
+
+impl<'a> From<&'a PathBuf> for FontSource<'a> {
+    fn from(path: &'a PathBuf) -> Self {
+        FontSource::Path(path)
+    }
+}
+
+impl<'a> From<&'a str> for FontSource<'a> {
+    fn from(path: &'a str) -> Self {
+        FontSource::Path(path.as_ref())
+    }
+}
+
+impl<'a> From<&'a [u8]> for FontSource<'a> {
+    fn from(data: &'a [u8]) -> Self {
+        FontSource::Bytes(data)
+    }
+}
+
+impl<'a> From<&'a Vec<u8>> for FontSource<'a> {
+    fn from(data: &'a Vec<u8>) -> Self {
+        FontSource::Bytes(data)
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+        .fmt(f)
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        fn zone(f: &mut Formatter, zone: &Option<usize>) -> FmtResult {
+            match zone {
+                Some(zone) => write!(f, " zone {}", zone),
+                None => " global zone".fmt(f),
+            }
+        }
+        match self {
+            Location::File => "file".fmt(f),
+            Location::Chunk(id) => write!(f, "chunk {}", String::from_utf8_lossy(id)),
+            Location::Preset(preset) => write!(f, "preset {}", preset),
+            Location::PresetZone(preset, index) => {
+                write!(f, "preset {}", preset)?;
+                zone(f, index)
+            }
+            Location::Instrument(inst) => write!(f, "instrument {}", inst),
+            Location::InstrumentZone(inst, index) => {
+                write!(f, "instrument {}", inst)?;
+                zone(f, index)
+            }
+            Location::Sample(sample) => write!(f, "sample {}", sample),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}: {}: {}", self.severity, self.location, self.message)
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        for diagnostic in &self.diagnostics {
+            writeln!(f, "{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl Report {
+    /// Check that there is no errors
+    pub fn is_valid(&self) -> bool {
+        !self.has(Severity::Error)
+    }
+
+    /// Get the highest severity of diagnostics
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.diagnostics.iter().map(|diag| diag.severity).max()
+    }
+
+    /// Iterate over diagnostics of severity
+    pub fn filter(&self, severity: Severity) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(move |diag| diag.severity == severity)
+    }
+
+    fn has(&self, severity: Severity) -> bool {
+        self.filter(severity).next().is_some()
+    }
+
+    fn push<S: Into<String>>(&mut self, severity: Severity, location: Location, message: S) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            location,
+            message: message.into(),
+        });
+    }
+}
+
+/**
+Validate SoundFont file
+
+Checks the file structure (chunk sizes, terminal records, indices),
+the suspicious content (sample and loop bounds, overlapping zones,
+invalid generator amounts) and the gaps of key coverage.
+
+```no_run
+use fluidlite::sf2::{validate_soundfont, Severity};
+
+let report = validate_soundfont("sf_/Boomwhacker.sf2");
+
+for diagnostic in report.filter(Severity::Error) {
+    eprintln!("{}", diagnostic);
+}
+```
+ */
+pub fn validate_soundfont<'a, S: Into<FontSource<'a>>>(source: S) -> Report {
+    let mut report = Report::default();
+    let result = match source.into() {
+        FontSource::Path(path) => File::open(path)
+            .map_err(From::from)
+            .and_then(|file| validate(&mut BufReader::new(file), &mut report)),
+        FontSource::Bytes(data) => validate(&mut Cursor::new(data), &mut report),
+    };
+    if let Err(error) = result {
+        report.push(Severity::Error, Location::File, error.to_string());
+    }
+    report
+}
+
+fn validate<R: Read + Seek>(reader: &mut R, report: &mut Report) -> Result<()> {
+    check_structure(reader, report)?;
+    if report.has(Severity::Error) {
+        return Ok(());
+    }
+    reader.seek(SeekFrom::Start(0))?;
+    check_content(&SoundFont::read(reader)?, report);
+    Ok(())
+}
+
+/// Read sub-chunks of list reporting chunks which exceed it
+fn sub_chunks<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    end: u64,
+    report: &mut Report,
+) -> Result<Vec<Chunk>> {
+    let mut chunks = Vec::new();
+    let mut offset = start;
+    while offset + 8 <= end {
+        reader.seek(SeekFrom::Start(offset))?;
+        let chunk = Chunk::read(reader)?;
+        if chunk.offset + chunk.size as u64 > end {
+            report.push(
+                Severity::Error,
+                Location::Chunk(chunk.id),
+                format!(
+                    "Chunk size {} exceeds its parent by {} bytes",
+                    chunk.size,
+                    chunk.offset + chunk.size as u64 - end
+                ),
+            );
+            break;
+        }
+        offset = chunk.end();
+        chunks.push(chunk);
+    }
+    Ok(chunks)
+}
+
+fn check_structure<R: Read + Seek>(reader: &mut R, report: &mut Report) -> Result<()> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+
+    if file_len < 12 {
+        report.push(Severity::Error, Location::File, "Not a SoundFont file");
+        return Ok(());
+    }
+    let riff = Chunk::read(reader)?;
+    let mut form = [0u8; 4];
+    reader.read_exact(&mut form)?;
+    if &riff.id != b"RIFF" || &form != b"sfbk" {
+        report.push(Severity::Error, Location::File, "Not a SoundFont file");
+        return Ok(());
+    }
+
+    let mut end = riff.offset + riff.size as u64;
+    if end > file_len {
+        report.push(
+            Severity::Error,
+            Location::File,
+            format!("File is truncated by {} bytes", end - file_len),
+        );
+        end = file_len;
+    }
+
+    let mut lists = Vec::new();
+    for chunk in sub_chunks(reader, riff.offset + 4, end, report)? {
+        if &chunk.id == b"LIST" && chunk.size >= 4 {
+            reader.seek(SeekFrom::Start(chunk.offset))?;
+            let mut form = [0u8; 4];
+            reader.read_exact(&mut form)?;
+            let chunks = sub_chunks(
+                reader,
+                chunk.offset + 4,
+                chunk.offset + chunk.size as u64,
+                report,
+            )?;
+            lists.push((form, chunks));
+        }
+    }
+
+    let mut list = |id: &Tag| {
+        let chunks = lists
+            .iter()
+            .position(|(form, _)| form == id)
+            .map(|index| lists.swap_remove(index).1);
+        if chunks.is_none() {
+            report.push(Severity::Error, Location::Chunk(*id), "Missing chunk");
+        }
+        chunks
+    };
+    let (info, sdta, pdta) = (list(b"INFO"), list(b"sdta"), list(b"pdta"));
+
+    if let Some(info) = info {
+        check_info(reader, &info, report)?;
+    }
+    if let Some(sdta) = sdta {
+        check_sdta(&sdta, report);
+    }
+    if let Some(pdta) = pdta {
+        check_pdta(reader, &pdta, report)?;
+    }
+
+    Ok(())
+}
+
+fn check_info<R: Read + Seek>(reader: &mut R, info: &[Chunk], report: &mut Report) -> Result<()> {
+    let find = |id: &Tag| info.iter().find(|chunk| &chunk.id == id);
+
+    match find(b"ifil") {
+        Some(chunk) if chunk.size != 4 => report.push(
+            Severity::Error,
+            Location::Chunk(chunk.id),
+            format!("Invalid version chunk size {}", chunk.size),
+        ),
+        Some(chunk) => {
+            let data = chunk.data(reader)?;
+            let major = u16_at(&data, 0);
+            if major != 2 && major != 3 {
+                report.push(
+                    Severity::Warning,
+                    Location::Chunk(chunk.id),
+                    format!("Unsupported format version {}", major),
+                );
+            }
+        }
+        None => report.push(
+            Severity::Error,
+            Location::Chunk(*b"ifil"),
+            "Missing version chunk",
+        ),
+    }
+    if find(b"INAM").is_none() {
+        report.push(
+            Severity::Error,
+            Location::Chunk(*b"INAM"),
+            "Missing bank name chunk",
+        );
+    }
+    if find(b"isng").is_none() {
+        report.push(
+            Severity::Warning,
+            Location::Chunk(*b"isng"),
+            "Missing sound engine chunk",
+        );
+    }
+    for chunk in info {
+        if chunk.id[0].is_ascii_uppercase() && chunk.size > INFO_MAX_LEN {
+            report.push(
+                Severity::Warning,
+                Location::Chunk(chunk.id),
+                format!("String is longer than {} bytes", INFO_MAX_LEN),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn check_sdta(sdta: &[Chunk], report: &mut Report) {
+    let find = |id: &Tag| sdta.iter().find(|chunk| &chunk.id == id);
+
+    match (find(b"smpl"), find(b"sm24")) {
+        (None, _) => report.push(
+            Severity::Warning,
+            Location::Chunk(*b"smpl"),
+            "Missing sample data (only valid for ROM samples)",
+        ),
+        (Some(smpl), Some(sm24)) => {
+            let expected = smpl.size / 2 + (smpl.size / 2) % 2;
+            if sm24.size != expected {
+                report.push(
+                    Severity::Warning,
+                    Location::Chunk(sm24.id),
+                    format!(
+                        "Size {} doesn't match sample data (expected {}), so it is ignored",
+                        sm24.size, expected
+                    ),
+                );
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_pdta<R: Read + Seek>(reader: &mut R, pdta: &[Chunk], report: &mut Report) -> Result<()> {
+    let mut records = Vec::with_capacity(Hydra::CHUNKS.len());
+
+    for (id, len) in Hydra::CHUNKS.iter() {
+        let chunk = match pdta.iter().find(|chunk| &chunk.id == *id) {
+            Some(chunk) => chunk,
+            None => {
+                report.push(Severity::Error, Location::Chunk(**id), "Missing chunk");
+                continue;
+            }
+        };
+        let size = chunk.size as usize;
+        if size / len * len != size {
+            report.push(
+                Severity::Error,
+                Location::Chunk(chunk.id),
+                format!(
+                    "Chunk size {} isn't a multiple of record size {}",
+                    chunk.size, len
+                ),
+            );
+        } else if chunk.size == 0 && !id.ends_with(b"mod") {
+            // modulators may be omitted entirely
+            report.push(
+                Severity::Error,
+                Location::Chunk(chunk.id),
+                "Missing terminal record",
+            );
+        } else {
+            records.push((*len, chunk.data(reader)?));
+        }
+    }
+
+    if records.len() != Hydra::CHUNKS.len() {
+        return Ok(());
+    }
+
+    // the index of first record of each item into the next list
+    let indices = |index: usize, offset: usize| {
+        let (len, data) = &records[index];
+        data.chunks(*len)
+            .map(|record| u16_at(record, offset) as usize)
+            .collect::<Vec<_>>()
+    };
+    let count = |index: usize| records[index].1.len() / records[index].0;
+    let pbag = |_| Location::Chunk(*b"pbag");
+    let ibag = |_| Location::Chunk(*b"ibag");
+
+    // phdr -> pbag, pbag -> pgen, pbag -> pmod and the same for instruments
+    check_indices(report, &indices(0, 24), count(1), Location::Preset, "bag");
+    check_indices(report, &indices(1, 0), count(3), pbag, "generator");
+    check_indices(report, &indices(1, 2), count(2), pbag, "modulator");
+    check_indices(
+        report,
+        &indices(4, 20),
+        count(5),
+        Location::Instrument,
+        "bag",
+    );
+    check_indices(report, &indices(5, 0), count(7), ibag, "generator");
+    check_indices(report, &indices(5, 2), count(6), ibag, "modulator");
+
+    Ok(())
+}
+
+/**
+Check that the indices into the next list grow and the terminal
+record refers to the terminal record of the next list
+ */
+fn check_indices<L>(report: &mut Report, indices: &[usize], count: usize, location: L, what: &str)
+where
+    L: Fn(usize) -> Location,
+{
+    for (item, pair) in indices.windows(2).enumerate() {
+        if pair[0] > pair[1] {
+            report.push(
+                Severity::Error,
+                location(item),
+                format!("The {} indices are not monotonic", what),
+            );
+        }
+    }
+    if let Some(last) = indices.last() {
+        // the modulators may have no terminal record
+        if *last + 1 != count && !(what == "modulator" && *last == count) {
+            report.push(
+                Severity::Error,
+                location(indices.len() - 1),
+                format!(
+                    "The terminal {} index {} doesn't match {} records",
+                    what, last, count
+                ),
+            );
+        }
+    }
+}
+
+fn check_content(font: &SoundFont, report: &mut Report) {
+    let mut used_instruments = vec![false; font.instruments.len()];
+    let mut used_samples = vec![false; font.samples.len()];
+
+    for (index, preset) in font.presets.iter().enumerate() {
+        let location = Location::Preset(index);
+        if preset.prog > 127 || preset.bank > 128 {
+            report.push(
+                Severity::Warning,
+                location,
+                format!("Invalid bank/program {}:{}", preset.bank, preset.prog),
+            );
+        }
+        if let Some(other) = font.presets[..index]
+            .iter()
+            .position(|other| (other.bank, other.prog) == (preset.bank, preset.prog))
+        {
+            report.push(
+                Severity::Warning,
+                location,
+                format!(
+                    "Preset {}:{} duplicates preset {}",
+                    preset.bank, preset.prog, other
+                ),
+            );
+        }
+        if preset.zones.is_empty() {
+            report.push(
+                Severity::Warning,
+                location,
+                format!("Preset \"{}\" has no zones", preset.name),
+            );
+        }
+
+        if let Some(zone) = &preset.global {
+            check_zone(report, Location::PresetZone(index, None), zone, true);
+        }
+        for (zone_index, zone) in preset.zones.iter().enumerate() {
+            let location = Location::PresetZone(index, Some(zone_index));
+            check_zone(report, location, zone, true);
+            match zone.instrument() {
+                Some(inst) if inst < font.instruments.len() => used_instruments[inst] = true,
+                Some(inst) => report.push(
+                    Severity::Error,
+                    location,
+                    format!("Instrument index {} is out of range", inst),
+                ),
+                None => report.push(
+                    Severity::Warning,
+                    location,
+                    "Zone has no instrument, so it is ignored",
+                ),
+            }
+        }
+        check_overlaps(
+            report,
+            &preset.zones,
+            Severity::Info,
+            |zone| Location::PresetZone(index, Some(zone)),
+            |_, _| false,
+        );
+        check_coverage(font, report, index);
+    }
+
+    for (index, inst) in font.instruments.iter().enumerate() {
+        let location = Location::Instrument(index);
+        if inst.zones.is_empty() {
+            report.push(
+                Severity::Warning,
+                location,
+                format!("Instrument \"{}\" has no zones", inst.name),
+            );
+        }
+        if !used_instruments[index] {
+            report.push(
+                Severity::Info,
+                location,
+                format!("Instrument \"{}\" is not used", inst.name),
+            );
+        }
+
+        if let Some(zone) = &inst.global {
+            check_zone(report, Location::InstrumentZone(index, None), zone, false);
+        }
+        for (zone_index, zone) in inst.zones.iter().enumerate() {
+            let location = Location::InstrumentZone(index, Some(zone_index));
+            check_zone(report, location, zone, false);
+            match zone.sample() {
+                Some(sample) if sample < font.samples.len() => used_samples[sample] = true,
+                Some(sample) => report.push(
+                    Severity::Error,
+                    location,
+                    format!("Sample index {} is out of range", sample),
+                ),
+                None => report.push(
+                    Severity::Warning,
+                    location,
+                    "Zone has no sample, so it is ignored",
+                ),
+            }
+        }
+
+        // the zones of stereo pairs overlap by design
+        check_overlaps(
+            report,
+            &inst.zones,
+            Severity::Warning,
+            |zone| Location::InstrumentZone(index, Some(zone)),
+            |zone, other| match (zone.sample(), other.sample()) {
+                (Some(sample), Some(other)) => {
+                    font.samples.get(sample).map(|sample| sample.link as usize) == Some(other)
+                }
+                _ => false,
+            },
+        );
+    }
+
+    for (index, sample) in font.samples.iter().enumerate() {
+        check_sample(font, report, index);
+        if !used_samples[index] {
+            report.push(
+                Severity::Info,
+                Location::Sample(index),
+                format!("Sample \"{}\" is not used", sample.name),
+            );
+        }
+    }
+}
+
+fn check_zone(report: &mut Report, location: Location, zone: &Zone, preset: bool) {
+    for (index, gen) in zone.gens.iter().enumerate() {
+        let param = match gen.param() {
+            Some(param) => param,
+            None => {
+                report.push(
+                    Severity::Warning,
+                    location,
+                    format!("Unknown generator {}", gen.oper),
+                );
+                continue;
+            }
+        };
+
+        if zone.gens[..index]
+            .iter()
+            .any(|other| other.oper == gen.oper)
+        {
+            report.push(
+                Severity::Warning,
+                location,
+                format!("Duplicate generator {:?}", param),
+            );
+        }
+
+        match param {
+            GenParam::Keyrange | GenParam::Velrange => {
+                let first = index == 0
+                    || (param == GenParam::Velrange
+                        && index == 1
+                        && zone.gens[0].oper == GenParam::Keyrange as u16);
+                if !first {
+                    report.push(
+                        Severity::Warning,
+                        location,
+                        format!("{:?} must precede other generators", param),
+                    );
+                }
+                let Range { lo, hi } = gen.range();
+                if lo > hi || hi > 127 {
+                    report.push(
+                        Severity::Warning,
+                        location,
+                        format!("Invalid {:?} {}-{}", param, lo, hi),
+                    );
+                }
+            }
+            GenParam::Instrument if !preset => report.push(
+                Severity::Warning,
+                location,
+                "Instrument generator in instrument zone",
+            ),
+            GenParam::SampleId if preset => report.push(
+                Severity::Warning,
+                location,
+                "Sample generator in preset zone",
+            ),
+            _ if preset && INSTRUMENT_ONLY.contains(&param) => report.push(
+                Severity::Warning,
+                location,
+                format!("{:?} isn't allowed in preset zones", param),
+            ),
+            _ if !preset => {
+                if let Some((_, min, max)) = GEN_RANGES.iter().find(|(gen, ..)| *gen == param) {
+                    // the sample mode 2 is unused
+                    let unused = param == GenParam::SampleMode && gen.amount == 2;
+                    if gen.amount < *min || gen.amount > *max || unused {
+                        report.push(
+                            Severity::Warning,
+                            location,
+                            format!(
+                                "{:?} amount {} is out of range {}..{}",
+                                param, gen.amount, min, max
+                            ),
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for modulator in &zone.mods {
+        // the destination may be a link to other modulator
+        let dest = Generator {
+            oper: modulator.dest,
+            amount: 0,
+        };
+        if modulator.dest & 0x8000 == 0 && dest.param().is_none() {
+            report.push(
+                Severity::Warning,
+                location,
+                format!("Unknown modulator destination {}", modulator.dest),
+            );
+        }
+    }
+}
+
+fn overlaps(a: Range, b: Range) -> bool {
+    a.lo <= b.hi && b.lo <= a.hi
+}
+
+fn check_overlaps<L, P>(
+    report: &mut Report,
+    zones: &[Zone],
+    severity: Severity,
+    location: L,
+    paired: P,
+) where
+    L: Fn(usize) -> Location,
+    P: Fn(&Zone, &Zone) -> bool,
+{
+    for (index, zone) in zones.iter().enumerate() {
+        for (other_index, other) in zones[..index].iter().enumerate() {
+            if paired(zone, other) {
+                continue;
+            }
+            let keys = overlaps(
+                zone.key_range().unwrap_or_default(),
+                other.key_range().unwrap_or_default(),
+            );
+            let vels = overlaps(
+                zone.vel_range().unwrap_or_default(),
+                other.vel_range().unwrap_or_default(),
+            );
+            if keys && vels {
+                report.push(
+                    severity,
+                    location(index),
+                    format!("Zone overlaps zone {}", other_index),
+                );
+            }
+        }
+    }
+}
+
+fn check_coverage(font: &SoundFont, report: &mut Report, index: usize) {
+    let preset = &font.presets[index];
+    let mut covered = [false; 128];
+
+    for (zone, inst) in font.preset_instruments(preset) {
+        let range = zone.key_range().unwrap_or_default();
+        for zone in inst.zones.iter().filter(|zone| font.sample(zone).is_some()) {
+            let inst_range = zone.key_range().unwrap_or_default();
+            let lo = range.lo.max(inst_range.lo) as usize;
+            let hi = range.hi.min(inst_range.hi).min(127) as usize;
+            for covered in covered.iter_mut().take(hi + 1).skip(lo) {
+                *covered = true;
+            }
+        }
+    }
+
+    let first = covered.iter().position(|covered| *covered);
+    let last = covered.iter().rposition(|covered| *covered);
+    let (first, last) = match (first, last) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return,
+    };
+
+    let mut key = first;
+    while key < last {
+        if covered[key] {
+            key += 1;
+            continue;
+        }
+        let gap_end = key
+            + covered[key..]
+                .iter()
+                .position(|covered| *covered)
+                .unwrap_or(0);
+        report.push(
+            Severity::Info,
+            Location::Preset(index),
+            format!("Keys {}-{} are not covered", key, gap_end - 1),
+        );
+        key = gap_end;
+    }
+}
+
+fn check_sample(font: &SoundFont, report: &mut Report, index: usize) {
+    let sample = &font.samples[index];
+    let location = Location::Sample(index);
+    let compressed = sample.is_compressed();
+
+    if compressed && !font.is_sf3() {
+        report.push(
+            Severity::Warning,
+            location,
+            "Compressed sample in uncompressed SoundFont",
+        );
+    }
+    if sample.sample_rate == 0 {
+        report.push(Severity::Error, location, "Sample rate is zero");
+    }
+    if sample.original_key > 127 && sample.original_key != 255 {
+        report.push(
+            Severity::Warning,
+            location,
+            format!("Invalid original key {}", sample.original_key),
+        );
+    }
+    if sample.end <= sample.start {
+        report.push(
+            Severity::Warning,
+            location,
+            format!("Sample \"{}\" has zero length", sample.name),
+        );
+    }
+
+    if !sample.is_rom() {
+        // the compressed samples are addressed in bytes
+        let len = font
+            .sample_data
+            .map_or(0, |chunk| chunk.size / if compressed { 1 } else { 2 });
+        if sample.end > len {
+            report.push(
+                Severity::Error,
+                location,
+                format!(
+                    "Sample end {} is outside of sample data {}",
+                    sample.end, len
+                ),
+            );
+        }
+    }
+
+    let loop_outside = if compressed {
+        sample.start_loop > sample.end_loop
+    } else {
+        sample.start_loop < sample.start
+            || sample.end_loop > sample.end
+            || sample.start_loop > sample.end_loop
+    };
+    if loop_outside {
+        report.push(
+            Severity::Warning,
+            location,
+            format!(
+                "Loop {}-{} is outside of sample {}-{}",
+                sample.start_loop, sample.end_loop, sample.start, sample.end
+            ),
+        );
+    }
+
+    let kind = sample.sample_type - SampleType::COMPRESSED - SampleType::ROM;
+    if kind.is_empty() {
+        report.push(Severity::Warning, location, "Invalid sample type");
+    }
+    if kind.intersects(SampleType::LEFT | SampleType::RIGHT | SampleType::LINKED) {
+        match font.samples.get(sample.link as usize) {
+            None => report.push(
+                Severity::Error,
+                location,
+                format!("Linked sample index {} is out of range", sample.link),
+            ),
+            Some(other) if other.link as usize != index => report.push(
+                Severity::Warning,
+                location,
+                format!("Linked sample {} doesn't link back", sample.link),
+            ),
+            Some(other)
+                if kind.contains(SampleType::LEFT)
+                    && !other.sample_type.contains(SampleType::RIGHT) =>
+            {
+                report.push(
+                    Severity::Warning,
+                    location,
+                    format!("Linked sample {} isn't the right channel", sample.link),
+                )
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn boomwhacker() -> Vec<u8> {
+        std::fs::read("sf_/Boomwhacker.sf2").unwrap()
+    }
+
+    fn patch_u32(data: &mut [u8], pos: usize, value: u32) {
+        data[pos..pos + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn chunk_pos(data: &[u8], id: &Tag) -> usize {
+        data.windows(4).position(|window| window == id).unwrap()
+    }
+
+    #[test]
+    fn valid_font() {
+        let report = validate_soundfont("sf_/Boomwhacker.sf2");
+
+        assert!(report.is_valid(), "{}", report);
+        assert_eq!(report.max_severity(), None);
+
+        let report = validate_soundfont("sf_/Boomwhacker.sf3");
+
+        assert!(report.is_valid(), "{}", report);
+    }
+
+    #[test]
+    fn structural_errors() {
+        let report = validate_soundfont(&b"RIFF\x04\x00\x00\x00WAVE"[..]);
+
+        assert_eq!(report.diagnostics[0].location, Location::File);
+        assert!(!report.is_valid());
+
+        let mut data = boomwhacker();
+        let pos = chunk_pos(&data, b"pgen");
+        patch_u32(&mut data, pos + 4, 13);
+        let report = validate_soundfont(&data);
+
+        assert!(report
+            .filter(Severity::Error)
+            .any(|diag| diag.location == Location::Chunk(*b"pgen")));
+
+        let mut data = boomwhacker();
+        data.truncate(data.len() - 100);
+        let report = validate_soundfont(&data);
+
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn content_problems() {
+        let mut data = boomwhacker();
+        // the loop end of second sample
+        let pos = chunk_pos(&data, b"shdr") + 8 + 46 + 32;
+        patch_u32(&mut data, pos, 1_000_000);
+        // the end of first sample
+        let pos = chunk_pos(&data, b"shdr") + 8 + 24;
+        patch_u32(&mut data, pos, 1_000_000);
+        let report = validate_soundfont(&data);
+
+        assert!(report
+            .filter(Severity::Error)
+            .any(|diag| diag.location == Location::Sample(0)));
+        assert!(report
+            .filter(Severity::Warning)
+            .any(|diag| diag.location == Location::Sample(1)));
+    }
+}
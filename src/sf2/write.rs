@@ -0,0 +1,575 @@
+use super::{
+    read::{NAME_LEN, SHDR_LEN},
+    Generator, Modulator, SamplePcm, SoundFont, Version, Zone,
+};
+use crate::{Error, GenParam, Result};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// The zero points which must follow each sample
+const SAMPLE_PADDING: usize = 46;
+
+/// The maximum length of INFO strings including terminator
+const INFO_MAX_LEN: usize = 256;
+
+/// The version written by default
+const DEFAULT_VERSION: Version = Version { major: 2, minor: 1 };
+
+/// The first version which supports 24-bit samples
+const SM24_VERSION: Version = Version { major: 2, minor: 4 };
+
+fn invalid(reason: &'static str) -> Error {
+    Error::Format { reason }
+}
+
+/// The buffer of chunk data
+#[derive(Default)]
+struct Buffer(Vec<u8>);
+
+impl Buffer {
+    fn u8(&mut self, value: u8) -> &mut Self {
+        self.0.push(value);
+        self
+    }
+
+    fn u16(&mut self, value: u16) -> &mut Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn u32(&mut self, value: u32) -> &mut Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn tag(&mut self, id: &[u8; 4]) -> &mut Self {
+        self.0.extend_from_slice(id);
+        self
+    }
+
+    /// Fixed-size zero-padded name
+    fn name(&mut self, name: &str) -> &mut Self {
+        let name = truncate(name, NAME_LEN - 1);
+        self.0.extend_from_slice(name.as_bytes());
+        self.0.resize(self.0.len() + NAME_LEN - name.len(), 0);
+        self
+    }
+
+    /// Append chunk with header and padding
+    fn chunk(&mut self, id: &[u8; 4], data: &[u8]) -> &mut Self {
+        self.tag(id).u32(data.len() as u32);
+        self.0.extend_from_slice(data);
+        if data.len() & 1 != 0 {
+            self.0.push(0);
+        }
+        self
+    }
+
+    /// Append zero-terminated string chunk padded to even size
+    fn string(&mut self, id: &[u8; 4], value: &str) -> &mut Self {
+        let mut data = truncate(value, INFO_MAX_LEN - 2).as_bytes().to_vec();
+        data.push(0);
+        if data.len() & 1 != 0 {
+            data.push(0);
+        }
+        self.chunk(id, &data)
+    }
+
+    fn version(&mut self, id: &[u8; 4], version: Version) -> &mut Self {
+        let mut data = Buffer::default();
+        data.u16(version.major).u16(version.minor);
+        self.chunk(id, &data.0)
+    }
+
+    fn generator(&mut self, gen: &Generator) -> &mut Self {
+        self.u16(gen.oper).u16(gen.amount as u16)
+    }
+
+    fn modulator(&mut self, modulator: &Modulator) -> &mut Self {
+        self.u16(modulator.src)
+            .u16(modulator.dest)
+            .u16(modulator.amount as u16)
+            .u16(modulator.amount_src)
+            .u16(modulator.transform)
+    }
+}
+
+/// Truncate string at char boundary
+fn truncate(value: &str, len: usize) -> &str {
+    let mut len = len.min(value.len());
+    while !value.is_char_boundary(len) {
+        len -= 1;
+    }
+    &value[..len]
+}
+
+/**
+The order of generators in zone
+
+The key range must be first followed by velocity range, and the
+instrument or sample must be last.
+ */
+fn gen_order(gen: &Generator) -> u8 {
+    match gen.param() {
+        Some(GenParam::Keyrange) => 0,
+        Some(GenParam::Velrange) => 1,
+        Some(GenParam::Instrument) | Some(GenParam::SampleId) => 3,
+        _ => 2,
+    }
+}
+
+/// The zone lists of presets or instruments
+struct Zones {
+    bags: Buffer,
+    mods: Buffer,
+    gens: Buffer,
+    bag_count: usize,
+    mod_count: usize,
+    gen_count: usize,
+}
+
+impl Zones {
+    fn new() -> Self {
+        Self {
+            bags: Buffer::default(),
+            mods: Buffer::default(),
+            gens: Buffer::default(),
+            bag_count: 0,
+            mod_count: 0,
+            gen_count: 0,
+        }
+    }
+
+    /// Add zones of item returning the index of first bag
+    fn add(&mut self, global: &Option<Zone>, zones: &[Zone]) -> Result<u16> {
+        let first = index(self.bag_count)?;
+        for zone in global.iter().chain(zones) {
+            self.bags
+                .u16(index(self.gen_count)?)
+                .u16(index(self.mod_count)?);
+            self.bag_count += 1;
+
+            let mut gens = zone.gens.iter().collect::<Vec<_>>();
+            gens.sort_by_key(|gen| gen_order(gen));
+            for gen in gens {
+                self.gens.generator(gen);
+            }
+            self.gen_count += zone.gens.len();
+
+            for modulator in &zone.mods {
+                self.mods.modulator(modulator);
+            }
+            self.mod_count += zone.mods.len();
+        }
+        Ok(first)
+    }
+
+    /// Add terminal records
+    fn finish(mut self) -> Result<Self> {
+        self.bags
+            .u16(index(self.gen_count)?)
+            .u16(index(self.mod_count)?);
+        self.mods.modulator(&Modulator {
+            src: 0,
+            dest: 0,
+            amount: 0,
+            amount_src: 0,
+            transform: 0,
+        });
+        self.gens.generator(&Generator { oper: 0, amount: 0 });
+        Ok(self)
+    }
+}
+
+fn index(value: usize) -> Result<u16> {
+    if value > u16::MAX as usize {
+        return Err(invalid("Too many records"));
+    }
+    Ok(value as u16)
+}
+
+/**
+Writing
+ */
+impl SoundFont {
+    /**
+    Write SoundFont with sample data
+
+    The `data` provides the PCM of every sample header in the same order,
+    so both lists must have equal length and the data must be mono.
+    The sample positions are computed from data, and the sample rate,
+    original key, correction and loop points are taken from data too.
+    The sample headers provide the names, the links and the types.
+
+    The 24-bit samples are written using `sm24` chunk, so the version
+    is raised to 2.04 when any sample has 24 bits.
+
+    The compressed samples (SF3) cannot be written.
+
+    ```no_run
+    use fluidlite::sf2::SoundFont;
+    use std::{fs::File, io::BufReader};
+
+    let mut reader = BufReader::new(File::open("sf_/Boomwhacker.sf2").unwrap());
+    let mut font = SoundFont::read(&mut reader).unwrap();
+    let data = (0..font.samples.len())
+        .map(|index| font.read_sample(&mut reader, index))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    font.info.name = "Renamed".into();
+    font.save("Renamed.sf2", &data).unwrap();
+    ```
+     */
+    pub fn write<W: Write>(&self, writer: &mut W, data: &[SamplePcm]) -> Result<()> {
+        if data.len() != self.samples.len() {
+            return Err(invalid("Sample data doesn't match sample headers"));
+        }
+        if data.iter().any(|pcm| pcm.channels != 1) {
+            return Err(invalid("Sample data must be mono"));
+        }
+        if self.samples.iter().any(|sample| sample.is_compressed()) {
+            return Err(invalid("Compressed samples cannot be written"));
+        }
+
+        let has_sm24 = data.iter().any(|pcm| pcm.bits > 16);
+        let mut version = self.info.version.max(DEFAULT_VERSION);
+        if has_sm24 {
+            version = version.max(SM24_VERSION);
+        }
+
+        let points = data
+            .iter()
+            .try_fold(0usize, |points, pcm| {
+                points
+                    .checked_add(pcm.data.len())?
+                    .checked_add(SAMPLE_PADDING)
+            })
+            .filter(|points| points.checked_mul(2).unwrap_or(usize::MAX) <= u32::MAX as usize)
+            .ok_or_else(|| invalid("Sample data is too large"))?;
+
+        let info = self.info_chunk(version);
+        // the sample positions fit into u32 because of the checked size
+        let pdta = self.pdta_chunk(data)?;
+
+        let smpl_size = points * 2;
+        // the chunk data is padded to even size
+        let sm24_size = if has_sm24 { points + (points & 1) } else { 0 };
+        let sdta_size = 4 + 8 + smpl_size + if has_sm24 { 8 + sm24_size } else { 0 };
+
+        let riff_size = 4 + (8 + info.len()) + (8 + sdta_size) + (8 + pdta.len());
+        if riff_size > u32::MAX as usize {
+            return Err(invalid("SoundFont is too large"));
+        }
+
+        let mut header = Buffer::default();
+        header
+            .tag(b"RIFF")
+            .u32(riff_size as u32)
+            .tag(b"sfbk")
+            .tag(b"LIST")
+            .u32(info.len() as u32);
+        writer.write_all(&header.0)?;
+        writer.write_all(&info)?;
+
+        let mut header = Buffer::default();
+        header
+            .tag(b"LIST")
+            .u32(sdta_size as u32)
+            .tag(b"sdta")
+            .tag(b"smpl")
+            .u32(smpl_size as u32);
+        writer.write_all(&header.0)?;
+
+        let padding = [0u8; SAMPLE_PADDING * 2];
+        for pcm in data {
+            let shift = pcm.bits.saturating_sub(16);
+            let mut buffer = Vec::with_capacity(pcm.data.len() * 2);
+            for point in &pcm.data {
+                buffer.extend_from_slice(&((point >> shift) as i16).to_le_bytes());
+            }
+            writer.write_all(&buffer)?;
+            writer.write_all(&padding)?;
+        }
+
+        if has_sm24 {
+            let mut header = Buffer::default();
+            header.tag(b"sm24").u32(sm24_size as u32);
+            writer.write_all(&header.0)?;
+            for pcm in data {
+                let low = pcm
+                    .data
+                    .iter()
+                    .map(|point| if pcm.bits > 16 { *point as u8 } else { 0 })
+                    .collect::<Vec<_>>();
+                writer.write_all(&low)?;
+                writer.write_all(&padding[..SAMPLE_PADDING])?;
+            }
+            if points & 1 != 0 {
+                writer.write_all(&[0])?;
+            }
+        }
+
+        let mut header = Buffer::default();
+        header.tag(b"LIST").u32(pdta.len() as u32);
+        writer.write_all(&header.0)?;
+        writer.write_all(&pdta)?;
+
+        Ok(())
+    }
+
+    /// Save SoundFont with sample data to file
+    pub fn save<P: AsRef<Path>>(&self, path: P, data: &[SamplePcm]) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer, data)?;
+        Ok(writer.flush()?)
+    }
+
+    fn info_chunk(&self, version: Version) -> Vec<u8> {
+        let info = &self.info;
+        let mut data = Buffer::default();
+        data.tag(b"INFO").version(b"ifil", version).string(
+            b"isng",
+            if info.sound_engine.is_empty() {
+                "EMU8000"
+            } else {
+                &info.sound_engine
+            },
+        );
+        data.string(b"INAM", &info.name);
+        if let Some(rom_name) = &info.rom_name {
+            data.string(b"irom", rom_name);
+        }
+        if let Some(rom_version) = info.rom_version {
+            data.version(b"iver", rom_version);
+        }
+        let strings = [
+            (b"ICRD", &info.creation_date),
+            (b"IENG", &info.engineer),
+            (b"IPRD", &info.product),
+            (b"ICOP", &info.copyright),
+            (b"ICMT", &info.comment),
+            (b"ISFT", &info.software),
+        ];
+        for (id, value) in strings.iter() {
+            if let Some(value) = value {
+                data.string(id, value);
+            }
+        }
+        data.0
+    }
+
+    fn pdta_chunk(&self, data: &[SamplePcm]) -> Result<Vec<u8>> {
+        let mut phdr = Buffer::default();
+        let mut zones = Zones::new();
+        for preset in &self.presets {
+            let bag = zones.add(&preset.global, &preset.zones)?;
+            phdr.name(&preset.name)
+                .u16(preset.prog)
+                .u16(preset.bank)
+                .u16(bag)
+                .u32(preset.library)
+                .u32(preset.genre)
+                .u32(preset.morphology);
+        }
+        phdr.name("EOP")
+            .u16(0)
+            .u16(0)
+            .u16(index(zones.bag_count)?)
+            .u32(0)
+            .u32(0)
+            .u32(0);
+        let preset_zones = zones.finish()?;
+
+        let mut inst = Buffer::default();
+        let mut zones = Zones::new();
+        for instrument in &self.instruments {
+            let bag = zones.add(&instrument.global, &instrument.zones)?;
+            inst.name(&instrument.name).u16(bag);
+        }
+        inst.name("EOI").u16(index(zones.bag_count)?);
+        let instrument_zones = zones.finish()?;
+
+        let mut shdr = Buffer::default();
+        let mut start = 0u32;
+        for (sample, pcm) in self.samples.iter().zip(data) {
+            let end = start + pcm.data.len() as u32;
+            let (start_loop, end_loop) = pcm.loop_points.unwrap_or((0, 0));
+            let (start_loop, end_loop) = start
+                .checked_add(start_loop)
+                .zip(start.checked_add(end_loop))
+                .ok_or_else(|| invalid("Sample loop is out of range"))?;
+            shdr.name(&sample.name)
+                .u32(start)
+                .u32(end)
+                .u32(start_loop)
+                .u32(end_loop)
+                .u32(pcm.sample_rate)
+                .u8(pcm.root_key)
+                .u8(pcm.correction as u8)
+                .u16(sample.link)
+                .u16(sample.sample_type.bits());
+            start = end + SAMPLE_PADDING as u32;
+        }
+        shdr.name("EOS");
+        shdr.0.resize(shdr.0.len() + SHDR_LEN - NAME_LEN, 0);
+
+        let mut pdta = Buffer::default();
+        pdta.tag(b"pdta")
+            .chunk(b"phdr", &phdr.0)
+            .chunk(b"pbag", &preset_zones.bags.0)
+            .chunk(b"pmod", &preset_zones.mods.0)
+            .chunk(b"pgen", &preset_zones.gens.0)
+            .chunk(b"inst", &inst.0)
+            .chunk(b"ibag", &instrument_zones.bags.0)
+            .chunk(b"imod", &instrument_zones.mods.0)
+            .chunk(b"igen", &instrument_zones.gens.0)
+            .chunk(b"shdr", &shdr.0);
+        Ok(pdta.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::*;
+    use crate::{Error, GenParam, Settings, Synth};
+    use std::io::Cursor;
+
+    fn sine_font(bits: u16) -> (SoundFont, Vec<SamplePcm>) {
+        let amplitude = if bits > 16 { 0x7F_FFFF } else { 0x7FFF } as f64 * 0.5;
+        let period = 100;
+        let pcm = SamplePcm {
+            name: "Sine".into(),
+            channels: 1,
+            bits,
+            sample_rate: 44100,
+            data: (0..period * 10)
+                .map(|point| {
+                    let phase = point as f64 / period as f64 * std::f64::consts::TAU;
+                    (phase.sin() * amplitude) as i32
+                })
+                .collect(),
+            root_key: 69,
+            correction: 0,
+            loop_points: Some((0, period * 10)),
+        };
+
+        let zone = |gens: &[(GenParam, i16)]| Zone {
+            gens: gens
+                .iter()
+                .map(|(param, amount)| Generator {
+                    oper: *param as u16,
+                    amount: *amount,
+                })
+                .collect(),
+            mods: Vec::new(),
+        };
+
+        let font = SoundFont {
+            info: Info {
+                name: "Sine".into(),
+                ..Default::default()
+            },
+            presets: vec![Preset {
+                name: "Sine".into(),
+                zones: vec![zone(&[(GenParam::Instrument, 0)])],
+                ..Default::default()
+            }],
+            instruments: vec![Instrument {
+                name: "Sine".into(),
+                global: Some(zone(&[(GenParam::Attenuation, 60)])),
+                // the sample id is moved to the end on writing
+                zones: vec![zone(&[(GenParam::SampleId, 0), (GenParam::SampleMode, 1)])],
+            }],
+            samples: vec![Sample {
+                name: "Sine".into(),
+                sample_type: SampleType::MONO,
+                ..Default::default()
+            }],
+            sample_data: None,
+            sample_data_24: None,
+        };
+
+        (font, vec![pcm])
+    }
+
+    #[test]
+    fn write_parse() {
+        for bits in [16, 24].iter() {
+            let (font, data) = sine_font(*bits);
+            let mut buffer = Vec::new();
+            font.write(&mut buffer, &data).unwrap();
+
+            assert!(validate_soundfont(&buffer).is_valid());
+
+            let mut reader = Cursor::new(&buffer);
+            let parsed = SoundFont::read(&mut reader).unwrap();
+
+            assert_eq!(parsed.presets, font.presets);
+            assert_eq!(parsed.instruments[0].global, font.instruments[0].global);
+            assert_eq!(
+                parsed.instruments[0].zones[0].gens.last().unwrap().param(),
+                Some(GenParam::SampleId)
+            );
+            assert_eq!(parsed.samples[0].end, 1000);
+
+            let pcm = parsed.read_sample(&mut reader, 0).unwrap();
+
+            assert_eq!(pcm, data[0]);
+        }
+    }
+
+    #[test]
+    fn write_errors() {
+        let (mut font, mut data) = sine_font(16);
+        font.samples.push(font.samples[0].clone());
+        data.push(data[0].clone());
+        data[1].loop_points = Some((0, u32::MAX));
+
+        assert!(matches!(
+            font.write(&mut Vec::new(), &data),
+            Err(Error::Format {
+                reason: "Sample loop is out of range"
+            })
+        ));
+    }
+
+    fn render(path: &str) -> Vec<f32> {
+        let synth = Synth::new(Settings::new().unwrap()).unwrap();
+        synth.sfload(path, true).unwrap();
+
+        let mut samples = vec![0f32; 4410 * 2];
+        synth.note_on(0, 60, 127).unwrap();
+        synth.write(samples.as_mut_slice()).unwrap();
+        samples
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut reader = Cursor::new(std::fs::read("sf_/Boomwhacker.sf2").unwrap());
+        let font = SoundFont::read(&mut reader).unwrap();
+        let data = (0..font.samples.len())
+            .map(|index| font.read_sample(&mut reader, index))
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+
+        let path =
+            std::env::temp_dir().join(format!("fluidlite-round-trip-{}.sf2", std::process::id()));
+        font.save(&path, &data).unwrap();
+
+        assert_eq!(
+            render("sf_/Boomwhacker.sf2"),
+            render(path.to_str().unwrap())
+        );
+
+        let (font, data) = sine_font(24);
+        font.save(&path, &data).unwrap();
+
+        assert!(render(path.to_str().unwrap()).iter().any(|s| *s != 0.0));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,226 @@
+/*!
+SFZ instrument files
+
+The parser of SFZ text files which converts the regions into
+SoundFont zones, so the instrument can be played by synth.
+
+The headers `<control>`, `<global>`, `<master>`, `<group>` and `<region>`
+are supported with `#define` and `#include` directives. The regions inherit
+the opcodes of enclosing headers. The samples must be WAV files.
+
+The supported opcodes are mapped to generators as follows:
+
+| Opcode | Generator |
+|--------|-----------|
+| `sample` | `SampleId` (the sample from WAV file) |
+| `lokey`, `hikey`, `key` | `Keyrange` |
+| `lovel`, `hivel` | `Velrange` |
+| `pitch_keycenter` | `OverrideRootKey` |
+| `pitch_keytrack` | `ScaleTune` |
+| `transpose`, `tune` | `CoarseTune`, `FineTune` |
+| `volume` | `Attenuation` (attenuation only) |
+| `pan` | `Pan` |
+| `offset`, `end` | `StartAddrOfs`, `EndAddrOfs` |
+| `loop_mode` | `SampleMode` (`one_shot` as `no_loop`) |
+| `loop_start`, `loop_end` | `StartLoopAddOfs`, `EndLoopAddrOfs` |
+| `ampeg_delay`, `ampeg_attack`, `ampeg_hold`, `ampeg_decay`, `ampeg_sustain`, `ampeg_release` | `VolEnv*` |
+| `cutoff`, `resonance` | `FilterFc`, `FilterQ` |
+| `group` with the same `off_by` | `ExclusiveClass` |
+
+Other opcodes are kept in regions but ignored by conversion.
+
+```no_run
+use fluidlite::{sfz::Sfz, FsFileApi, Settings, Synth};
+
+let sfz = Sfz::load(&mut FsFileApi, "piano/piano.sfz").unwrap();
+println!("{}: {} regions", sfz.name, sfz.regions.len());
+
+let synth = Synth::new(Settings::new().unwrap()).unwrap();
+synth.sfload_sfz(&mut FsFileApi, "piano/piano.sfz", true).unwrap();
+```
+ */
+
+mod build;
+mod parse;
+
+use crate::{loader::read_file, sf2::Range, FileApi, Result};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/**
+The region with inherited opcodes
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Region {
+    /// The opcodes by names (the `key` is expanded)
+    pub opcodes: BTreeMap<String, String>,
+}
+
+impl Region {
+    /// Get the value of opcode
+    pub fn get(&self, opcode: &str) -> Option<&str> {
+        self.opcodes.get(opcode).map(String::as_str)
+    }
+
+    /// Get the sample path
+    pub fn sample(&self) -> Option<&str> {
+        self.get("sample")
+    }
+
+    /// Get the key range
+    pub fn key_range(&self) -> Range {
+        Range {
+            lo: self.key("lokey").unwrap_or(0),
+            hi: self.key("hikey").unwrap_or(127),
+        }
+    }
+
+    /// Get the velocity range
+    pub fn vel_range(&self) -> Range {
+        Range {
+            lo: self
+                .number("lovel")
+                .map_or(1, |vel| vel.clamp(0.0, 127.0) as u8),
+            hi: self
+                .number("hivel")
+                .map_or(127, |vel| vel.clamp(0.0, 127.0) as u8),
+        }
+    }
+
+    /// Get the key which sample plays at original pitch
+    pub fn key_center(&self) -> u8 {
+        self.key("pitch_keycenter").unwrap_or(60)
+    }
+
+    /// Get opcode as key (the number or the note name like `c#4`)
+    fn key(&self, opcode: &str) -> Option<u8> {
+        self.get(opcode).and_then(parse_key)
+    }
+
+    /// Get opcode as number
+    fn number(&self, opcode: &str) -> Option<f64> {
+        self.get(opcode)?.parse().ok()
+    }
+}
+
+/// Parse key number or note name (`c4` is the middle C)
+fn parse_key(value: &str) -> Option<u8> {
+    if let Ok(key) = value.parse::<i32>() {
+        return if (0..128).contains(&key) {
+            Some(key as u8)
+        } else {
+            None
+        };
+    }
+
+    let value = value.to_ascii_lowercase();
+    let mut chars = value.chars();
+    let mut key = match chars.next()? {
+        'c' => 0,
+        'd' => 2,
+        'e' => 4,
+        'f' => 5,
+        'g' => 7,
+        'a' => 9,
+        'b' => 11,
+        _ => return None,
+    };
+    let mut octave = chars.as_str();
+    if let Some(rest) = octave.strip_prefix('#') {
+        key += 1;
+        octave = rest;
+    } else if let Some(rest) = octave.strip_prefix('b') {
+        key -= 1;
+        octave = rest;
+    }
+    let key = key + (octave.parse::<i32>().ok()? + 1) * 12;
+    if (0..128).contains(&key) {
+        Some(key as u8)
+    } else {
+        None
+    }
+}
+
+/**
+The parsed SFZ instrument
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Sfz {
+    /// The instrument name (the file name without extension)
+    pub name: String,
+    /// The directory which sample paths are relative to
+    pub dir: PathBuf,
+    /// The opcodes of `<control>` header
+    pub control: BTreeMap<String, String>,
+    pub regions: Vec<Region>,
+}
+
+impl Sfz {
+    /**
+    Parse SFZ text
+
+    The `#include` directives aren't supported, use [`Sfz::load`] instead.
+     */
+    pub fn parse(text: &str) -> Result<Self> {
+        parse::parse(text, &mut |_| {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "Includes require file access",
+            )
+            .into())
+        })
+    }
+
+    /**
+    Load SFZ file using file API
+
+    The included files and samples are located relative to the directory
+    of loaded file.
+     */
+    pub fn load<F: FileApi, P: AsRef<Path>>(fileapi: &mut F, filename: P) -> Result<Self> {
+        let path = filename.as_ref();
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        let text = read_text(fileapi, path)?;
+        let mut sfz = parse::parse(&text, &mut |name| read_text(fileapi, &dir.join(name)))?;
+
+        sfz.name = path
+            .file_stem()
+            .map(|name| name.to_string_lossy().into())
+            .unwrap_or_default();
+        sfz.dir = dir;
+        Ok(sfz)
+    }
+
+    /// Get the path of region sample using `default_path` of control
+    pub fn sample_path(&self, region: &Region) -> Option<PathBuf> {
+        let sample = region.sample()?;
+        let default_path = self.control.get("default_path").map_or("", String::as_str);
+        // the Windows separators are common in SFZ files
+        let path = format!("{}{}", default_path, sample).replace('\\', "/");
+        Some(self.dir.join(path))
+    }
+}
+
+fn read_text<F: FileApi>(fileapi: &mut F, path: &Path) -> Result<String> {
+    Ok(String::from_utf8_lossy(&read_file(fileapi, path)?).into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn key_names() {
+        assert_eq!(parse_key("60"), Some(60));
+        assert_eq!(parse_key("c4"), Some(60));
+        assert_eq!(parse_key("C#4"), Some(61));
+        assert_eq!(parse_key("db4"), Some(61));
+        assert_eq!(parse_key("a-1"), Some(9));
+        assert_eq!(parse_key("g9"), Some(127));
+        assert_eq!(parse_key("128"), None);
+        assert_eq!(parse_key("h4"), None);
+    }
+}
@@ -0,0 +1,475 @@
+use super::{Region, Sfz};
+use crate::{
+    sf2::{
+        Generator, Info, Instrument, Preset, Range, Sample, SamplePcm, SampleType, SoundFont, Zone,
+    },
+    Error, FileApi, FileReader, GenParam, Result,
+};
+use std::{collections::HashMap, path::PathBuf};
+
+/// The lowest value of time generators (1 ms)
+const MIN_TIMECENTS: i32 = -12000;
+
+/// The offsets in sample points are split to fine and coarse generators
+const COARSE_OFFSET: i32 = 32768;
+
+/// The samples of single file by channels
+#[derive(Clone, Copy)]
+enum FileSamples {
+    Mono(u16),
+    Stereo(u16, u16),
+}
+
+/// The builder of SoundFont
+struct Builder<'a, F> {
+    fileapi: &'a mut F,
+    files: HashMap<PathBuf, FileSamples>,
+    samples: Vec<Sample>,
+    data: Vec<SamplePcm>,
+    zones: Vec<Zone>,
+}
+
+impl<'a, F: FileApi> Builder<'a, F> {
+    /// Load samples of file once
+    fn file(&mut self, path: PathBuf) -> Result<FileSamples> {
+        if let Some(samples) = self.files.get(&path) {
+            return Ok(*samples);
+        }
+
+        let mut reader =
+            FileReader::open(self.fileapi, &path).map_err(|error| Error::file(&path, error))?;
+        let mut pcm = SamplePcm::read_wav(&mut reader)?;
+        pcm.name = path
+            .file_stem()
+            .map(|name| name.to_string_lossy().into())
+            .unwrap_or_default();
+
+        let samples = if pcm.channels == 2 {
+            let left = self.sample(pcm.channel(0), SampleType::LEFT, 1)?;
+            let right = self.sample(pcm.channel(1), SampleType::RIGHT, -1)?;
+            FileSamples::Stereo(left, right)
+        } else {
+            FileSamples::Mono(self.sample(pcm.channel(0), SampleType::MONO, 0)?)
+        };
+        self.files.insert(path, samples);
+        Ok(samples)
+    }
+
+    /// Add sample linked to sample at relative position
+    fn sample(&mut self, pcm: SamplePcm, sample_type: SampleType, link: isize) -> Result<u16> {
+        let index = self.samples.len();
+        if index >= u16::MAX as usize {
+            return Err(Error::Format {
+                reason: "Too many samples",
+            });
+        }
+        self.samples.push(Sample {
+            name: pcm.name.clone(),
+            link: if link == 0 {
+                0
+            } else {
+                (index as isize + link) as u16
+            },
+            sample_type,
+            ..Default::default()
+        });
+        self.data.push(pcm);
+        Ok(index as u16)
+    }
+
+    fn region(&mut self, sfz: &Sfz, region: &Region) -> Result<()> {
+        let path = match sfz.sample_path(region) {
+            // the generated samples like `*sine` aren't supported
+            Some(path) if !region.sample().unwrap_or("").starts_with('*') => path,
+            _ => return Ok(()),
+        };
+        let (left, right) = match self.file(path)? {
+            FileSamples::Mono(sample) => (sample, None),
+            FileSamples::Stereo(left, right) => (left, Some(right)),
+        };
+
+        let pan = region.number("pan").unwrap_or(0.0) * 5.0;
+        match right {
+            None => {
+                let zone = self.zone(region, left, pan)?;
+                self.zones.extend(zone);
+            }
+            Some(right) => {
+                let zone = self.zone(region, left, pan - 500.0)?;
+                self.zones.extend(zone);
+                let zone = self.zone(region, right, pan + 500.0)?;
+                self.zones.extend(zone);
+            }
+        }
+        Ok(())
+    }
+
+    /// Make zone or nothing when region is silent
+    fn zone(&self, region: &Region, sample: u16, pan: f64) -> Result<Option<Zone>> {
+        let pcm = &self.data[sample as usize];
+        let frames = pcm.frames() as i32;
+        let mut gens = Gens::default();
+
+        let keys = region.key_range();
+        if keys != Range::ALL {
+            gens.range(GenParam::Keyrange, keys);
+        }
+        let vels = region.vel_range();
+        if vels != Range::ALL {
+            gens.range(GenParam::Velrange, vels);
+        }
+
+        if region.get("pitch_keycenter") != Some("sample") {
+            gens.set(GenParam::OverrideRootKey, region.key_center() as i32);
+        }
+        let tune = region.number("tune").unwrap_or(0.0) as i32;
+        let transpose = region.number("transpose").unwrap_or(0.0) as i32;
+        gens.set_nonzero(GenParam::CoarseTune, transpose + tune / 100);
+        gens.set_nonzero(GenParam::FineTune, tune % 100);
+        if let Some(keytrack) = region.number("pitch_keytrack") {
+            gens.set(GenParam::ScaleTune, keytrack.clamp(0.0, 1200.0) as i32);
+        }
+
+        // only attenuation can be represented
+        let volume = region.number("volume").unwrap_or(0.0);
+        gens.set_nonzero(
+            GenParam::Attenuation,
+            (-volume * 10.0).clamp(0.0, 1440.0) as i32,
+        );
+        gens.set_nonzero(GenParam::Pan, pan.clamp(-500.0, 500.0) as i32);
+
+        if let Some(offset) = region.number("offset") {
+            gens.offset(
+                GenParam::StartAddrOfs,
+                GenParam::StartAddrCoarseOfs,
+                (offset as i32).clamp(0, frames),
+            );
+        }
+        if let Some(end) = region.number("end") {
+            // the end is inclusive and zero end means silent region
+            if end <= 0.0 {
+                return Ok(None);
+            }
+            let end = (end + 1.0).min(frames as f64) as i32;
+            gens.offset(
+                GenParam::EndAddrOfs,
+                GenParam::EndAddrCoarseOfs,
+                end - frames,
+            );
+        }
+
+        // the loop points are kept inside sample
+        let point = |point: f64| point.clamp(0.0, frames as f64) as i32;
+        let (start_loop, end_loop) = pcm.loop_points.unwrap_or((0, frames as u32));
+        let (start_loop, end_loop) = (point(start_loop as _), point(end_loop as _));
+        if let Some(start) = region.number("loop_start") {
+            gens.offset(
+                GenParam::StartLoopAddOfs,
+                GenParam::Startloopaddrcoarseofs,
+                point(start) - start_loop,
+            );
+        }
+        if let Some(end) = region.number("loop_end") {
+            gens.offset(
+                GenParam::EndLoopAddrOfs,
+                GenParam::EndLoopAddrCoarseOfs,
+                point(end + 1.0) - end_loop,
+            );
+        }
+
+        let has_loop = pcm.loop_points.is_some()
+            || region.get("loop_start").is_some()
+            || region.get("loop_end").is_some();
+        let mode = match region.get("loop_mode") {
+            Some("loop_continuous") => 1,
+            Some("loop_sustain") => 3,
+            Some(_) => 0,
+            None if has_loop => 1,
+            None => 0,
+        };
+        gens.set_nonzero(GenParam::SampleMode, mode);
+
+        let envelope = [
+            ("ampeg_delay", GenParam::VolEnvDelay),
+            ("ampeg_attack", GenParam::VolEnvAttack),
+            ("ampeg_hold", GenParam::VolEnvHold),
+            ("ampeg_decay", GenParam::VolEnvDecay),
+            ("ampeg_release", GenParam::VolEnvRelease),
+        ];
+        for (opcode, param) in envelope.iter() {
+            if let Some(time) = region.number(opcode) {
+                gens.set(*param, timecents(time));
+            }
+        }
+        if let Some(sustain) = region.number("ampeg_sustain") {
+            // the level in percents to attenuation in centibels
+            let level = (sustain / 100.0).clamp(0.0, 1.0);
+            let attenuation = if level > 0.0 {
+                (-200.0 * level.log10()).min(1440.0)
+            } else {
+                1440.0
+            };
+            gens.set(GenParam::VolEnvSustain, attenuation as i32);
+        }
+
+        if let Some(cutoff) = region.number("cutoff") {
+            // the absolute cents relative to 8.176 Hz
+            let cents = 1200.0 * (cutoff.max(1.0) / 8.176).log2();
+            gens.set(GenParam::FilterFc, cents.clamp(1500.0, 13500.0) as i32);
+        }
+        if let Some(resonance) = region.number("resonance") {
+            gens.set(
+                GenParam::FilterQ,
+                (resonance * 10.0).clamp(0.0, 960.0) as i32,
+            );
+        }
+
+        let group = region.number("group").unwrap_or(0.0) as i32;
+        if group > 0 && region.number("off_by") == Some(group as f64) {
+            gens.set(GenParam::ExclusiveClass, group.min(127));
+        }
+
+        gens.set(GenParam::SampleId, sample as i32);
+        Ok(Some(Zone {
+            gens: gens.0,
+            mods: Vec::new(),
+        }))
+    }
+}
+
+/// Convert time in seconds to timecents
+fn timecents(time: f64) -> i32 {
+    if time <= 0.0 {
+        MIN_TIMECENTS
+    } else {
+        ((1200.0 * time.log2()) as i32).clamp(MIN_TIMECENTS, 8000)
+    }
+}
+
+#[derive(Default)]
+struct Gens(Vec<Generator>);
+
+impl Gens {
+    fn set(&mut self, param: GenParam, amount: i32) {
+        self.0.push(Generator {
+            oper: param as u16,
+            amount: amount.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+        });
+    }
+
+    fn set_nonzero(&mut self, param: GenParam, amount: i32) {
+        if amount != 0 {
+            self.set(param, amount);
+        }
+    }
+
+    fn range(&mut self, param: GenParam, range: Range) {
+        self.set(param, i16::from_le_bytes([range.lo, range.hi]) as i32);
+    }
+
+    /// Set offset using fine and coarse generators
+    fn offset(&mut self, fine: GenParam, coarse: GenParam, offset: i32) {
+        // the remainder has the same sign as offset
+        self.set_nonzero(fine, offset % COARSE_OFFSET);
+        self.set_nonzero(coarse, offset / COARSE_OFFSET);
+    }
+}
+
+/**
+Conversion to SoundFont
+ */
+impl Sfz {
+    /**
+    Convert to SoundFont with sample data
+
+    The result has single preset `0:0` with single instrument
+    which has a zone for each region (or two zones for stereo samples).
+    The samples are loaded from WAV files using file API.
+
+    The result can be written using [`SoundFont::write`].
+     */
+    pub fn to_soundfont<F: FileApi>(&self, fileapi: &mut F) -> Result<(SoundFont, Vec<SamplePcm>)> {
+        let mut builder = Builder {
+            fileapi,
+            files: HashMap::new(),
+            samples: Vec::new(),
+            data: Vec::new(),
+            zones: Vec::new(),
+        };
+        for region in &self.regions {
+            builder.region(self, region)?;
+        }
+
+        let name = if self.name.is_empty() {
+            "SFZ"
+        } else {
+            &self.name
+        };
+        let mut instrument_zone = Gens::default();
+        instrument_zone.set(GenParam::Instrument, 0);
+
+        let font = SoundFont {
+            info: Info {
+                name: name.into(),
+                ..Default::default()
+            },
+            presets: vec![Preset {
+                name: name.into(),
+                zones: vec![Zone {
+                    gens: instrument_zone.0,
+                    mods: Vec::new(),
+                }],
+                ..Default::default()
+            }],
+            instruments: vec![Instrument {
+                name: name.into(),
+                global: None,
+                zones: builder.zones,
+            }],
+            samples: builder.samples,
+            sample_data: None,
+            sample_data_24: None,
+        };
+        Ok((font, builder.data))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{sf2::validate_soundfont, FsFileApi};
+    use std::{fs::File, path::Path};
+
+    /// Write test instrument to directory
+    fn write_sfz(dir: &Path) {
+        std::fs::create_dir_all(dir.join("samples")).unwrap();
+
+        let period = 100;
+        let sine = |channels: u16| SamplePcm {
+            channels,
+            bits: 16,
+            sample_rate: 44100,
+            data: (0..period * 100 * channels as usize)
+                .map(|point| {
+                    let frame = point / channels as usize;
+                    let phase = frame as f64 / period as f64 * std::f64::consts::TAU;
+                    (phase.sin() * 16000.0) as i32
+                })
+                .collect(),
+            root_key: 60,
+            loop_points: Some((0, period as u32 * 100)),
+            ..Default::default()
+        };
+        for (name, channels) in [("mono.wav", 1), ("stereo.wav", 2)].iter() {
+            let mut file = File::create(dir.join("samples").join(name)).unwrap();
+            sine(*channels).write_wav(&mut file).unwrap();
+        }
+
+        std::fs::write(
+            dir.join("test.sfz"),
+            "#define $CENTER 69\n\
+             <control> default_path=samples/\n\
+             <global> ampeg_release=0.5 ampeg_sustain=50\n\
+             #include \"regions.sfz\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("regions.sfz"),
+            "<region> sample=mono.wav lokey=0 hikey=63 pitch_keycenter=$CENTER volume=-6\n\
+             <region> sample=stereo.wav lokey=64 hikey=127 loop_mode=no_loop cutoff=2000\n",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn convert() {
+        let dir =
+            std::env::temp_dir().join(format!("fluidlite-sfz-convert-{}", std::process::id()));
+        write_sfz(&dir);
+
+        let sfz = Sfz::load(&mut FsFileApi, dir.join("test.sfz")).unwrap();
+
+        assert_eq!(sfz.name, "test");
+        assert_eq!(sfz.regions.len(), 2);
+
+        let (font, data) = sfz.to_soundfont(&mut FsFileApi).unwrap();
+
+        assert_eq!(font.samples.len(), 3);
+        assert_eq!(data.len(), 3);
+        assert_eq!(font.samples[1].sample_type, SampleType::LEFT);
+        assert_eq!(font.samples[1].link, 2);
+        assert_eq!(font.samples[2].link, 1);
+
+        let zones = &font.instruments[0].zones;
+        assert_eq!(zones.len(), 3);
+        assert_eq!(zones[0].key_range(), Some(Range { lo: 0, hi: 63 }));
+        assert_eq!(zones[0].sample(), Some(0));
+        let amount = |zone: &Zone, param| zone.gen(param).map(|gen| gen.amount);
+        assert_eq!(amount(&zones[0], GenParam::OverrideRootKey), Some(69));
+        assert_eq!(amount(&zones[0], GenParam::Attenuation), Some(60));
+        assert_eq!(amount(&zones[0], GenParam::SampleMode), Some(1));
+        assert_eq!(amount(&zones[0], GenParam::VolEnvSustain), Some(60));
+        assert_eq!(amount(&zones[0], GenParam::VolEnvRelease), Some(-1200));
+        assert_eq!(amount(&zones[1], GenParam::Pan), Some(-500));
+        assert_eq!(amount(&zones[2], GenParam::Pan), Some(500));
+        assert_eq!(amount(&zones[2], GenParam::SampleMode), None);
+        assert_eq!(amount(&zones[2], GenParam::FilterFc), Some(9521));
+
+        let mut buffer = Vec::new();
+        font.write(&mut buffer, &data).unwrap();
+
+        assert!(validate_soundfont(&buffer).is_valid());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn huge_offsets() {
+        let dir = std::env::temp_dir().join(format!("fluidlite-sfz-huge-{}", std::process::id()));
+        write_sfz(&dir);
+        std::fs::write(
+            dir.join("huge.sfz"),
+            "<region> sample=samples/mono.wav end=4000000000 \
+             loop_start=4000000000 loop_end=4000000000 offset=4000000000\n",
+        )
+        .unwrap();
+
+        let sfz = Sfz::load(&mut FsFileApi, dir.join("huge.sfz")).unwrap();
+        let (font, _) = sfz.to_soundfont(&mut FsFileApi).unwrap();
+
+        // the offsets are clamped to the end of sample
+        let zone = &font.instruments[0].zones[0];
+        let amount = |param| zone.gen(param).map(|gen| gen.amount);
+        assert_eq!(amount(GenParam::StartAddrOfs), Some(10000));
+        assert_eq!(amount(GenParam::EndAddrOfs), None);
+        assert_eq!(amount(GenParam::StartLoopAddOfs), Some(10000));
+        assert_eq!(amount(GenParam::EndLoopAddrOfs), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn load_synth() {
+        let dir = std::env::temp_dir().join(format!("fluidlite-sfz-synth-{}", std::process::id()));
+        write_sfz(&dir);
+
+        let synth = crate::Synth::new(crate::Settings::new().unwrap()).unwrap();
+        let id = synth
+            .sfload_sfz(&mut FsFileApi, dir.join("test.sfz"), true)
+            .unwrap();
+
+        // the other fonts are still loaded
+        synth.sfload("sf_/Boomwhacker.sf2", false).unwrap();
+
+        assert_eq!(synth.sfcount(), 2);
+
+        let mut samples = [0f32; 4410 * 2];
+        synth.program_select(0, id, 0, 0).unwrap();
+        synth.note_on(0, 60, 127).unwrap();
+        synth.write(samples.as_mut()).unwrap();
+
+        assert!(samples.iter().any(|sample| *sample != 0.0));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
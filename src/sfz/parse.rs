@@ -0,0 +1,342 @@
+use super::{Region, Sfz};
+use crate::{Error, Result};
+use std::collections::BTreeMap;
+
+/// The maximum nesting of included files
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// The old names of opcodes
+const ALIASES: [(&str, &str); 6] = [
+    ("loopmode", "loop_mode"),
+    ("loopstart", "loop_start"),
+    ("loopend", "loop_end"),
+    ("fil_cutoff", "cutoff"),
+    ("fil_resonance", "resonance"),
+    ("gain", "volume"),
+];
+
+type Opcodes = BTreeMap<String, String>;
+
+/// The header which opcodes belong to
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Section {
+    /// Opcodes before the first header
+    None,
+    Control,
+    Global,
+    Master,
+    Group,
+    Region,
+    /// The headers like `<curve>` or `<effect>`
+    Unsupported,
+}
+
+struct Parser<'a> {
+    include: &'a mut dyn FnMut(&str) -> Result<String>,
+    defines: Vec<(String, String)>,
+    section: Section,
+    control: Opcodes,
+    global: Opcodes,
+    master: Opcodes,
+    group: Opcodes,
+    region: Opcodes,
+    regions: Vec<Region>,
+}
+
+/// Parse SFZ text loading the included files using callback
+pub(super) fn parse(text: &str, include: &mut dyn FnMut(&str) -> Result<String>) -> Result<Sfz> {
+    let mut parser = Parser {
+        include,
+        defines: Vec::new(),
+        section: Section::None,
+        control: Opcodes::new(),
+        global: Opcodes::new(),
+        master: Opcodes::new(),
+        group: Opcodes::new(),
+        region: Opcodes::new(),
+        regions: Vec::new(),
+    };
+    parser.text(text, 0)?;
+    parser.finish_region();
+
+    Ok(Sfz {
+        control: parser.control,
+        regions: parser.regions,
+        ..Default::default()
+    })
+}
+
+fn error(line: usize, reason: &'static str) -> Error {
+    Error::Parse { line, reason }
+}
+
+impl<'a> Parser<'a> {
+    fn text(&mut self, text: &str, depth: usize) -> Result<()> {
+        let text = strip_comments(text);
+
+        for (index, line) in text.lines().enumerate() {
+            let line_num = index + 1;
+            let line = line.trim();
+
+            if let Some(rest) = line.strip_prefix("#define") {
+                let mut parts = rest.trim().splitn(2, char::is_whitespace);
+                let name = parts.next().unwrap_or("");
+                if !name.starts_with('$') || name.len() < 2 {
+                    return Err(error(line_num, "Invalid define"));
+                }
+                let value = self.substitute(parts.next().unwrap_or("").trim());
+                self.defines.retain(|(other, _)| other != name);
+                self.defines.push((name.into(), value));
+                // the longer names must be replaced first
+                self.defines
+                    .sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+            } else if let Some(rest) = line.strip_prefix("#include") {
+                let name = rest.trim().trim_matches('"');
+                if name.is_empty() {
+                    return Err(error(line_num, "Invalid include"));
+                }
+                if depth >= MAX_INCLUDE_DEPTH {
+                    return Err(error(line_num, "Too deep includes"));
+                }
+                let name = self.substitute(name);
+                let text = (self.include)(&name)?;
+                self.text(&text, depth + 1)?;
+            } else {
+                let line = self.substitute(line);
+                self.line(&line, line_num)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replace defined variables
+    fn substitute(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        if text.contains('$') {
+            for (name, value) in &self.defines {
+                text = text.replace(name.as_str(), value);
+            }
+        }
+        text
+    }
+
+    fn line(&mut self, line: &str, line_num: usize) -> Result<()> {
+        let mut rest = line.trim_start();
+
+        while !rest.is_empty() {
+            if let Some(header) = rest.strip_prefix('<') {
+                let end = header
+                    .find('>')
+                    .ok_or_else(|| error(line_num, "Unterminated header"))?;
+                self.header(header[..end].trim());
+                rest = header[end + 1..].trim_start();
+            } else {
+                let eq = rest
+                    .find('=')
+                    .ok_or_else(|| error(line_num, "Invalid opcode"))?;
+                let name = rest[..eq].trim();
+                if name.is_empty() || name.contains(char::is_whitespace) {
+                    return Err(error(line_num, "Invalid opcode"));
+                }
+                let value = &rest[eq + 1..];
+                let end = value_end(value);
+                self.opcode(name, value[..end].trim());
+                rest = value[end..].trim_start();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn header(&mut self, name: &str) {
+        self.finish_region();
+
+        self.section = match name {
+            "control" => Section::Control,
+            "global" => {
+                self.global.clear();
+                self.master.clear();
+                self.group.clear();
+                Section::Global
+            }
+            "master" => {
+                self.master.clear();
+                self.group.clear();
+                Section::Master
+            }
+            "group" => {
+                self.group.clear();
+                Section::Group
+            }
+            "region" => Section::Region,
+            _ => Section::Unsupported,
+        };
+    }
+
+    fn opcode(&mut self, name: &str, value: &str) {
+        let opcodes = match self.section {
+            Section::Control => &mut self.control,
+            // the opcodes without header apply to all regions
+            Section::None | Section::Global => &mut self.global,
+            Section::Master => &mut self.master,
+            Section::Group => &mut self.group,
+            Section::Region => &mut self.region,
+            Section::Unsupported => return,
+        };
+
+        let name = ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == name)
+            .map_or(name, |(_, name)| name);
+
+        if name == "key" {
+            for name in ["lokey", "hikey", "pitch_keycenter"].iter() {
+                opcodes.insert(name.to_string(), value.into());
+            }
+        } else {
+            opcodes.insert(name.into(), value.into());
+        }
+    }
+
+    /// Add region with inherited opcodes
+    fn finish_region(&mut self) {
+        if self.section != Section::Region {
+            return;
+        }
+        let mut opcodes = self.global.clone();
+        for (name, value) in self.master.iter().chain(&self.group).chain(&self.region) {
+            opcodes.insert(name.clone(), value.clone());
+        }
+        self.region.clear();
+        self.section = Section::None;
+        self.regions.push(Region { opcodes });
+    }
+}
+
+/**
+Find the end of opcode value
+
+The value (like sample path) may contain spaces, so it ends
+before the next opcode or header.
+ */
+fn value_end(value: &str) -> usize {
+    for (pos, c) in value.char_indices() {
+        if !c.is_whitespace() {
+            continue;
+        }
+        let next = value[pos..].trim_start();
+        if next.starts_with('<') || is_opcode(next) {
+            return pos;
+        }
+    }
+    value.len()
+}
+
+fn is_opcode(text: &str) -> bool {
+    let name = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+        .map_or(text, |end| &text[..end]);
+    !name.is_empty() && text[name.len()..].starts_with('=')
+}
+
+/// Replace comments with spaces keeping the lines
+fn strip_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    loop {
+        let line = rest.find("//").unwrap_or(usize::MAX);
+        let block = rest.find("/*").unwrap_or(usize::MAX);
+        if line < block {
+            out.push_str(&rest[..line]);
+            rest = &rest[line..];
+            rest = &rest[rest.find('\n').unwrap_or(rest.len())..];
+        } else if block < line {
+            out.push_str(&rest[..block]);
+            let end = rest[block..]
+                .find("*/")
+                .map_or(rest.len(), |end| block + end + 2);
+            // keep the line numbers
+            out.extend(rest[block..end].chars().filter(|c| *c == '\n'));
+            out.push(' ');
+            rest = &rest[end..];
+        } else {
+            out.push_str(rest);
+            return out;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TEXT: &str = r#"
+// comment
+#define $KEY 60
+<control> default_path=samples\ /* block
+comment */
+<global> volume=-6 loop_mode=one_shot
+<group> lovel=1 hivel=64
+#include "inc.sfz"
+<region> sample=soft piano.wav key=$KEY
+<region> sample=soft piano.wav lokey=61 hikey=c#5 pitch_keycenter=62 volume=-3
+<group> lovel=65
+<region> sample=loud.wav
+<curve> curve_index=1 v000=0
+"#;
+
+    #[test]
+    fn parse_text() {
+        let mut includes = Vec::new();
+        let sfz = parse(TEXT, &mut |name| {
+            includes.push(name.to_string());
+            Ok("<region> sample=included.wav\n".into())
+        })
+        .unwrap();
+
+        assert_eq!(includes, vec!["inc.sfz"]);
+        assert_eq!(sfz.control["default_path"], "samples\\");
+        assert_eq!(sfz.regions.len(), 4);
+
+        let region = &sfz.regions[1];
+        assert_eq!(region.sample(), Some("soft piano.wav"));
+        assert_eq!(region.get("volume"), Some("-6"));
+        assert_eq!(region.get("loop_mode"), Some("one_shot"));
+        assert_eq!(region.key_range(), crate::sf2::Range { lo: 60, hi: 60 });
+        assert_eq!(region.vel_range().hi, 64);
+        assert_eq!(region.key_center(), 60);
+
+        let region = &sfz.regions[2];
+        assert_eq!(region.get("volume"), Some("-3"));
+        assert_eq!(region.key_range(), crate::sf2::Range { lo: 61, hi: 73 });
+        assert_eq!(region.key_center(), 62);
+
+        // the group opcodes are reset
+        let region = &sfz.regions[3];
+        assert_eq!(region.vel_range(), crate::sf2::Range { lo: 65, hi: 127 });
+        assert_eq!(region.get("volume"), Some("-6"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let no_include = &mut |_: &str| -> Result<String> { unreachable!() };
+
+        assert!(matches!(
+            parse("<region sample=a.wav", no_include),
+            Err(Error::Parse { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse("\n<region> sample", no_include),
+            Err(Error::Parse { line: 2, .. })
+        ));
+        assert!(matches!(
+            parse("#define KEY 60", no_include),
+            Err(Error::Parse { line: 1, .. })
+        ));
+
+        let recursive = &mut |_: &str| Ok("#include \"self.sfz\"".to_string());
+        assert!(parse("#include \"self.sfz\"", recursive).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 mod chorus;
 mod count;
+mod fallback;
 mod font;
 mod gen;
 mod loader;
@@ -8,15 +9,26 @@ mod midi;
 mod misc;
 mod params;
 mod reverb;
+mod state;
 mod tuning;
 mod write;
 
-use std::ptr::NonNull;
+use std::{cell::RefCell, ptr::NonNull};
 
+pub use self::chorus::{ChorusMode, ChorusParams};
+pub use self::fallback::{FallbackEvent, PresetFallback};
+pub use self::font::{FontIter, PresetMatch};
+pub use self::gen::GenParam;
+pub use self::params::InterpMethod;
+pub use self::reverb::ReverbParams;
+pub use self::state::{ChannelState, ProgramState, SynthState, TuningState};
 pub use self::tuning::TuningIter;
 pub use self::write::IsSamples;
 
-use crate::{ffi, result_from_ptr, Result, Settings, SettingsRef};
+use self::{fallback::FallbackState, loader::SharedFiles};
+use crate::{
+    ffi, private::HasHandle, result_from_ptr, Chan, IsSettings, Result, Settings, SettingsRef,
+};
 
 /**
 The synth object
@@ -32,10 +44,23 @@ the audio device and create a background audio thread.
 
 The API for sending MIDI events is probably what you expect:
 `Synth::noteon()`, `Synth::noteoff()`, ...
+
+# Real-time safety
+
+The MIDI channel messages (like `Synth::note_on()` or `Synth::cc()`) and
+`Synth::write()` never allocate on the Rust side, even when they fail.
+The error message reported by the library is copied into
+the fixed-capacity [`FluidError`](crate::FluidError) buffer,
+so it is safe to call these from the audio callback.
+The only exception is `Synth::program_change()` with enabled
+preset fallback, which searches the loaded fonts
+(see [`Synth::set_preset_fallback`]).
  */
-#[repr(transparent)]
 pub struct Synth {
     handle: NonNull<ffi::fluid_synth_t>,
+    fallback: RefCell<FallbackState>,
+    /// The files of in-memory loader (see [`Synth::sfload_bytes`])
+    memory: RefCell<Option<SharedFiles>>,
 }
 
 unsafe impl Send for Synth {}
@@ -47,8 +72,24 @@ impl Synth {
     As soon as the synthesizer is created, it will start playing.
      */
     pub fn new(settings: Settings) -> Result<Self> {
-        result_from_ptr(unsafe { ffi::new_fluid_synth(settings.into_ptr().as_ptr()) })
-            .map(|handle| Self { handle })
+        let drums = settings
+            .str_("synth.drums-channel.active")
+            .and_then(|active| active.get().map(|active| active == "yes"))
+            .unwrap_or(true);
+        let handle =
+            result_from_ptr(unsafe { ffi::new_fluid_synth(settings.into_ptr().as_ptr()) })?;
+        let channels = unsafe { ffi::fluid_synth_count_midi_channels(handle.as_ptr()) as u32 };
+
+        Ok(Self {
+            handle,
+            fallback: RefCell::new(FallbackState::new(
+                channels,
+                Chan::new(9)
+                    .ok()
+                    .filter(|chan| drums && chan.get() < channels),
+            )),
+            memory: RefCell::new(None),
+        })
     }
 
     /**
@@ -81,11 +122,106 @@ impl Drop for Synth {
     }
 }
 
+impl HasHandle for Synth {
+    type Handle = ffi::fluid_synth_t;
+
+    fn get_handle(&self) -> NonNull<Self::Handle> {
+        self.handle
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::{Settings, Synth};
+    use crate::Error;
     use byte_slice_cast::AsByteSlice;
-    use std::{fs::File, io::Write};
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+        fs::File,
+        io::Write,
+    };
+
+    struct CountingAlloc;
+
+    thread_local! {
+        static ALLOCS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn count_alloc() {
+        let _ = ALLOCS.try_with(|allocs| allocs.set(allocs.get() + 1));
+    }
+
+    fn allocs() -> usize {
+        ALLOCS.with(|allocs| allocs.get())
+    }
+
+    unsafe impl GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            count_alloc();
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            count_alloc();
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOC: CountingAlloc = CountingAlloc;
+
+    #[test]
+    fn realtime_no_alloc() {
+        let synth = Synth::new(Settings::new().unwrap()).unwrap();
+        let mut samples = [0f32; 64 * 2];
+
+        let before = allocs();
+
+        // no soundfont loaded so note on fails
+        assert!(synth.note_on(0, 60, 127).is_err());
+        let _ = synth.note_off(0, 60);
+        synth.cc(0, 64, 127).unwrap();
+        synth.pitch_bend(0, 8192).unwrap();
+        synth.write(samples.as_mut()).unwrap();
+
+        assert_eq!(allocs(), before);
+    }
+
+    #[test]
+    fn invalid_args() {
+        let synth = Synth::new(Settings::new().unwrap()).unwrap();
+
+        assert_eq!(
+            synth.note_on(16, 60, 127),
+            Err(Error::InvalidChannel { chan: 16, max: 15 })
+        );
+        assert_eq!(
+            synth.note_on(0, 128, 127),
+            Err(Error::InvalidKey { key: 128 })
+        );
+        assert_eq!(
+            synth.note_on(0, 60, 128),
+            Err(Error::InvalidVel { vel: 128 })
+        );
+        assert_eq!(synth.note_on(0, 60, 127), Err(Error::NoPreset { chan: 0 }));
+        assert_eq!(
+            synth.cc(0, 7, 128),
+            Err(Error::InvalidValue { val: 128, max: 127 })
+        );
+        // the message of library is kept
+        assert!(matches!(
+            synth.sfload("sf_/NotExists.sf2", true),
+            Err(Error::FontLoad {
+                path,
+                error: Some(_),
+            }) if path.as_os_str() == "sf_/NotExists.sf2"
+        ));
+    }
 
     #[test]
     fn synth_sf2() {
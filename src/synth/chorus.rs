@@ -3,23 +3,21 @@ use crate::{ffi, Synth};
 /**
 Chorus type
  */
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[repr(u32)]
 pub enum ChorusMode {
+    #[default]
     Sine = ffi::fluid_chorus_mod_FLUID_CHORUS_MOD_SINE as _,
     Triangle = ffi::fluid_chorus_mod_FLUID_CHORUS_MOD_TRIANGLE as _,
 }
 
-impl Default for ChorusMode {
-    fn default() -> Self {
-        ChorusMode::Sine
-    }
-}
-
 /**
 Chorus parameters
  */
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChorusParams {
     pub nr: u32,
     pub level: f64,
@@ -0,0 +1,314 @@
+use crate::{Bank, Chan, Ctrl, FontId, IsFont, Prog, Synth, Val};
+
+/**
+The policy of selecting presets which aren't provided by loaded fonts
+
+The drum channels fall back to the standard drum kit
+([`Bank::DRUMS`], [`Prog::STANDARD_DRUMS`]) with any policy except `None`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PresetFallback {
+    /// Keep the channel silent (the default behavior of library)
+    #[default]
+    None,
+    /// Use the same program in bank `0`
+    SameProgram,
+    /**
+    Use the same program in bank `0` or the nearest program
+    of the same General MIDI family (see [`GmFamily`](crate::GmFamily))
+    in requested bank and then in bank `0`
+     */
+    GmFamily,
+}
+
+/**
+The fallback of preset reported to handler
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FallbackEvent {
+    pub chan: Chan,
+    /// The requested bank and program
+    pub requested: (Bank, Prog),
+    /// The selected font, bank and program (`None` when nothing is found)
+    pub selected: Option<(FontId, Bank, Prog)>,
+}
+
+/// The handler of preset fallbacks
+type FallbackFn = Box<dyn FnMut(&FallbackEvent) + Send>;
+
+/**
+The state of fallback policy
+
+The banks are tracked per channel, because the library doesn't report
+the bank selected on a channel until the program is changed.
+ */
+pub(crate) struct FallbackState {
+    policy: PresetFallback,
+    handler: Option<FallbackFn>,
+    banks: Vec<u32>,
+    /// The last bank select MSB per channel like `bank_msb` of library
+    msbs: Vec<u32>,
+    drums: Option<Chan>,
+}
+
+impl FallbackState {
+    pub(crate) fn new(channels: u32, drums: Option<Chan>) -> Self {
+        Self {
+            policy: PresetFallback::None,
+            handler: None,
+            banks: vec![0; channels as usize],
+            msbs: vec![0; channels as usize],
+            drums,
+        }
+    }
+
+    fn bank(&self, chan: Chan) -> Bank {
+        if Some(chan) == self.drums {
+            Bank::DRUMS
+        } else {
+            Bank::saturating(self.banks[chan.get() as usize])
+        }
+    }
+
+    fn set_bank(&mut self, chan: Chan, bank: u32) {
+        if let Some(slot) = self.banks.get_mut(chan.get() as usize) {
+            *slot = bank;
+        }
+    }
+}
+
+/**
+Preset fallback
+ */
+impl Synth {
+    /**
+    Set the policy of selecting presets which aren't provided by loaded fonts
+
+    The policy applies to [`Synth::program_change`]. The presets are
+    searched through the SoundFont stack in order of [`Synth::sfont_iter`].
+
+    Note that the search queries each loaded font for presets,
+    which are allocated by the library, so [`Synth::program_change`]
+    isn't real-time safe when the policy other than [`PresetFallback::None`] is set.
+     */
+    pub fn set_preset_fallback(&self, policy: PresetFallback) {
+        self.fallback.borrow_mut().policy = policy;
+    }
+
+    /// Get the policy of selecting presets which aren't provided by loaded fonts
+    pub fn get_preset_fallback(&self) -> PresetFallback {
+        self.fallback.borrow().policy
+    }
+
+    /**
+    Set the handler which is called on each fallback of preset
+
+    The handler is called from [`Synth::program_change`] and must not use synth.
+     */
+    pub fn set_preset_fallback_handler<F>(&self, handler: F)
+    where
+        F: FnMut(&FallbackEvent) + Send + 'static,
+    {
+        self.fallback.borrow_mut().handler = Some(Box::new(handler));
+    }
+
+    /// Remove the handler of preset fallbacks
+    pub fn reset_preset_fallback_handler(&self) {
+        self.fallback.borrow_mut().handler = None;
+    }
+
+    /// Track the bank selected on channel
+    pub(super) fn track_bank(&self, chan: Chan, bank: Bank) {
+        self.fallback.borrow_mut().set_bank(chan, bank.get());
+    }
+
+    /// Track the bank selected on channel using control change
+    pub(super) fn track_bank_ctrl(&self, chan: Chan, ctrl: Ctrl, val: Val) {
+        let mut state = self.fallback.borrow_mut();
+        if Some(chan) == state.drums {
+            // the bank selection is ignored on drum channel
+            return;
+        }
+        match ctrl {
+            Ctrl::BANK_SELECT_MSB => {
+                if let Some(msb) = state.msbs.get_mut(chan.get() as usize) {
+                    *msb = val.get();
+                }
+                state.set_bank(chan, val.get());
+            }
+            Ctrl::BANK_SELECT_LSB => {
+                let msb = state.msbs.get(chan.get() as usize).copied().unwrap_or(0);
+                state.set_bank(chan, msb << 7 | val.get());
+            }
+            _ => (),
+        }
+    }
+
+    /// Forget the tracked banks
+    pub(super) fn reset_banks(&self) {
+        let mut state = self.fallback.borrow_mut();
+        let state = &mut *state;
+        for bank in state.banks.iter_mut().chain(&mut state.msbs) {
+            *bank = 0;
+        }
+    }
+
+    /**
+    Select the fallback preset when the requested one isn't provided by fonts
+
+    Returns `None` when the requested preset exists or fallback is disabled.
+     */
+    pub(super) fn select_fallback(&self, chan: Chan, prog: Prog) -> Option<FallbackEvent> {
+        let (policy, bank) = {
+            let state = self.fallback.borrow();
+            (state.policy, state.bank(chan))
+        };
+        if policy == PresetFallback::None || self.font_of_preset(bank, prog).is_some() {
+            return None;
+        }
+
+        let same_program = [(Bank::saturating(0), prog)];
+        let drums = [(Bank::DRUMS, Prog::STANDARD_DRUMS)];
+
+        let candidates = if bank == Bank::DRUMS {
+            &drums[..]
+        } else {
+            &same_program[..]
+        };
+        let mut selected = candidates.iter().find_map(|(bank, prog)| {
+            self.font_of_preset(*bank, *prog)
+                .map(|font_id| (font_id, *bank, *prog))
+        });
+
+        if selected.is_none() && policy == PresetFallback::GmFamily && bank != Bank::DRUMS {
+            selected = [bank, Bank::saturating(0)]
+                .iter()
+                .flat_map(|bank| family_members(prog).map(move |prog| (*bank, prog)))
+                .find_map(|(bank, prog)| {
+                    self.font_of_preset(bank, prog)
+                        .map(|font_id| (font_id, bank, prog))
+                });
+        }
+
+        Some(FallbackEvent {
+            chan,
+            requested: (bank, prog),
+            selected,
+        })
+    }
+
+    /// Report the fallback to handler
+    pub(super) fn report_fallback(&self, event: &FallbackEvent) {
+        // the state isn't borrowed while the handler is running
+        let handler = self.fallback.borrow_mut().handler.take();
+        if let Some(mut handler) = handler {
+            handler(event);
+            self.fallback.borrow_mut().handler.get_or_insert(handler);
+        }
+    }
+
+    /// Find the font which provides preset like synth does
+    fn font_of_preset(&self, bank: Bank, prog: Prog) -> Option<FontId> {
+        self.sfont_iter().find_map(|font| {
+            let offset = self.get_bank_offset(font.get_id()).unwrap_or(0);
+            let bank = bank.get().checked_sub(offset)?;
+            font.get_preset(Bank::saturating(bank), prog.get())
+                .map(|_| font.get_id())
+        })
+    }
+}
+
+/// Get the other programs of the GM family ordered by distance
+fn family_members(prog: Prog) -> impl Iterator<Item = Prog> {
+    let first = prog.gm_family() as u32 * 8;
+    let prog = prog.get();
+    (1..8)
+        .flat_map(move |distance| [prog.checked_sub(distance), Some(prog + distance)])
+        .flatten()
+        .filter(move |member| (first..first + 8).contains(member))
+        .map(Prog::saturating)
+}
+
+#[cfg(test)]
+mod test {
+    use super::family_members;
+    use crate::{Bank, Chan, Ctrl, FallbackEvent, PresetFallback, Prog, Settings, Synth};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn family_order() {
+        let members = family_members(Prog::saturating(2))
+            .map(|prog| prog.get())
+            .collect::<Vec<_>>();
+        assert_eq!(members, [1, 3, 0, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn preset_fallback() {
+        let synth = Synth::new(Settings::new().unwrap()).unwrap();
+        let id = synth.sfload("sf_/Boomwhacker.sf2", true).unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        synth.set_preset_fallback_handler({
+            let events = events.clone();
+            move |event: &FallbackEvent| events.lock().unwrap().push(*event)
+        });
+
+        // the channel stays silent by default
+        synth.bank_select(0, 5).unwrap();
+        synth.program_change(0, 0).unwrap();
+        assert!(events.lock().unwrap().is_empty());
+
+        synth.set_preset_fallback(PresetFallback::SameProgram);
+        assert_eq!(synth.get_preset_fallback(), PresetFallback::SameProgram);
+
+        synth.program_change(0, 0).unwrap();
+        assert_eq!(
+            synth.get_program_checked(0).unwrap(),
+            (id, Bank::saturating(0), Prog::saturating(0))
+        );
+        assert_eq!(
+            events.lock().unwrap().pop(),
+            Some(FallbackEvent {
+                chan: Chan::new(0).unwrap(),
+                requested: (Bank::saturating(5), Prog::saturating(0)),
+                selected: Some((id, Bank::saturating(0), Prog::saturating(0))),
+            })
+        );
+
+        // the font has only program 0 in bank 0, the requested bank is kept
+        synth.program_change(0, 3).unwrap();
+        let event = events.lock().unwrap().pop().unwrap();
+        assert_eq!(event.requested.0, Bank::saturating(5));
+        assert_eq!(event.selected, None);
+
+        synth.set_preset_fallback(PresetFallback::GmFamily);
+        synth.program_change(0, 3).unwrap();
+        assert_eq!(
+            events.lock().unwrap().pop().unwrap().selected,
+            Some((id, Bank::saturating(0), Prog::saturating(0)))
+        );
+
+        // the bank of selected preset is tracked
+        synth.program_select(0, id, 0, 0).unwrap();
+        synth.program_change(0, 0).unwrap();
+        assert!(events.lock().unwrap().is_empty());
+
+        // the existing presets are selected as usual
+        synth.bank_select(0, 5).unwrap();
+        synth.bank_select(0, 0).unwrap();
+        synth.program_change(0, 0).unwrap();
+        assert!(events.lock().unwrap().is_empty());
+
+        // the LSB is combined with the last MSB like the library does
+        synth.cc(0, Ctrl::BANK_SELECT_MSB, 1).unwrap();
+        synth.bank_select(0, 300).unwrap();
+        synth.cc(0, Ctrl::BANK_SELECT_LSB, 2).unwrap();
+        synth.program_change(0, 0).unwrap();
+        assert_eq!(
+            events.lock().unwrap().pop().unwrap().requested.0,
+            Bank::saturating(1 << 7 | 2)
+        );
+    }
+}
@@ -1,5 +1,11 @@
-use crate::{ffi, option_from_ptr, Chan, Error, FontId, FontRef, PresetRef, Result, Status, Synth};
-use std::{ffi::CString, marker::PhantomData, path::Path, ptr::NonNull};
+use crate::{
+    ffi, loader::take_fileapi_error, option_from_ptr, Bank, BankStyle, Chan, Error, FontId,
+    FontRef, IntoMidi, IsFont, PresetRef, Prog, Result, Status, Synth,
+};
+use std::{
+    ffi::CString,
+    path::{Path, PathBuf},
+};
 
 /**
 SoundFont management
@@ -13,13 +19,19 @@ impl Synth {
     is found.
      */
     pub fn sfload<P: AsRef<Path>>(&self, filename: P, reset_presets: bool) -> Result<FontId> {
-        let filename = filename.as_ref().to_str().ok_or(Error::Path)?;
+        let path = filename.as_ref();
+        let filename = path.to_str().ok_or(Error::Path)?;
         let filename = CString::new(filename).map_err(|_| Error::Path)?;
 
+        take_fileapi_error();
         self.neg_err(unsafe {
             ffi::fluid_synth_sfload(self.handle.as_ptr(), filename.as_ptr(), reset_presets as _)
         })
         .map(|id| id as _)
+        .map_err(|error| match take_fileapi_error() {
+            Some(io_error) => Error::file(path, io_error),
+            None => Error::font_load(path, error),
+        })
     }
 
     /**
@@ -27,17 +39,36 @@ impl Synth {
     index on the stack.
      */
     pub fn sfreload(&self, id: FontId) -> Result<FontId> {
+        let path = self
+            .get_sfont_by_id(id)
+            .and_then(|font| font.get_name().map(PathBuf::from));
+
+        take_fileapi_error();
         self.neg_err(unsafe { ffi::fluid_synth_sfreload(self.handle.as_ptr(), id as _) })
             .map(|id| id as _)
+            .map_err(|error| match (path, take_fileapi_error()) {
+                (Some(path), Some(io_error)) => Error::file(&path, io_error),
+                (Some(path), None) => Error::font_load(&path, error),
+                (None, _) => error,
+            })
     }
 
     /**
     Removes a SoundFont from the stack and deallocates it.
+
+    The data of font loaded from memory is freed too.
      */
     pub fn sfunload(&self, id: FontId, reset_presets: bool) -> Status {
+        let path = self
+            .get_sfont_by_id(id)
+            .and_then(|font| font.get_name().map(PathBuf::from));
         self.zero_ok(unsafe {
             ffi::fluid_synth_sfunload(self.handle.as_ptr(), id, reset_presets as _)
-        })
+        })?;
+        if let Some(path) = path {
+            self.release_data(&path);
+        }
+        Ok(())
     }
 
     /**
@@ -55,14 +86,62 @@ impl Synth {
      */
     pub fn get_sfont(&self, num: u32) -> Option<FontRef<'_>> {
         option_from_ptr(unsafe { ffi::fluid_synth_get_sfont(self.handle.as_ptr(), num) })
-            .map(|ptr| unsafe { FontRef::from_ptr(ptr) })
+            .map(|ptr| unsafe { FontRef::from_ptr(self, ptr) })
     }
 
     /**
     Get an iterator over loaded SoundFonts.
      */
     pub fn sfont_iter(&self) -> FontIter<'_> {
-        unsafe { FontIter::from_ptr(self.handle) }
+        FontIter {
+            synth: self,
+            font_no: 0,
+        }
+    }
+
+    /**
+    Find presets of loaded SoundFonts by name.
+
+    The names are matched fuzzily, ignoring case and punctuation.
+    Presets are also matched by General MIDI names of its programs
+    (or GS drum kit names in the percussion bank), so searching
+    for "Acoustic Grand" finds a piano even if it is named differently.
+
+    The matches are ordered from the best one. Equal matches keep
+    the order of the SoundFont stack.
+     */
+    pub fn find_preset<S: AsRef<str>>(&self, name: S) -> Vec<PresetMatch> {
+        let query = name_tokens(name.as_ref());
+        let mut matches = Vec::new();
+
+        for font in self.sfont_iter() {
+            let font_id = font.get_id();
+            for preset in font.preset_iter() {
+                let bank = Bank::saturating(preset.bank);
+                let prog = Prog::saturating(preset.num);
+                let standard = if bank == Bank::DRUMS {
+                    BankStyle::Gs.drum_kit_name(prog)
+                } else {
+                    Some(prog.gm_name())
+                };
+                let score =
+                    name_score(&query, &preset.name).max(standard.map_or(0.0, |standard| {
+                        PresetMatch::STANDARD_NAME_WEIGHT * name_score(&query, standard)
+                    }));
+                if score >= PresetMatch::MIN_SCORE {
+                    matches.push(PresetMatch {
+                        font_id,
+                        bank,
+                        prog,
+                        name: preset.name,
+                        score,
+                    });
+                }
+            }
+        }
+
+        matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+        matches
     }
 
     /**
@@ -70,17 +149,21 @@ impl Synth {
      */
     pub fn get_sfont_by_id(&self, id: FontId) -> Option<FontRef<'_>> {
         option_from_ptr(unsafe { ffi::fluid_synth_get_sfont_by_id(self.handle.as_ptr(), id) })
-            .map(|ptr| unsafe { FontRef::from_ptr(ptr) })
+            .map(|ptr| unsafe { FontRef::from_ptr(self, ptr) })
     }
 
     /**
     Remove a SoundFont that was previously added using
     fluid_synth_add_sfont(). The synthesizer does not delete the
     SoundFont; this is responsability of the caller.
+
+    Nothing happens when the font is already unloaded.
      */
     pub fn remove_sfont(&self, sfont: FontRef<'_>) {
-        unsafe {
-            ffi::fluid_synth_remove_sfont(self.handle.as_ptr(), sfont.as_ptr());
+        if let Some(sfont) = sfont.as_ptr() {
+            unsafe {
+                ffi::fluid_synth_remove_sfont(self.handle.as_ptr(), sfont.as_ptr());
+            }
         }
     }
 
@@ -97,11 +180,12 @@ impl Synth {
     /**
     Get the preset of a channel
      */
-    pub fn get_channel_preset(&self, chan: Chan) -> Option<PresetRef<'_>> {
+    pub fn get_channel_preset<C: IntoMidi<Chan>>(&self, chan: C) -> Option<PresetRef<'_>> {
+        let chan = chan.into_midi().ok()?;
         option_from_ptr(unsafe {
-            ffi::fluid_synth_get_channel_preset(self.handle.as_ptr(), chan as _)
+            ffi::fluid_synth_get_channel_preset(self.handle.as_ptr(), chan.get() as _)
         })
-        .map(|ptr| unsafe { PresetRef::from_ptr(ptr) })
+        .map(|ptr| unsafe { PresetRef::from_ptr(self, ptr) })
     }
 
     /**
@@ -127,7 +211,8 @@ impl Synth {
 
 #[cfg(test)]
 mod test {
-    use crate::{IsFont, IsPreset, Settings, Synth};
+    use super::{name_score, name_tokens};
+    use crate::{Bank, IsFont, IsPreset, Prog, Settings, Synth};
 
     #[test]
     fn font_and_preset() {
@@ -150,38 +235,204 @@ mod test {
         assert_eq!(preset.get_banknum().unwrap(), 0);
         assert_eq!(preset.get_num().unwrap(), 0);
     }
+
+    #[test]
+    fn unloaded_refs() {
+        let synth = Synth::new(Settings::new().unwrap()).unwrap();
+        let id = synth.sfload("sf_/Boomwhacker.sf2", true).unwrap();
+
+        let font = synth.get_sfont_by_id(id).unwrap();
+        let preset = font.get_preset(0, 0).unwrap();
+        let presets = font.preset_iter();
+
+        // the iterators don't share the cursor of font
+        for _ in font.preset_iter() {
+            assert_eq!(font.preset_iter().count(), 1);
+        }
+
+        synth.program_select(0, id, 0, 0).unwrap();
+        let channel_preset = synth.get_channel_preset(0).unwrap();
+        assert_eq!(channel_preset.font_id(), id);
+        assert_eq!(channel_preset.get_name().unwrap(), "Boomwhacker");
+
+        // the references stay valid when font is reloaded
+        let id = synth.sfreload(id).unwrap();
+        assert!(font.is_loaded());
+        assert_eq!(preset.get_name().unwrap(), "Boomwhacker");
+
+        synth.sfunload(id, true).unwrap();
+
+        assert!(!font.is_loaded());
+        assert_eq!(font.get_id(), id);
+        assert_eq!(font.get_name(), None);
+        assert!(font.get_preset(0, 0).is_none());
+        assert_eq!(font.preset_iter().count(), 0);
+        // the presets are collected on creation of iterator
+        assert_eq!(presets.count(), 1);
+
+        assert_eq!(preset.get_name(), None);
+        assert_eq!(preset.get_banknum(), None);
+        assert_eq!(preset.get_num(), None);
+        assert_eq!(channel_preset.get_name(), None);
+
+        // the removing of unloaded font does nothing
+        synth.remove_sfont(font);
+
+        // the IDs aren't reused, so the old references don't refer to new font
+        let new_id = synth.sfload("sf_/Boomwhacker.sf2", true).unwrap();
+        assert_ne!(new_id, id);
+        assert!(!font.is_loaded());
+        assert_eq!(preset.get_name(), None);
+    }
+
+    #[test]
+    fn removed_font() {
+        let synth = Synth::new(Settings::new().unwrap()).unwrap();
+        let id = synth.sfload("sf_/Boomwhacker.sf2", true).unwrap();
+
+        let font = synth.get_sfont(0).unwrap();
+        let preset = font.get_preset(0, 0).unwrap();
+        synth.remove_sfont(synth.get_sfont_by_id(id).unwrap());
+
+        assert_eq!(synth.sfcount(), 0);
+        assert!(!font.is_loaded());
+        assert_eq!(preset.get_name(), None);
+        assert!(synth.sfont_iter().next().is_none());
+    }
+
+    #[test]
+    fn fuzzy_names() {
+        let query = name_tokens("Acoustic Grand");
+
+        assert_eq!(name_score(&query, "acoustic grand"), 1.0);
+        assert_eq!(name_score(&query, "Acoustic Grand Piano"), 0.9);
+        assert!(name_score(&query, "Acoustc Grnd Pno") > 0.6);
+        assert!(name_score(&query, "Violin") < 0.6);
+        assert_eq!(name_score(&[], "Violin"), 0.0);
+    }
+
+    #[test]
+    fn find_preset() {
+        let synth = Synth::new(Settings::new().unwrap()).unwrap();
+        let font_id = synth.sfload("sf_/Boomwhacker.sf2", true).unwrap();
+
+        let font = synth.get_sfont_by_id(font_id).unwrap();
+        let presets = font.preset_iter().collect::<Vec<_>>();
+
+        assert_eq!(presets.len(), 1);
+        assert_eq!(presets[0].name, "Boomwhacker");
+
+        let found = synth.find_preset("boomwaker");
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].font_id, font_id);
+        assert_eq!(found[0].bank, Bank::MIN);
+        assert_eq!(found[0].prog, Prog::MIN);
+
+        // matched by General MIDI name of program 0
+        assert_eq!(synth.find_preset("Acoustic Grand").len(), 1);
+        assert!(synth.find_preset("Timpani").is_empty());
+    }
 }
 
 /**
 The iterator over loaded SoundFonts.
  */
 pub struct FontIter<'a> {
-    handle: NonNull<ffi::fluid_synth_t>,
-    phantom: PhantomData<&'a ()>,
+    synth: &'a Synth,
     font_no: u32,
 }
 
-impl<'a> FontIter<'a> {
-    unsafe fn from_ptr(handle: NonNull<ffi::fluid_synth_t>) -> Self {
-        Self {
-            handle,
-            phantom: PhantomData,
-            font_no: 0,
-        }
-    }
-}
-
 impl<'a> Iterator for FontIter<'a> {
     type Item = FontRef<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let font = option_from_ptr(unsafe {
-            ffi::fluid_synth_get_sfont(self.handle.as_ptr(), self.font_no)
-        })
-        .map(|ptr| unsafe { FontRef::from_ptr(ptr) });
+        let font = self.synth.get_sfont(self.font_no);
         if font.is_some() {
             self.font_no += 1;
         }
         font
     }
 }
+
+/**
+The preset found by name
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresetMatch {
+    pub font_id: FontId,
+    pub bank: Bank,
+    pub prog: Prog,
+    /// The name of preset in SoundFont
+    pub name: String,
+    /// The similarity of name from `0.0` to `1.0`
+    pub score: f32,
+}
+
+impl PresetMatch {
+    /// The minimum score of reported matches
+    pub const MIN_SCORE: f32 = 0.6;
+
+    /// The weight of matching by standard names instead of actual preset name
+    const STANDARD_NAME_WEIGHT: f32 = 0.9;
+}
+
+fn name_tokens(name: &str) -> Vec<String> {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+        .collect()
+}
+
+fn name_score(query: &[String], name: &str) -> f32 {
+    if query.is_empty() {
+        return 0.0;
+    }
+
+    let name = name_tokens(name);
+    if query == name.as_slice() {
+        return 1.0;
+    }
+
+    let query_str = query.join(" ");
+    let name_str = name.join(" ");
+    if name_str.contains(&query_str) {
+        return 0.9;
+    }
+
+    // average similarity of each query word to the best matching name word
+    let total = query
+        .iter()
+        .map(|word| {
+            name.iter()
+                .map(|name_word| word_score(word, name_word))
+                .fold(0.0, f32::max)
+        })
+        .sum::<f32>();
+
+    0.8 * total / query.len() as f32
+}
+
+fn word_score(word: &str, name_word: &str) -> f32 {
+    if name_word.starts_with(word) {
+        return 1.0;
+    }
+
+    let word = word.chars().collect::<Vec<_>>();
+    let name_word = name_word.chars().collect::<Vec<_>>();
+
+    // Levenshtein distance
+    let mut row = (0..=name_word.len()).collect::<Vec<_>>();
+    for (i, a) in word.iter().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for (j, b) in name_word.iter().enumerate() {
+            let cost = if a == b { diag } else { diag + 1 };
+            diag = row[j + 1];
+            row[j + 1] = cost.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+
+    let len = word.len().max(name_word.len());
+    1.0 - row[name_word.len()] as f32 / len as f32
+}
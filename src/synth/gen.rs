@@ -1,4 +1,4 @@
-use crate::{ffi, Chan, Status, Synth};
+use crate::{ffi, Chan, IntoMidi, Result, Status, Synth};
 
 /**
 Generator (effect) numbers
@@ -6,6 +6,8 @@ Generator (effect) numbers
 See also _Soundfont 2.01 specifications section 8.1.3_
  */
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[repr(u32)]
 pub enum GenParam {
     /** Sample start address offset (0-32767) */
@@ -13,6 +15,7 @@ pub enum GenParam {
     /**< Sample end address offset (-32767-0) */
     EndAddrOfs = ffi::fluid_gen_type_GEN_ENDADDROFS as _,
     /**< Sample loop start address offset (-32767-32767) */
+    #[cfg_attr(feature = "serde", serde(rename = "start_loop_addr_ofs"))]
     StartLoopAddOfs = ffi::fluid_gen_type_GEN_STARTLOOPADDROFS as _,
     /**< Sample loop end address offset (-32767-32767) */
     EndLoopAddrOfs = ffi::fluid_gen_type_GEN_ENDLOOPADDROFS as _,
@@ -37,8 +40,10 @@ pub enum GenParam {
     /** Modulation LFO to volume */
     ModLfoToVol = ffi::fluid_gen_type_GEN_MODLFOTOVOL as _,
     /** Chorus send amount */
+    #[cfg_attr(feature = "serde", serde(rename = "chorus_send"))]
     ChorussEnd = ffi::fluid_gen_type_GEN_CHORUSSEND as _,
     /** Reverb send amount */
+    #[cfg_attr(feature = "serde", serde(rename = "reverb_send"))]
     ReverbsEnd = ffi::fluid_gen_type_GEN_REVERBSEND as _,
     /** Stereo panning */
     Pan = ffi::fluid_gen_type_GEN_PAN as _,
@@ -47,6 +52,7 @@ pub enum GenParam {
     /** Modulation LFO frequency */
     ModLfoFreq = ffi::fluid_gen_type_GEN_MODLFOFREQ as _,
     /** Vibrato LFO delay */
+    #[cfg_attr(feature = "serde", serde(rename = "vib_lfo_delay"))]
     Viblfodelay = ffi::fluid_gen_type_GEN_VIBLFODELAY as _,
     /** Vibrato LFO frequency */
     VibLfoFreq = ffi::fluid_gen_type_GEN_VIBLFOFREQ as _,
@@ -85,12 +91,16 @@ pub enum GenParam {
     /** Instrument ID (shouldn't be set by user) */
     Instrument = ffi::fluid_gen_type_GEN_INSTRUMENT as _,
     /** MIDI note range */
+    #[cfg_attr(feature = "serde", serde(rename = "key_range"))]
     Keyrange = ffi::fluid_gen_type_GEN_KEYRANGE as _,
     /** MIDI velocity range */
+    #[cfg_attr(feature = "serde", serde(rename = "vel_range"))]
     Velrange = ffi::fluid_gen_type_GEN_VELRANGE as _,
     /** Sample start loop address coarse offset (X 32768) */
+    #[cfg_attr(feature = "serde", serde(rename = "start_loop_addr_coarse_ofs"))]
     Startloopaddrcoarseofs = ffi::fluid_gen_type_GEN_STARTLOOPADDRCOARSEOFS as _,
     /** Fixed MIDI note number */
+    #[cfg_attr(feature = "serde", serde(rename = "key_num"))]
     Keynum = ffi::fluid_gen_type_GEN_KEYNUM as _,
     /** Fixed MIDI velocity value */
     Velocity = ffi::fluid_gen_type_GEN_VELOCITY as _,
@@ -123,6 +133,65 @@ pub enum GenParam {
     Pitch = ffi::fluid_gen_type_GEN_PITCH as _,
 }
 
+impl GenParam {
+    /// All generators
+    pub const ALL: [Self; 53] = [
+        Self::StartAddrOfs,
+        Self::EndAddrOfs,
+        Self::StartLoopAddOfs,
+        Self::EndLoopAddrOfs,
+        Self::StartAddrCoarseOfs,
+        Self::ModLfoToPitch,
+        Self::VibLfoToPitch,
+        Self::ModEnvToPitch,
+        Self::FilterFc,
+        Self::FilterQ,
+        Self::ModLfoToFilterFc,
+        Self::ModEnvToFilterFc,
+        Self::EndAddrCoarseOfs,
+        Self::ModLfoToVol,
+        Self::ChorussEnd,
+        Self::ReverbsEnd,
+        Self::Pan,
+        Self::ModLfoDelay,
+        Self::ModLfoFreq,
+        Self::Viblfodelay,
+        Self::VibLfoFreq,
+        Self::ModEnvDelay,
+        Self::ModEnvAttack,
+        Self::ModEnvHold,
+        Self::ModEnvDecay,
+        Self::ModEnvSustain,
+        Self::ModEnvRelease,
+        Self::KeyToModEnvHold,
+        Self::KeyToModEnvDecay,
+        Self::VolEnvDelay,
+        Self::VolEnvAttack,
+        Self::VolEnvHold,
+        Self::VolEnvDecay,
+        Self::VolEnvSustain,
+        Self::VolEnvRelease,
+        Self::KeyToVolEnvHold,
+        Self::KeyToVolEnvDecay,
+        Self::Instrument,
+        Self::Keyrange,
+        Self::Velrange,
+        Self::Startloopaddrcoarseofs,
+        Self::Keynum,
+        Self::Velocity,
+        Self::Attenuation,
+        Self::EndLoopAddrCoarseOfs,
+        Self::CoarseTune,
+        Self::FineTune,
+        Self::SampleId,
+        Self::SampleMode,
+        Self::ScaleTune,
+        Self::ExclusiveClass,
+        Self::OverrideRootKey,
+        Self::Pitch,
+    ];
+}
+
 /**
 Generator interface
  */
@@ -136,9 +205,10 @@ impl Synth {
     parameter numbers and ranges are described in the SoundFont 2.01
     specification, paragraph 8.1.3, page 48.
      */
-    pub fn set_gen(&self, chan: Chan, param: GenParam, value: f32) -> Status {
+    pub fn set_gen<C: IntoMidi<Chan>>(&self, chan: C, param: GenParam, value: f32) -> Status {
+        let chan = self.check_chan(chan.into_midi()?)?;
         self.zero_ok(unsafe {
-            ffi::fluid_synth_set_gen(self.handle.as_ptr(), chan as _, param as _, value)
+            ffi::fluid_synth_set_gen(self.handle.as_ptr(), chan.get() as _, param as _, value)
         })
     }
 
@@ -148,7 +218,8 @@ impl Synth {
 
     Returns the value of the generator.
      */
-    pub fn get_gen(&self, chan: Chan, param: GenParam) -> f32 {
-        unsafe { ffi::fluid_synth_get_gen(self.handle.as_ptr(), chan as _, param as _) }
+    pub fn get_gen<C: IntoMidi<Chan>>(&self, chan: C, param: GenParam) -> Result<f32> {
+        let chan = self.check_chan(chan.into_midi()?)?;
+        Ok(unsafe { ffi::fluid_synth_get_gen(self.handle.as_ptr(), chan.get() as _, param as _) })
     }
 }
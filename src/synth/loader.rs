@@ -1,4 +1,20 @@
-use crate::{ffi, Loader, Synth};
+use crate::{
+    dls::Dls,
+    ffi,
+    loader::{read_file, NotServed},
+    rmid::{Rmid, RmidBank},
+    sf2::SoundFont,
+    sfz::Sfz,
+    FileApi, FontId, IsFont, Loader, MemoryFile, MemoryFileApi, Result, Synth,
+};
+use std::{
+    io::{Cursor, ErrorKind, Result as IoResult, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
 
 impl Synth {
     /**
@@ -11,4 +27,248 @@ impl Synth {
             ffi::fluid_synth_add_sfloader(self.handle.as_ptr(), loader.into_ptr().as_ptr());
         }
     }
+
+    /**
+    Load SFZ instrument as SoundFont
+
+    The SFZ file, included files and WAV samples are read using file API.
+    The instrument is converted to SoundFont with single preset `0:0`
+    (see [`Sfz::to_soundfont`]) which name is the path of SFZ file.
+
+    The converted font is kept in memory until it is unloaded.
+     */
+    pub fn sfload_sfz<F: FileApi, P: AsRef<Path>>(
+        &self,
+        fileapi: &mut F,
+        filename: P,
+        reset_presets: bool,
+    ) -> Result<FontId> {
+        let path = filename.as_ref();
+        let (font, data) = Sfz::load(fileapi, path)?.to_soundfont(fileapi)?;
+
+        let mut buffer = Vec::new();
+        font.write(&mut buffer, &data)?;
+        self.sfload_data(path, buffer, reset_presets)
+    }
+
+    /**
+    Load DLS collection as SoundFont
+
+    The collection is converted to SoundFont with a preset for each
+    instrument (see [`Dls::to_soundfont`]) which name is the path of DLS file.
+
+    The converted font is kept in memory until it is unloaded.
+     */
+    pub fn sfload_dls<F: FileApi, P: AsRef<Path>>(
+        &self,
+        fileapi: &mut F,
+        filename: P,
+        reset_presets: bool,
+    ) -> Result<FontId> {
+        let path = filename.as_ref();
+        let (font, data) = Dls::load(fileapi, path)?.to_soundfont()?;
+
+        let mut buffer = Vec::new();
+        font.write(&mut buffer, &data)?;
+        self.sfload_data(path, buffer, reset_presets)
+    }
+
+    /**
+    Load SoundFont decoding compressed samples in Rust
+
+    The SF3 samples are decoded eagerly (see [`SoundFont::decompress`]),
+    so the font is loaded as SF2 and C vorbis decoders aren't required.
+    The decoding of compressed samples requires `with-lewton` feature.
+    The uncompressed fonts are loaded too.
+
+    The decoded font is kept in memory until it is unloaded.
+     */
+    pub fn sfload_sf3<F: FileApi, P: AsRef<Path>>(
+        &self,
+        fileapi: &mut F,
+        filename: P,
+        reset_presets: bool,
+    ) -> Result<FontId> {
+        let path = filename.as_ref();
+        let mut reader = Cursor::new(read_file(fileapi, path)?);
+        let (font, data) = SoundFont::read(&mut reader)?.decompress(&mut reader)?;
+
+        let mut buffer = Vec::new();
+        font.write(&mut buffer, &data)?;
+        self.sfload_data(path, buffer, reset_presets)
+    }
+
+    /**
+    Load the bank embedded into RMID file
+
+    The bank is loaded on top of stack with the bank offset of file
+    (`0` when unspecified). Returns `None` when file has no bank.
+    The bank is unloaded when the result is dropped,
+    so keep it until playback of file ends.
+     */
+    pub fn load_rmid_bank(&self, rmid: &Rmid) -> Result<Option<RmidBank<'_>>> {
+        /// The counter which makes the names of banks unique
+        static NEXT_BANK: AtomicUsize = AtomicUsize::new(0);
+
+        let data = match rmid.bank_data()? {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        let path = PathBuf::from(format!(
+            "rmid-bank-{}.sf2",
+            NEXT_BANK.fetch_add(1, Ordering::Relaxed)
+        ));
+        let id = self.sfload_data(&path, data, true)?;
+        let bank = RmidBank { synth: self, id };
+        self.set_bank_offset(id, rmid.bank_offset.unwrap_or(0) as u32)?;
+        Ok(Some(bank))
+    }
+
+    /**
+    Load SoundFont from memory
+
+    The name identifies the font like the file path (see [`MemoryFileApi`]).
+    The data can be `Vec<u8>`, `&'static [u8]` or any other owned bytes,
+    so the fonts embedded with `include_bytes!` aren't copied.
+
+    The data is kept until the font is unloaded, so it can be reloaded.
+    The data of font with same name is replaced.
+     */
+    pub fn sfload_bytes<P, D>(&self, name: P, data: D, reset_presets: bool) -> Result<FontId>
+    where
+        P: AsRef<Path>,
+        D: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        self.sfload_data(name.as_ref(), data, reset_presets)
+    }
+
+    /**
+    Load SoundFont from memory using the name
+
+    The data is served by the in-memory loader shared by all fonts of synth.
+     */
+    pub(crate) fn sfload_data<D>(&self, path: &Path, data: D, reset_presets: bool) -> Result<FontId>
+    where
+        D: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        let files = self.memory_files();
+        lock(&files).add(path, data);
+        self.sfload(path, reset_presets)
+            .inspect_err(|_| self.release_data(path))
+    }
+
+    /**
+    Reload font from data keeping its ID and position on the stack
+
+    The data is served in place of the file while reloading only.
+     */
+    #[cfg(feature = "watch")]
+    pub(crate) fn sfreload_data<D>(&self, id: FontId, data: D) -> Result<FontId>
+    where
+        D: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        let path = self
+            .get_sfont_by_id(id)
+            .and_then(|font| font.get_name())
+            .map(PathBuf::from)
+            .ok_or(crate::Error::Path)?;
+        self.serving(&path, data, || self.sfreload(id))
+    }
+
+    /**
+    Load SoundFont from memory serving the data while loading only
+
+    The loaded font doesn't keep the data, so it is reloaded from the file.
+     */
+    pub(crate) fn sfload_data_once<D>(
+        &self,
+        path: &Path,
+        data: D,
+        reset_presets: bool,
+    ) -> Result<FontId>
+    where
+        D: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        self.serving(path, data, || self.sfload(path, reset_presets))
+    }
+
+    /// Serve the data in place of the file while calling the function
+    fn serving<D, T>(&self, path: &Path, data: D, func: impl FnOnce() -> T) -> T
+    where
+        D: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        let files = self.memory_files();
+        let served = lock(&files).contains(path);
+        lock(&files).add(path, data);
+        let result = func();
+        if !served {
+            lock(&files).remove(path);
+        }
+        result
+    }
+
+    /// Get the files of in-memory loader adding it on first use
+    fn memory_files(&self) -> SharedFiles {
+        let mut files = self.memory.borrow_mut();
+        if let Some(files) = &*files {
+            return files.clone();
+        }
+        let shared = SharedFiles::default();
+        if let Ok(loader) = Loader::new_default() {
+            loader.set_file_api(SharedFileApi(shared.clone()));
+            self.add_sfloader(loader);
+            *files = Some(shared.clone());
+        }
+        shared
+    }
+
+    /// Free the data served from memory unless a loaded font still uses it
+    pub(crate) fn release_data(&self, path: &Path) {
+        let files = match &*self.memory.borrow() {
+            Some(files) => files.clone(),
+            None => return,
+        };
+        let used = self
+            .sfont_iter()
+            .any(|font| font.get_name().as_deref().map(Path::new) == Some(path));
+        if !used {
+            lock(&files).remove(path);
+        }
+    }
+}
+
+/// The files served by in-memory loader of synth
+pub(crate) type SharedFiles = Arc<Mutex<MemoryFileApi>>;
+
+fn lock(files: &SharedFiles) -> std::sync::MutexGuard<'_, MemoryFileApi> {
+    // the file API never panics holding the lock, so the poisoning is ignored
+    files.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+/// The file API which serves the shared in-memory files
+struct SharedFileApi(SharedFiles);
+
+impl FileApi for SharedFileApi {
+    type File = MemoryFile;
+
+    fn open(&mut self, filename: &Path) -> IoResult<Self::File> {
+        lock(&self.0)
+            .open(filename)
+            .map_err(|error| match error.kind() {
+                ErrorKind::NotFound => NotServed.into(),
+                _ => error,
+            })
+    }
+
+    fn read(file: &mut Self::File, buf: &mut [u8]) -> IoResult<()> {
+        MemoryFileApi::read(file, buf)
+    }
+
+    fn seek(file: &mut Self::File, pos: SeekFrom) -> IoResult<u64> {
+        MemoryFileApi::seek(file, pos)
+    }
+
+    fn tell(file: &mut Self::File) -> IoResult<u64> {
+        MemoryFileApi::tell(file)
+    }
 }
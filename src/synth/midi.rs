@@ -1,133 +1,280 @@
-use crate::{ffi, Bank, Chan, Ctrl, FontId, Key, PresetId, Prog, Result, Status, Synth, Val, Vel};
+use crate::{
+    ffi, Bank, Chan, Ctrl, FontId, IntoMidi, Key, PitchBend, PresetId, Prog, Result, Status, Synth,
+    Val, Vel,
+};
 use std::mem::MaybeUninit;
 
 /**
 MIDI channel messages
+
+All methods accept both checked values (like [`Key`]) and plain integers.
+Integers are checked before sending to synth.
  */
 impl Synth {
     /**
     Send a noteon message.
+
+    Fails with `Error::NoPreset` when no preset is selected on the channel.
      */
-    pub fn note_on(&self, chan: Chan, key: Key, vel: Vel) -> Status {
+    pub fn note_on<C, K, V>(&self, chan: C, key: K, vel: V) -> Status
+    where
+        C: IntoMidi<Chan>,
+        K: IntoMidi<Key>,
+        V: IntoMidi<Vel>,
+    {
+        let (key, vel) = (key.into_midi()?, vel.into_midi()?);
+        let chan = self.check_chan(chan.into_midi()?)?;
         self.zero_ok(unsafe {
-            ffi::fluid_synth_noteon(self.handle.as_ptr(), chan as _, key as _, vel as _)
+            ffi::fluid_synth_noteon(
+                self.handle.as_ptr(),
+                chan.get() as _,
+                key.get() as _,
+                vel.get() as _,
+            )
         })
+        .map_err(|error| self.preset_error(chan, error))
     }
 
     /**
     Send a noteoff message.
      */
-    pub fn note_off(&self, chan: Chan, key: Key) -> Status {
-        self.zero_ok(unsafe { ffi::fluid_synth_noteoff(self.handle.as_ptr(), chan as _, key as _) })
+    pub fn note_off<C, K>(&self, chan: C, key: K) -> Status
+    where
+        C: IntoMidi<Chan>,
+        K: IntoMidi<Key>,
+    {
+        let key = key.into_midi()?;
+        let chan = self.check_chan(chan.into_midi()?)?;
+        self.zero_ok(unsafe {
+            ffi::fluid_synth_noteoff(self.handle.as_ptr(), chan.get() as _, key.get() as _)
+        })
     }
 
     /**
     Send a control change message.
      */
-    pub fn cc(&self, chan: Chan, ctrl: Ctrl, val: Val) -> Status {
+    pub fn cc<C, N, V>(&self, chan: C, ctrl: N, val: V) -> Status
+    where
+        C: IntoMidi<Chan>,
+        N: IntoMidi<Ctrl>,
+        V: IntoMidi<Val>,
+    {
+        let (ctrl, val) = (ctrl.into_midi()?, val.into_midi()?);
+        let chan = self.check_chan(chan.into_midi()?)?;
+        self.track_bank_ctrl(chan, ctrl, val);
         self.zero_ok(unsafe {
-            ffi::fluid_synth_cc(self.handle.as_ptr(), chan as _, ctrl as _, val as _)
+            ffi::fluid_synth_cc(
+                self.handle.as_ptr(),
+                chan.get() as _,
+                ctrl.get() as _,
+                val.get() as _,
+            )
         })
     }
 
     /**
     Get a control value.
      */
-    pub fn get_cc(&self, chan: Chan, ctrl: Ctrl) -> Result<Val> {
+    pub fn get_cc<C, N>(&self, chan: C, ctrl: N) -> Result<u32>
+    where
+        C: IntoMidi<Chan>,
+        N: IntoMidi<Ctrl>,
+    {
+        self.get_cc_checked(chan, ctrl).map(Val::get)
+    }
+
+    /**
+    Get a control value as checked value.
+     */
+    pub fn get_cc_checked<C, N>(&self, chan: C, ctrl: N) -> Result<Val>
+    where
+        C: IntoMidi<Chan>,
+        N: IntoMidi<Ctrl>,
+    {
+        let ctrl = ctrl.into_midi()?;
+        let chan = self.check_chan(chan.into_midi()?)?;
         let mut val = MaybeUninit::uninit();
 
         self.zero_ok(unsafe {
-            ffi::fluid_synth_get_cc(self.handle.as_ptr(), chan as _, ctrl as _, val.as_mut_ptr())
+            ffi::fluid_synth_get_cc(
+                self.handle.as_ptr(),
+                chan.get() as _,
+                ctrl.get() as _,
+                val.as_mut_ptr(),
+            )
         })
-        .map(|_| unsafe { val.assume_init() as _ })
+        .map(|_| Val::saturating(unsafe { val.assume_init() as _ }))
     }
 
     /**
     Send a pitch bend message.
      */
-    pub fn pitch_bend(&self, chan: Chan, val: Val) -> Status {
+    pub fn pitch_bend<C, V>(&self, chan: C, val: V) -> Status
+    where
+        C: IntoMidi<Chan>,
+        V: IntoMidi<PitchBend>,
+    {
+        let val = val.into_midi()?;
+        let chan = self.check_chan(chan.into_midi()?)?;
         self.zero_ok(unsafe {
-            ffi::fluid_synth_pitch_bend(self.handle.as_ptr(), chan as _, val as _)
+            ffi::fluid_synth_pitch_bend(self.handle.as_ptr(), chan.get() as _, val.get() as _)
         })
     }
 
     /**
     Get the pitch bend value.
      */
-    pub fn get_pitch_bend(&self, chan: Chan) -> Result<Val> {
+    pub fn get_pitch_bend<C: IntoMidi<Chan>>(&self, chan: C) -> Result<u32> {
+        self.get_pitch_bend_checked(chan).map(PitchBend::get)
+    }
+
+    /**
+    Get the pitch bend value as checked value.
+     */
+    pub fn get_pitch_bend_checked<C: IntoMidi<Chan>>(&self, chan: C) -> Result<PitchBend> {
+        let chan = self.check_chan(chan.into_midi()?)?;
         let mut pitch_bend = MaybeUninit::uninit();
 
         self.zero_ok(unsafe {
             ffi::fluid_synth_get_pitch_bend(
                 self.handle.as_ptr(),
-                chan as _,
+                chan.get() as _,
                 pitch_bend.as_mut_ptr(),
             )
         })
-        .map(|_| unsafe { pitch_bend.assume_init() as _ })
+        .map(|_| PitchBend::saturating(unsafe { pitch_bend.assume_init() as _ }))
     }
 
     /**
     Set the pitch wheel sensitivity.
      */
-    pub fn pitch_wheel_sens(&self, chan: Chan, val: Val) -> Status {
+    pub fn pitch_wheel_sens<C, V>(&self, chan: C, val: V) -> Status
+    where
+        C: IntoMidi<Chan>,
+        V: IntoMidi<Val>,
+    {
+        let val = val.into_midi()?;
+        let chan = self.check_chan(chan.into_midi()?)?;
         self.zero_ok(unsafe {
-            ffi::fluid_synth_pitch_wheel_sens(self.handle.as_ptr(), chan as _, val as _)
+            ffi::fluid_synth_pitch_wheel_sens(self.handle.as_ptr(), chan.get() as _, val.get() as _)
         })
     }
 
     /**
     Get the pitch wheel sensitivity.
      */
-    pub fn get_pitch_wheel_sens(&self, chan: Chan) -> Result<Val> {
+    pub fn get_pitch_wheel_sens<C: IntoMidi<Chan>>(&self, chan: C) -> Result<u32> {
+        self.get_pitch_wheel_sens_checked(chan).map(Val::get)
+    }
+
+    /**
+    Get the pitch wheel sensitivity as checked value.
+     */
+    pub fn get_pitch_wheel_sens_checked<C: IntoMidi<Chan>>(&self, chan: C) -> Result<Val> {
+        let chan = self.check_chan(chan.into_midi()?)?;
         let mut val = MaybeUninit::uninit();
 
         self.zero_ok(unsafe {
-            ffi::fluid_synth_get_pitch_wheel_sens(self.handle.as_ptr(), chan as _, val.as_mut_ptr())
+            ffi::fluid_synth_get_pitch_wheel_sens(
+                self.handle.as_ptr(),
+                chan.get() as _,
+                val.as_mut_ptr(),
+            )
         })
-        .map(|_| unsafe { val.assume_init() as _ })
+        .map(|_| Val::saturating(unsafe { val.assume_init() as _ }))
     }
 
     /**
     Send a program change message.
+
+    The missing presets are replaced according to [`Synth::set_preset_fallback`].
      */
-    pub fn program_change(&self, chan: Chan, prog: Prog) -> Status {
-        self.zero_ok(unsafe {
-            ffi::fluid_synth_program_change(self.handle.as_ptr(), chan as _, prog as _)
-        })
+    pub fn program_change<C, P>(&self, chan: C, prog: P) -> Status
+    where
+        C: IntoMidi<Chan>,
+        P: IntoMidi<Prog>,
+    {
+        let prog = prog.into_midi()?;
+        let chan = self.check_chan(chan.into_midi()?)?;
+        let fallback = self.select_fallback(chan, prog);
+        let status = self.zero_ok(unsafe {
+            ffi::fluid_synth_program_change(self.handle.as_ptr(), chan.get() as _, prog.get() as _)
+        });
+
+        match fallback {
+            Some(event) => {
+                // the requested bank stays tracked for the next program changes
+                let status = match event.selected {
+                    Some((font_id, bank, prog)) => self.select_preset(chan, font_id, bank, prog),
+                    None => status,
+                };
+                self.report_fallback(&event);
+                status
+            }
+            None => status,
+        }
     }
 
     /**
     Set channel pressure
      */
-    pub fn channel_pressure(&self, chan: Chan, val: Val) -> Status {
+    pub fn channel_pressure<C, V>(&self, chan: C, val: V) -> Status
+    where
+        C: IntoMidi<Chan>,
+        V: IntoMidi<Val>,
+    {
+        let val = val.into_midi()?;
+        let chan = self.check_chan(chan.into_midi()?)?;
         self.zero_ok(unsafe {
-            ffi::fluid_synth_channel_pressure(self.handle.as_ptr(), chan as _, val as _)
+            ffi::fluid_synth_channel_pressure(self.handle.as_ptr(), chan.get() as _, val.get() as _)
         })
     }
 
     /**
     Set key pressure (aftertouch)
      */
-    pub fn key_pressure(&self, chan: Chan, key: Key, val: Val) -> Status {
+    pub fn key_pressure<C, K, V>(&self, chan: C, key: K, val: V) -> Status
+    where
+        C: IntoMidi<Chan>,
+        K: IntoMidi<Key>,
+        V: IntoMidi<Val>,
+    {
+        let (key, val) = (key.into_midi()?, val.into_midi()?);
+        let chan = self.check_chan(chan.into_midi()?)?;
         self.zero_ok(unsafe {
-            ffi::fluid_synth_key_pressure(self.handle.as_ptr(), chan as _, key as _, val as _)
+            ffi::fluid_synth_key_pressure(
+                self.handle.as_ptr(),
+                chan.get() as _,
+                key.get() as _,
+                val.get() as _,
+            )
         })
     }
 
     /**
     Select a bank.
      */
-    pub fn bank_select(&self, chan: Chan, bank: Bank) -> Status {
-        self.zero_ok(unsafe { ffi::fluid_synth_bank_select(self.handle.as_ptr(), chan as _, bank) })
+    pub fn bank_select<C, B>(&self, chan: C, bank: B) -> Status
+    where
+        C: IntoMidi<Chan>,
+        B: IntoMidi<Bank>,
+    {
+        let bank = bank.into_midi()?;
+        let chan = self.check_chan(chan.into_midi()?)?;
+        self.track_bank(chan, bank);
+        self.zero_ok(unsafe {
+            ffi::fluid_synth_bank_select(self.handle.as_ptr(), chan.get() as _, bank.get())
+        })
     }
 
     /**
     Select a sfont.
      */
-    pub fn sfont_select(&self, chan: Chan, sfont_id: FontId) -> Status {
+    pub fn sfont_select<C: IntoMidi<Chan>>(&self, chan: C, sfont_id: FontId) -> Status {
+        let chan = self.check_chan(chan.into_midi()?)?;
+        // the bank of channel isn't changed, so the tracked bank stays valid
         self.zero_ok(unsafe {
-            ffi::fluid_synth_sfont_select(self.handle.as_ptr(), chan as _, sfont_id)
+            ffi::fluid_synth_sfont_select(self.handle.as_ptr(), chan.get() as _, sfont_id)
         })
     }
 
@@ -137,20 +284,34 @@ impl Synth {
     allows any preset to be selected and circumvents preset masking
     due to previously loaded SoundFonts on the SoundFont stack.
      */
-    pub fn program_select(
+    pub fn program_select<C, B, P>(
         &self,
-        chan: Chan,
+        chan: C,
         sfont_id: FontId,
-        bank_num: Bank,
-        preset_num: PresetId,
-    ) -> Status {
+        bank_num: B,
+        preset_num: P,
+    ) -> Status
+    where
+        C: IntoMidi<Chan>,
+        B: IntoMidi<Bank>,
+        P: IntoMidi<Prog>,
+    {
+        let (bank_num, preset_num) = (bank_num.into_midi()?, preset_num.into_midi()?);
+        let chan = self.check_chan(chan.into_midi()?)?;
+        self.select_preset(chan, sfont_id, bank_num, preset_num)?;
+        self.track_bank(chan, bank_num);
+        Ok(())
+    }
+
+    /// Select a preset without tracking the bank
+    fn select_preset(&self, chan: Chan, sfont_id: FontId, bank: Bank, prog: Prog) -> Status {
         self.zero_ok(unsafe {
             ffi::fluid_synth_program_select(
                 self.handle.as_ptr(),
-                chan as _,
+                chan.get() as _,
                 sfont_id,
-                bank_num,
-                preset_num,
+                bank.get(),
+                prog.get(),
             )
         })
     }
@@ -158,7 +319,16 @@ impl Synth {
     /**
     Returns the program, bank, and SoundFont number of the preset on a given channel.
      */
-    pub fn get_program(&self, chan: Chan) -> Result<(FontId, Bank, PresetId)> {
+    pub fn get_program<C: IntoMidi<Chan>>(&self, chan: C) -> Result<(FontId, u32, PresetId)> {
+        self.get_program_checked(chan)
+            .map(|(sfont_id, bank, prog)| (sfont_id, bank.get(), prog.get()))
+    }
+
+    /**
+    Returns the program, bank, and SoundFont number of the preset as checked values.
+     */
+    pub fn get_program_checked<C: IntoMidi<Chan>>(&self, chan: C) -> Result<(FontId, Bank, Prog)> {
+        let chan = self.check_chan(chan.into_midi()?)?;
         let mut sfont_id = MaybeUninit::uninit();
         let mut bank_num = MaybeUninit::uninit();
         let mut preset_num = MaybeUninit::uninit();
@@ -166,7 +336,7 @@ impl Synth {
         self.zero_ok(unsafe {
             ffi::fluid_synth_get_program(
                 self.handle.as_ptr(),
-                chan as _,
+                chan.get() as _,
                 sfont_id.as_mut_ptr(),
                 bank_num.as_mut_ptr(),
                 preset_num.as_mut_ptr(),
@@ -175,8 +345,8 @@ impl Synth {
         .map(|_| unsafe {
             (
                 sfont_id.assume_init(),
-                bank_num.assume_init(),
-                preset_num.assume_init(),
+                Bank::saturating(bank_num.assume_init()),
+                Prog::saturating(preset_num.assume_init()),
             )
         })
     }
@@ -196,6 +366,7 @@ impl Synth {
     A reset turns all the notes off and resets the controller values.
      */
     pub fn system_reset(&self) -> Status {
+        self.reset_banks();
         self.zero_ok(unsafe { ffi::fluid_synth_system_reset(self.handle.as_ptr()) })
     }
 }
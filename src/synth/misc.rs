@@ -1,14 +1,16 @@
-use crate::{ffi, Error, Result, Status, Synth};
+use crate::{ffi, Chan, Error, FluidError, Result, Status, Synth};
 use std::ffi::CStr;
 
 impl Synth {
     /**
     Get a textual representation of the last error
+
+    The message is copied into the inline buffer, so this never allocates.
      */
-    pub(super) fn error(&self) -> String {
+    pub(super) fn error(&self) -> FluidError {
         let error = unsafe { ffi::fluid_synth_error(self.handle.as_ptr()) };
         let error = unsafe { CStr::from_ptr(error) };
-        error.to_str().unwrap().into()
+        FluidError::from_cstr(error)
     }
 
     pub(super) fn neg_err(&self, ret: i32) -> Result<i32> {
@@ -26,4 +28,30 @@ impl Synth {
             Err(Error::Fluid(self.error()))
         }
     }
+
+    /**
+    Check that channel number is less than the number of MIDI channels
+     */
+    pub(super) fn check_chan(&self, chan: Chan) -> Result<Chan> {
+        let count = self.count_midi_channels();
+        if chan.get() < count {
+            Ok(chan)
+        } else {
+            Err(Error::InvalidChannel {
+                chan: chan.get(),
+                max: count.saturating_sub(1),
+            })
+        }
+    }
+
+    /**
+    Report the failure on channel without selected preset as [`Error::NoPreset`]
+     */
+    pub(super) fn preset_error(&self, chan: Chan, error: Error) -> Error {
+        if self.get_channel_preset(chan).is_some() {
+            error
+        } else {
+            Error::NoPreset { chan: chan.get() }
+        }
+    }
 }
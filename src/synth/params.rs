@@ -1,7 +1,9 @@
 use crate::{ffi, Status, Synth};
 
 /* Flags to choose the interpolation method */
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[repr(u32)]
 pub enum InterpMethod {
     /**
@@ -16,6 +18,7 @@ pub enum InterpMethod {
     Fourth-order interpolation: Requires 50% of the whole DSP processing time, good quality
     (default)
      */
+    #[default]
     FourthOrder = ffi::fluid_interp_FLUID_INTERP_4THORDER as _,
     /**
     Seventh-order interpolation
@@ -23,12 +26,6 @@ pub enum InterpMethod {
     SeventhOrder = ffi::fluid_interp_FLUID_INTERP_7THORDER as _,
 }
 
-impl Default for InterpMethod {
-    fn default() -> Self {
-        Self::FourthOrder
-    }
-}
-
 /**
 Synthesis parameters
  */
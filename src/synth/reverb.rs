@@ -4,6 +4,7 @@ use crate::{ffi, Synth};
 Reverb parameters
  */
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ReverbParams {
     pub roomsize: f64,
     pub damp: f64,
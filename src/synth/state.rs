@@ -0,0 +1,310 @@
+use crate::{
+    Bank, ChorusParams, Ctrl, Error, FontId, GenParam, IsFont, PitchBend, Prog, ReverbParams,
+    Status, Synth, Val,
+};
+use std::convert::TryInto;
+
+/**
+The preset selected on a channel
+ */
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProgramState {
+    pub font_id: FontId,
+    /// The name of SoundFont used to find it when ids differ
+    pub font_name: Option<String>,
+    pub bank: Bank,
+    pub prog: Prog,
+}
+
+/**
+The state of MIDI channel
+ */
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelState {
+    /// The selected preset (if any)
+    pub program: Option<ProgramState>,
+    /// The values of all 128 controllers
+    pub cc: Vec<Val>,
+    pub pitch_bend: PitchBend,
+    pub pitch_wheel_sens: Val,
+    /// The generators which differ from zero
+    pub gens: Vec<(GenParam, f32)>,
+}
+
+/**
+The user-defined tuning
+ */
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TuningState {
+    pub bank: Bank,
+    pub prog: Prog,
+    pub name: String,
+    /// The pitches of all 128 keys in cents
+    pub pitch: Vec<f64>,
+}
+
+/**
+The snapshot of synth state
+
+See [`Synth::snapshot`] and [`Synth::restore`].
+ */
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SynthState {
+    pub channels: Vec<ChannelState>,
+    pub gain: f32,
+    pub polyphony: u32,
+    pub reverb: ReverbParams,
+    pub chorus: ChorusParams,
+    pub tunings: Vec<TuningState>,
+}
+
+impl ChannelState {
+    /**
+    The controllers which are not restored
+
+    Bank select is restored by program selection and data entry
+    would re-apply the last RPN/NRPN. Channel mode messages
+    have side effects like turning the notes off.
+     */
+    const SKIPPED_CCS: [Ctrl; 6] = [
+        Ctrl::BANK_SELECT_MSB,
+        Ctrl::BANK_SELECT_LSB,
+        Ctrl::DATA_ENTRY_MSB,
+        Ctrl::DATA_ENTRY_LSB,
+        Ctrl::DATA_INCREMENT,
+        Ctrl::DATA_DECREMENT,
+    ];
+
+    fn is_restored(ctrl: Ctrl) -> bool {
+        ctrl < Ctrl::ALL_SOUND_OFF && !Self::SKIPPED_CCS.contains(&ctrl)
+    }
+}
+
+/**
+State snapshot
+ */
+impl Synth {
+    /**
+    Capture the current state of synth
+
+    The state includes the program selection, controllers, pitch bend
+    and generators of every channel, the master parameters, effects
+    and the defined tunings.
+
+    The tunings selected on channels cannot be queried, so these
+    should be selected again after restoring.
+     */
+    pub fn snapshot(&self) -> SynthState {
+        let channels = (0..self.count_midi_channels())
+            .map(|chan| self.channel_snapshot(chan))
+            .collect();
+
+        let tunings = self
+            .tuning_iter()
+            .filter_map(|(bank, prog)| {
+                let (name, pitch) = self.tuning_dump(bank, prog).ok()?;
+                Some(TuningState {
+                    bank,
+                    prog,
+                    name,
+                    pitch: pitch.to_vec(),
+                })
+            })
+            .collect();
+
+        SynthState {
+            channels,
+            gain: self.get_gain(),
+            polyphony: self.get_polyphony(),
+            reverb: self.get_reverb(),
+            chorus: self.get_chorus(),
+            tunings,
+        }
+    }
+
+    fn channel_snapshot(&self, chan: u32) -> ChannelState {
+        let program = if self.get_channel_preset(chan).is_some() {
+            self.get_program_checked(chan)
+                .ok()
+                .map(|(font_id, bank, prog)| ProgramState {
+                    font_id,
+                    font_name: self
+                        .get_sfont_by_id(font_id)
+                        .and_then(|font| font.get_name()),
+                    bank,
+                    prog,
+                })
+        } else {
+            None
+        };
+
+        let cc = (0..=Ctrl::MAX.get())
+            .map(|ctrl| self.get_cc_checked(chan, ctrl).unwrap_or_default())
+            .collect();
+
+        let gens = GenParam::ALL
+            .iter()
+            .filter_map(|param| {
+                let value = self.get_gen(chan, *param).ok()?;
+                if value != 0.0 {
+                    Some((*param, value))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        ChannelState {
+            program,
+            cc,
+            pitch_bend: self
+                .get_pitch_bend_checked(chan)
+                .unwrap_or(PitchBend::CENTER),
+            pitch_wheel_sens: self.get_pitch_wheel_sens_checked(chan).unwrap_or_default(),
+            gens,
+        }
+    }
+
+    /**
+    Apply the state captured by [`Synth::snapshot`]
+
+    The synth should have the same SoundFonts loaded. The fonts are
+    found by name first, so the order of loading may differ. When the
+    number of channels differs, only the common channels are restored.
+     */
+    pub fn restore(&self, state: &SynthState) -> Status {
+        for tuning in &state.tunings {
+            let pitch: &[f64; 128] =
+                tuning
+                    .pitch
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| Error::Tuning {
+                        bank: tuning.bank.get(),
+                        prog: tuning.prog.get(),
+                    })?;
+            self.create_key_tuning(tuning.bank, tuning.prog, &tuning.name, pitch)?;
+        }
+
+        for (chan, channel) in (0..self.count_midi_channels()).zip(&state.channels) {
+            self.restore_channel(chan, channel)?;
+        }
+
+        self.set_gain(state.gain);
+        self.set_polyphony(state.polyphony)?;
+        self.set_reverb(&state.reverb);
+        self.set_chorus(&state.chorus);
+
+        Ok(())
+    }
+
+    fn restore_channel(&self, chan: u32, state: &ChannelState) -> Status {
+        if let Some(program) = &state.program {
+            let font_id = program
+                .font_name
+                .as_deref()
+                .and_then(|name| {
+                    self.sfont_iter()
+                        .find(|font| font.get_name().as_deref() == Some(name))
+                        .map(|font| font.get_id())
+                })
+                .unwrap_or(program.font_id);
+            self.program_select(chan, font_id, program.bank, program.prog)?;
+        }
+
+        for (ctrl, val) in state.cc.iter().enumerate() {
+            let ctrl = Ctrl::new(ctrl as _)?;
+            if ChannelState::is_restored(ctrl) {
+                self.cc(chan, ctrl, *val)?;
+            }
+        }
+
+        self.pitch_wheel_sens(chan, state.pitch_wheel_sens)?;
+        self.pitch_bend(chan, state.pitch_bend)?;
+
+        for param in GenParam::ALL.iter() {
+            let value = state
+                .gens
+                .iter()
+                .find(|(gen, _)| gen == param)
+                .map_or(0.0, |(_, value)| *value);
+            self.set_gen(chan, *param, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Ctrl, GenParam, Settings, Synth};
+
+    fn synth_with_font() -> Synth {
+        let synth = Synth::new(Settings::new().unwrap()).unwrap();
+        synth.sfload("sf_/Boomwhacker.sf2", true).unwrap();
+        synth
+    }
+
+    #[test]
+    fn snapshot_restore() {
+        let synth = synth_with_font();
+
+        synth.cc(1, Ctrl::VOLUME, 100).unwrap();
+        synth.cc(1, Ctrl::PAN, 20).unwrap();
+        synth.pitch_wheel_sens(1, 12).unwrap();
+        synth.pitch_bend(1, 1000).unwrap();
+        synth.set_gen(2, GenParam::FilterFc, -1200.0).unwrap();
+        synth.set_gain(0.5);
+
+        let mut pitch = [0.0; 128];
+        for (key, pitch) in pitch.iter_mut().enumerate() {
+            *pitch = key as f64 * 100.0 + 10.0;
+        }
+        synth.create_key_tuning(0, 1, "Shifted", &pitch).unwrap();
+
+        let state = synth.snapshot();
+
+        assert_eq!(state.channels.len(), 16);
+        assert_eq!(state.channels[1].cc[Ctrl::VOLUME.get() as usize], 100);
+        assert_eq!(state.channels[1].pitch_bend, 1000);
+        assert_eq!(state.channels[2].gens, [(GenParam::FilterFc, -1200.0)]);
+        assert_eq!(state.tunings.len(), 1);
+        assert_eq!(state.tunings[0].name, "Shifted");
+
+        let restored = synth_with_font();
+
+        restored.restore(&state).unwrap();
+
+        assert_eq!(restored.snapshot(), state);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_json() {
+        use crate::{Bank, ChorusMode, ChorusParams, InterpMethod};
+
+        let chorus = ChorusParams {
+            mode: ChorusMode::Triangle,
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&chorus).unwrap();
+
+        assert!(json.contains(r#""mode":"triangle""#));
+        assert_eq!(serde_json::from_str::<ChorusParams>(&json).unwrap(), chorus);
+
+        assert_eq!(
+            serde_json::to_string(&GenParam::ChorussEnd).unwrap(),
+            r#""chorus_send""#
+        );
+        assert_eq!(
+            serde_json::to_string(&InterpMethod::FourthOrder).unwrap(),
+            r#""fourth_order""#
+        );
+        assert_eq!(serde_json::to_string(&Bank::DRUMS).unwrap(), "128");
+        assert!(serde_json::from_str::<Bank>("16384").is_err());
+    }
+}
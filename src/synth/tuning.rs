@@ -1,4 +1,7 @@
-use crate::{ffi, Bank, Chan, Prog, Result, Status, Synth};
+use crate::{
+    ffi, hz_to_cents, hz_to_cents_table, mts, Bank, Chan, Error, IntoMidi, Key, KeyboardMapping,
+    Prog, Result, Scale, Status, Synth,
+};
 use std::{
     ffi::{CStr, CString},
     marker::PhantomData,
@@ -16,23 +19,33 @@ impl Synth {
     the pitch in cents of every key in cents. However, if 'pitches' is
     NULL, a new tuning is created with the well-tempered scale.
      */
-    pub fn create_key_tuning<S: AsRef<str>>(
+    pub fn create_key_tuning<B, P, S>(
         &self,
-        tuning_bank: Bank,
-        tuning_prog: Prog,
+        tuning_bank: B,
+        tuning_prog: P,
         name: S,
         pitch: &[f64; 128],
-    ) -> Status {
-        let name = CString::new(name.as_ref()).unwrap();
+    ) -> Status
+    where
+        B: IntoMidi<Bank>,
+        P: IntoMidi<Prog>,
+        S: AsRef<str>,
+    {
+        let (tuning_bank, tuning_prog) = (tuning_bank.into_midi()?, tuning_prog.into_midi()?);
+        let name = tuning_name(name.as_ref(), tuning_bank, tuning_prog)?;
         self.zero_ok(unsafe {
             ffi::fluid_synth_create_key_tuning(
                 self.handle.as_ptr(),
-                tuning_bank as _,
-                tuning_prog as _,
+                tuning_bank.get() as _,
+                tuning_prog.get() as _,
                 name.as_ptr(),
                 pitch.as_ptr() as _,
             )
         })
+        .map_err(|_| Error::Tuning {
+            bank: tuning_bank.get(),
+            prog: tuning_prog.get(),
+        })
     }
 
     /**
@@ -42,44 +55,64 @@ impl Synth {
     pitches[0] equals -33, then the C-keys will be tuned 33 cents
     below the well-tempered C.
      */
-    pub fn create_octave_tuning<S: AsRef<str>>(
+    pub fn create_octave_tuning<B, P, S>(
         &self,
-        tuning_bank: Bank,
-        tuning_prog: Prog,
+        tuning_bank: B,
+        tuning_prog: P,
         name: S,
         pitch: &[f64; 12],
-    ) -> Status {
-        let name = CString::new(name.as_ref()).unwrap();
+    ) -> Status
+    where
+        B: IntoMidi<Bank>,
+        P: IntoMidi<Prog>,
+        S: AsRef<str>,
+    {
+        let (tuning_bank, tuning_prog) = (tuning_bank.into_midi()?, tuning_prog.into_midi()?);
+        let name = tuning_name(name.as_ref(), tuning_bank, tuning_prog)?;
         self.zero_ok(unsafe {
             ffi::fluid_synth_create_octave_tuning(
                 self.handle.as_ptr(),
-                tuning_bank as _,
-                tuning_prog as _,
+                tuning_bank.get() as _,
+                tuning_prog.get() as _,
                 name.as_ptr(),
                 pitch.as_ptr(),
             )
         })
+        .map_err(|_| Error::Tuning {
+            bank: tuning_bank.get(),
+            prog: tuning_prog.get(),
+        })
     }
 
-    pub fn activate_octave_tuning<S: AsRef<str>>(
+    pub fn activate_octave_tuning<B, P, S>(
         &self,
-        bank: Bank,
-        prog: Prog,
+        bank: B,
+        prog: P,
         name: S,
         pitch: &[f64; 12],
         apply: bool,
-    ) -> Status {
-        let name = CString::new(name.as_ref()).unwrap();
+    ) -> Status
+    where
+        B: IntoMidi<Bank>,
+        P: IntoMidi<Prog>,
+        S: AsRef<str>,
+    {
+        let (bank, prog) = (bank.into_midi()?, prog.into_midi()?);
+        let name = tuning_name(name.as_ref(), bank, prog)?;
         self.zero_ok(unsafe {
             ffi::fluid_synth_activate_octave_tuning(
                 self.handle.as_ptr(),
-                bank as _,
-                prog as _,
+                bank.get() as _,
+                prog.get() as _,
                 name.as_ptr(),
                 pitch.as_ptr(),
                 apply as _,
             )
         })
+        .map_err(|_| Error::Tuning {
+            bank: bank.get(),
+            prog: prog.get(),
+        })
     }
 
     /**
@@ -89,65 +122,234 @@ impl Synth {
     will have their pitch updated. 'APPLY' IS CURRENTLY IGNORED. The
     changes will be available for newly triggered notes only.
      */
-    pub fn tune_notes<K, P>(
+    pub fn tune_notes<B, P, K, T>(
         &self,
-        tuning_bank: Bank,
-        tuning_prog: Prog,
+        tuning_bank: B,
+        tuning_prog: P,
         keys: K,
-        pitch: P,
+        pitch: T,
         apply: bool,
     ) -> Status
     where
+        B: IntoMidi<Bank>,
+        P: IntoMidi<Prog>,
         K: AsRef<[u32]>,
-        P: AsRef<[f64]>,
+        T: AsRef<[f64]>,
     {
+        let (tuning_bank, tuning_prog) = (tuning_bank.into_midi()?, tuning_prog.into_midi()?);
         let keys = keys.as_ref();
         let pitch = pitch.as_ref();
         let len = keys.len().min(pitch.len());
+        for key in &keys[..len] {
+            Key::new(*key)?;
+        }
         self.zero_ok(unsafe {
             ffi::fluid_synth_tune_notes(
                 self.handle.as_ptr(),
-                tuning_bank as _,
-                tuning_prog as _,
+                tuning_bank.get() as _,
+                tuning_prog.get() as _,
                 len as _,
                 keys.as_ptr() as _,
                 pitch.as_ptr() as _,
                 apply as _,
             )
         })
+        .map_err(|_| Error::Tuning {
+            bank: tuning_bank.get(),
+            prog: tuning_prog.get(),
+        })
+    }
+
+    /**
+    Create a new key-based tuning from the frequencies of every key in Hz.
+
+    See [`Synth::create_key_tuning`].
+     */
+    pub fn create_key_tuning_hz<B, P, S>(
+        &self,
+        tuning_bank: B,
+        tuning_prog: P,
+        name: S,
+        hz: &[f64; 128],
+    ) -> Status
+    where
+        B: IntoMidi<Bank>,
+        P: IntoMidi<Prog>,
+        S: AsRef<str>,
+    {
+        self.create_key_tuning(tuning_bank, tuning_prog, name, &hz_to_cents_table(hz))
+    }
+
+    /**
+    Request note tuning changes using frequencies in Hz.
+
+    See [`Synth::tune_notes`].
+     */
+    pub fn tune_notes_hz<B, P, K, F>(
+        &self,
+        tuning_bank: B,
+        tuning_prog: P,
+        keys: K,
+        hz: F,
+        apply: bool,
+    ) -> Status
+    where
+        B: IntoMidi<Bank>,
+        P: IntoMidi<Prog>,
+        K: AsRef<[u32]>,
+        F: AsRef<[f64]>,
+    {
+        let pitch = hz
+            .as_ref()
+            .iter()
+            .map(|hz| hz_to_cents(*hz))
+            .collect::<Vec<_>>();
+        self.tune_notes(tuning_bank, tuning_prog, keys, pitch, apply)
+    }
+
+    /**
+    Create a new key-based tuning from Scala scale and keyboard mapping.
+
+    The default mapping (`None`) maps the scale linearly starting from
+    the middle C with A4 tuned to 440 Hz.
+    The description of scale is used as name of tuning.
+     */
+    pub fn load_scala_tuning<B, P>(
+        &self,
+        tuning_bank: B,
+        tuning_prog: P,
+        scale: &Scale,
+        mapping: Option<&KeyboardMapping>,
+    ) -> Status
+    where
+        B: IntoMidi<Bank>,
+        P: IntoMidi<Prog>,
+    {
+        let pitch = match mapping {
+            Some(mapping) => scale.key_tuning(mapping)?,
+            None => scale.key_tuning(&KeyboardMapping::default())?,
+        };
+        self.create_key_tuning(tuning_bank, tuning_prog, &scale.description, &pitch)
+    }
+
+    /**
+    Dump the tuning as Scala scale with keyboard mapping.
+
+    See [`Scale::from_key_tuning`].
+     */
+    pub fn tuning_dump_scala<B, P>(&self, bank: B, prog: P) -> Result<(Scale, KeyboardMapping)>
+    where
+        B: IntoMidi<Bank>,
+        P: IntoMidi<Prog>,
+    {
+        let (name, pitch) = self.tuning_dump(bank, prog)?;
+        Ok(Scale::from_key_tuning(name, &pitch))
+    }
+
+    /**
+    Apply the MIDI Tuning Standard message.
+
+    The bulk dumps create tunings in bank `0`. The octave tunings
+    are created in bank [`mts::OCTAVE_TUNING_BANK`] using channel
+    number as program and activated on the addressed channels.
+    The dump requests are ignored.
+     */
+    pub fn apply_mts(&self, message: &mts::Message) -> Status {
+        match message {
+            mts::Message::BulkDumpRequest { .. } => Ok(()),
+            mts::Message::BulkDump {
+                prog, name, pitch, ..
+            } => self.create_key_tuning(Bank::MIN, *prog, name, pitch),
+            mts::Message::NoteChange {
+                realtime,
+                bank,
+                prog,
+                notes,
+                ..
+            } => {
+                let keys = notes.iter().map(|(key, _)| key.get()).collect::<Vec<_>>();
+                let pitch = notes.iter().map(|(_, cents)| *cents).collect::<Vec<_>>();
+                self.tune_notes(bank.unwrap_or(Bank::MIN), *prog, keys, pitch, *realtime)
+            }
+            mts::Message::OctaveChange {
+                realtime,
+                channels,
+                derivation,
+                ..
+            } => {
+                for chan in 0..self.count_midi_channels().min(16) {
+                    if channels & (1 << chan) != 0 {
+                        self.activate_octave_tuning(
+                            mts::OCTAVE_TUNING_BANK,
+                            chan,
+                            "MTS octave tuning",
+                            derivation,
+                            *realtime,
+                        )?;
+                        self.activate_tuning(chan, mts::OCTAVE_TUNING_BANK, chan, *realtime)?;
+                    }
+                }
+                Ok(())
+            }
+        }
     }
 
     /**
     Select a tuning for a channel.
      */
-    pub fn select_tuning(&self, chan: Chan, tuning_bank: Bank, tuning_prog: Prog) -> Status {
+    pub fn select_tuning<C, B, P>(&self, chan: C, tuning_bank: B, tuning_prog: P) -> Status
+    where
+        C: IntoMidi<Chan>,
+        B: IntoMidi<Bank>,
+        P: IntoMidi<Prog>,
+    {
+        let (tuning_bank, tuning_prog) = (tuning_bank.into_midi()?, tuning_prog.into_midi()?);
+        let chan = self.check_chan(chan.into_midi()?)?;
         self.zero_ok(unsafe {
             ffi::fluid_synth_select_tuning(
                 self.handle.as_ptr(),
-                chan as _,
-                tuning_bank as _,
-                tuning_prog as _,
+                chan.get() as _,
+                tuning_bank.get() as _,
+                tuning_prog.get() as _,
             )
         })
+        .map_err(|_| Error::Tuning {
+            bank: tuning_bank.get(),
+            prog: tuning_prog.get(),
+        })
     }
 
-    pub fn activate_tuning(&self, chan: Chan, bank: Bank, prog: Prog, apply: bool) -> Status {
+    pub fn activate_tuning<C, B, P>(&self, chan: C, bank: B, prog: P, apply: bool) -> Status
+    where
+        C: IntoMidi<Chan>,
+        B: IntoMidi<Bank>,
+        P: IntoMidi<Prog>,
+    {
+        let (bank, prog) = (bank.into_midi()?, prog.into_midi()?);
+        let chan = self.check_chan(chan.into_midi()?)?;
         self.zero_ok(unsafe {
             ffi::fluid_synth_activate_tuning(
                 self.handle.as_ptr(),
-                chan as _,
-                bank as _,
-                prog as _,
+                chan.get() as _,
+                bank.get() as _,
+                prog.get() as _,
                 apply as _,
             )
         })
+        .map_err(|_| Error::Tuning {
+            bank: bank.get(),
+            prog: prog.get(),
+        })
     }
 
     /**
     Set the tuning to the default well-tempered tuning on a channel.
      */
-    pub fn reset_tuning(&self, chan: Chan) -> Status {
-        self.zero_ok(unsafe { ffi::fluid_synth_reset_tuning(self.handle.as_ptr(), chan as _) })
+    pub fn reset_tuning<C: IntoMidi<Chan>>(&self, chan: C) -> Status {
+        let chan = self.check_chan(chan.into_midi()?)?;
+        self.zero_ok(unsafe {
+            ffi::fluid_synth_reset_tuning(self.handle.as_ptr(), chan.get() as _)
+        })
     }
 
     /**
@@ -162,7 +364,12 @@ impl Synth {
 
     This function returns both the name and pitch values of a tuning.
      */
-    pub fn tuning_dump(&self, bank: Bank, prog: Prog) -> Result<(String, [f64; 128])> {
+    pub fn tuning_dump<B, P>(&self, bank: B, prog: P) -> Result<(String, [f64; 128])>
+    where
+        B: IntoMidi<Bank>,
+        P: IntoMidi<Prog>,
+    {
+        let (bank, prog) = (bank.into_midi()?, prog.into_midi()?);
         const NAME_LEN: usize = 128;
 
         let mut name = MaybeUninit::<[u8; NAME_LEN]>::uninit();
@@ -171,17 +378,20 @@ impl Synth {
         self.zero_ok(unsafe {
             ffi::fluid_synth_tuning_dump(
                 self.handle.as_ptr(),
-                bank as _,
-                prog as _,
+                bank.get() as _,
+                prog.get() as _,
                 name.as_mut_ptr() as _,
                 NAME_LEN as _,
                 pitch.as_mut_ptr() as _,
             )
+        })
+        .map_err(|_| Error::Tuning {
+            bank: bank.get(),
+            prog: prog.get(),
         })?;
         Ok((
             (unsafe { CStr::from_ptr(name.as_ptr() as _) })
-                .to_str()
-                .unwrap()
+                .to_string_lossy()
                 .into(),
             unsafe { pitch.assume_init() },
         ))
@@ -192,7 +402,12 @@ impl Synth {
 
     This function returns the only name of a tuning.
      */
-    pub fn tuning_dump_name(&self, bank: Bank, prog: Prog) -> Result<String> {
+    pub fn tuning_dump_name<B, P>(&self, bank: B, prog: P) -> Result<String>
+    where
+        B: IntoMidi<Bank>,
+        P: IntoMidi<Prog>,
+    {
+        let (bank, prog) = (bank.into_midi()?, prog.into_midi()?);
         const NAME_LEN: usize = 128;
 
         let mut name = MaybeUninit::<[u8; NAME_LEN]>::uninit();
@@ -200,16 +415,19 @@ impl Synth {
         self.zero_ok(unsafe {
             ffi::fluid_synth_tuning_dump(
                 self.handle.as_ptr(),
-                bank as _,
-                prog as _,
+                bank.get() as _,
+                prog.get() as _,
                 name.as_mut_ptr() as _,
                 NAME_LEN as _,
                 null_mut(),
             )
+        })
+        .map_err(|_| Error::Tuning {
+            bank: bank.get(),
+            prog: prog.get(),
         })?;
         Ok((unsafe { CStr::from_ptr(name.as_ptr() as _) })
-            .to_str()
-            .unwrap()
+            .to_string_lossy()
             .into())
     }
 
@@ -218,23 +436,40 @@ impl Synth {
 
     This function returns the only pitch values of a tuning.
      */
-    pub fn tuning_dump_pitch(&self, bank: Bank, prog: Prog) -> Result<[f64; 128]> {
+    pub fn tuning_dump_pitch<B, P>(&self, bank: B, prog: P) -> Result<[f64; 128]>
+    where
+        B: IntoMidi<Bank>,
+        P: IntoMidi<Prog>,
+    {
+        let (bank, prog) = (bank.into_midi()?, prog.into_midi()?);
         let mut pitch = MaybeUninit::<[f64; 128]>::uninit();
 
         self.zero_ok(unsafe {
             ffi::fluid_synth_tuning_dump(
                 self.handle.as_ptr(),
-                bank as _,
-                prog as _,
+                bank.get() as _,
+                prog.get() as _,
                 null_mut(),
                 0,
                 pitch.as_mut_ptr() as _,
             )
+        })
+        .map_err(|_| Error::Tuning {
+            bank: bank.get(),
+            prog: prog.get(),
         })?;
         Ok(unsafe { pitch.assume_init() })
     }
 }
 
+/// Convert the name of tuning which must not contain zero bytes
+fn tuning_name(name: &str, bank: Bank, prog: Prog) -> Result<CString> {
+    CString::new(name).map_err(|_| Error::Tuning {
+        bank: bank.get(),
+        prog: prog.get(),
+    })
+}
+
 /**
 The iterator over tunings
  */
@@ -266,23 +501,51 @@ impl<'a> Iterator for TuningIter<'a> {
                 ffi::fluid_synth_tuning_iteration_start(self.handle.as_ptr());
             }
         }
-        if self.next {
-            let mut bank = MaybeUninit::uninit();
-            let mut prog = MaybeUninit::uninit();
-            self.next = 0
-                != unsafe {
-                    ffi::fluid_synth_tuning_iteration_next(
-                        self.handle.as_ptr(),
-                        bank.as_mut_ptr(),
-                        prog.as_mut_ptr(),
-                    )
-                };
-
-            Some((unsafe { bank.assume_init() as _ }, unsafe {
-                prog.assume_init() as _
-            }))
-        } else {
-            None
+        if !self.next {
+            return None;
         }
+
+        let mut bank = MaybeUninit::uninit();
+        let mut prog = MaybeUninit::uninit();
+
+        // the bank and program are filled only when the next tuning exists
+        if 0 == unsafe {
+            ffi::fluid_synth_tuning_iteration_next(
+                self.handle.as_ptr(),
+                bank.as_mut_ptr(),
+                prog.as_mut_ptr(),
+            )
+        } {
+            self.next = false;
+            return None;
+        }
+
+        Some(unsafe {
+            (
+                Bank::saturating(bank.assume_init() as _),
+                Prog::saturating(prog.assume_init() as _),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Error, Settings, Synth};
+
+    #[test]
+    fn invalid_tuning() {
+        let synth = Synth::new(Settings::new().unwrap()).unwrap();
+
+        assert_eq!(
+            synth.create_key_tuning(0, 1, "Bad\0name", &[0.0; 128]),
+            Err(Error::Tuning { bank: 0, prog: 1 })
+        );
+
+        synth.create_key_tuning(0, 1, "Good", &[0.0; 128]).unwrap();
+        assert_eq!(
+            synth.tune_notes(0, 1, [60, 128], [6000.0, 12800.0], false),
+            Err(Error::InvalidKey { key: 128 })
+        );
     }
 }
@@ -0,0 +1,323 @@
+use crate::{ConcertPitch, Key};
+use std::num::NonZeroU32;
+
+/// The 5-limit just intonation ratios of chromatic scale
+const JUST_5_LIMIT: [(u32, u32); 12] = [
+    (1, 1),
+    (16, 15),
+    (9, 8),
+    (6, 5),
+    (5, 4),
+    (4, 3),
+    (45, 32),
+    (3, 2),
+    (8, 5),
+    (5, 3),
+    (9, 5),
+    (15, 8),
+];
+
+/// The 7-limit just intonation ratios of chromatic scale
+const JUST_7_LIMIT: [(u32, u32); 12] = [
+    (1, 1),
+    (16, 15),
+    (8, 7),
+    (6, 5),
+    (5, 4),
+    (4, 3),
+    (7, 5),
+    (3, 2),
+    (8, 5),
+    (5, 3),
+    (7, 4),
+    (15, 8),
+];
+
+/// Pythagorean tuning from Eb to G#
+const PYTHAGOREAN: [(u32, u32); 12] = [
+    (1, 1),
+    (2187, 2048),
+    (9, 8),
+    (32, 27),
+    (81, 64),
+    (4, 3),
+    (729, 512),
+    (3, 2),
+    (6561, 4096),
+    (27, 16),
+    (16, 9),
+    (243, 128),
+];
+
+/// Werckmeister III (1/4 Pythagorean comma on C-G-D-A and B-F#)
+const WERCKMEISTER_3: [f64; 12] = [
+    0.0, 90.225, 192.180, 294.135, 390.225, 498.045, 588.270, 696.090, 792.180, 888.270, 996.090,
+    1092.180,
+];
+
+/// Kirnberger III (1/4 syntonic comma on C-G-D-A-E and schisma on F#-C#)
+const KIRNBERGER_3: [f64; 12] = [
+    0.0, 90.225, 193.157, 294.135, 386.314, 498.045, 590.224, 696.578, 792.180, 889.735, 996.090,
+    1088.269,
+];
+
+/// Vallotti (1/6 Pythagorean comma on F-C-G-D-A-E-B)
+const VALLOTTI: [f64; 12] = [
+    0.0, 94.135, 196.090, 298.045, 392.180, 501.955, 592.180, 698.045, 796.090, 894.135, 1000.0,
+    1090.225,
+];
+
+/// Young II (1/6 Pythagorean comma on C-G-D-A-E-B-F#)
+const YOUNG_2: [f64; 12] = [
+    0.0, 90.225, 196.090, 294.135, 392.180, 498.045, 588.270, 698.045, 792.180, 894.135, 996.090,
+    1090.225,
+];
+
+/// The syntonic comma in cents
+const SYNTONIC_COMMA: f64 = 21.506_289_596;
+
+fn ratio_cents((num, den): (u32, u32)) -> f64 {
+    1200.0 * (num as f64 / den as f64).log2()
+}
+
+/**
+The octave-repeating temperament
+
+Defined by deviations of 12 pitch classes from the equal temperament
+in cents, starting from C. Use [`Temperament::key_tuning`] or
+[`Temperament::octave_tuning`] to get the tables for
+[`Synth::create_key_tuning`](crate::Synth::create_key_tuning) and
+[`Synth::create_octave_tuning`](crate::Synth::create_octave_tuning).
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Temperament {
+    pub name: String,
+    /// The deviations of pitch classes from equal temperament in cents
+    pub offsets: [f64; 12],
+}
+
+impl Temperament {
+    /// Create temperament from pitches of chromatic scale starting from C in cents
+    pub fn from_cents<S: Into<String>>(name: S, cents: [f64; 12]) -> Self {
+        let mut offsets = [0.0; 12];
+        for (step, (offset, cents)) in offsets.iter_mut().zip(cents.iter()).enumerate() {
+            *offset = cents - step as f64 * 100.0;
+        }
+        Self {
+            name: name.into(),
+            offsets,
+        }
+    }
+
+    /**
+    Create temperament from frequency ratios of chromatic scale
+
+    The ratios are the intervals of 12 consecutive semitones above the tonic.
+    Only the pitch class of tonic key is used.
+     */
+    pub fn from_ratios<S: Into<String>>(name: S, tonic: Key, ratios: [(u32, u32); 12]) -> Self {
+        let tonic = tonic.get() as usize % 12;
+        let mut offsets = [0.0; 12];
+        for (step, ratio) in ratios.iter().enumerate() {
+            offsets[(tonic + step) % 12] = ratio_cents(*ratio) - step as f64 * 100.0;
+        }
+        Self {
+            name: name.into(),
+            offsets,
+        }
+    }
+
+    /// The 12-tone equal temperament
+    pub fn equal() -> Self {
+        Self {
+            name: "Equal temperament".into(),
+            offsets: [0.0; 12],
+        }
+    }
+
+    /// The 5-limit just intonation on tonic
+    pub fn just_5_limit(tonic: Key) -> Self {
+        Self::from_ratios("5-limit just intonation", tonic, JUST_5_LIMIT)
+    }
+
+    /// The 7-limit just intonation on tonic
+    pub fn just_7_limit(tonic: Key) -> Self {
+        Self::from_ratios("7-limit just intonation", tonic, JUST_7_LIMIT)
+    }
+
+    /// The Pythagorean tuning (pure fifths from Eb to G#)
+    pub fn pythagorean() -> Self {
+        Self::from_ratios("Pythagorean", Key::MIDDLE_C, PYTHAGOREAN)
+    }
+
+    /// The quarter-comma meantone (fifths from Eb to G#)
+    pub fn quarter_comma_meantone() -> Self {
+        let fifth = ratio_cents((3, 2)) - SYNTONIC_COMMA / 4.0;
+        let mut cents = [0.0; 12];
+        // the number of fifths from C of each pitch class
+        for fifths in -3i32..=8 {
+            let pitch = (fifths as f64 * fifth).rem_euclid(1200.0);
+            cents[(fifths * 7).rem_euclid(12) as usize] = pitch;
+        }
+        Self::from_cents("Quarter-comma meantone", cents)
+    }
+
+    /// The Werckmeister III well temperament
+    pub fn werckmeister_3() -> Self {
+        Self::from_cents("Werckmeister III", WERCKMEISTER_3)
+    }
+
+    /// The Kirnberger III well temperament
+    pub fn kirnberger_3() -> Self {
+        Self::from_cents("Kirnberger III", KIRNBERGER_3)
+    }
+
+    /// The Vallotti well temperament
+    pub fn vallotti() -> Self {
+        Self::from_cents("Vallotti", VALLOTTI)
+    }
+
+    /// The Young II well temperament
+    pub fn young() -> Self {
+        Self::from_cents("Young", YOUNG_2)
+    }
+
+    /// Get the catalog of historical temperaments
+    pub fn historical() -> Vec<Self> {
+        vec![
+            Self::pythagorean(),
+            Self::quarter_comma_meantone(),
+            Self::werckmeister_3(),
+            Self::kirnberger_3(),
+            Self::vallotti(),
+            Self::young(),
+        ]
+    }
+
+    /// The offset of all pitches which keeps A4 at the concert pitch
+    fn shift(&self, pitch: ConcertPitch) -> f64 {
+        pitch.key_to_cents(Key::A4) - Key::A4.get() as f64 * 100.0 - self.offsets[9]
+    }
+
+    /**
+    Get the deviations of pitch classes for octave-based tuning
+
+    The deviations are shifted so A4 sounds at the concert pitch.
+     */
+    pub fn octave_tuning(&self, pitch: ConcertPitch) -> [f64; 12] {
+        let shift = self.shift(pitch);
+        let mut cents = self.offsets;
+        for cents in cents.iter_mut() {
+            *cents += shift;
+        }
+        cents
+    }
+
+    /**
+    Get the absolute pitches of all keys for key-based tuning
+
+    The pitches are shifted so A4 sounds at the concert pitch.
+     */
+    pub fn key_tuning(&self, pitch: ConcertPitch) -> [f64; 128] {
+        let shift = self.shift(pitch);
+        let mut cents = [0.0; 128];
+        for (key, cents) in cents.iter_mut().enumerate() {
+            *cents = key as f64 * 100.0 + self.offsets[key % 12] + shift;
+        }
+        cents
+    }
+}
+
+/**
+Get the absolute pitches of all keys for equal division of interval
+
+Every key is one of `divisions` steps of interval in cents
+(`1200.0` for the octave) with A4 at the concert pitch.
+ */
+pub fn equal_division(divisions: NonZeroU32, interval: f64, pitch: ConcertPitch) -> [f64; 128] {
+    let step = interval / divisions.get() as f64;
+    let reference = pitch.key_to_cents(Key::A4);
+    let mut cents = [0.0; 128];
+    for (key, cents) in cents.iter_mut().enumerate() {
+        *cents = reference + (key as f64 - Key::A4.get() as f64) * step;
+    }
+    cents
+}
+
+/**
+Get the absolute pitches of all keys for equal division of octave
+
+See [`equal_division`].
+ */
+pub fn edo(divisions: NonZeroU32, pitch: ConcertPitch) -> [f64; 128] {
+    equal_division(divisions, 1200.0, pitch)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    macro_rules! assert_eqf {
+        ($a:expr, $b:expr) => {{
+            let (a, b): (f64, f64) = ($a, $b);
+            assert!((a - b).abs() < 1.0e-3, "{} != {}", a, b);
+        }};
+    }
+
+    #[test]
+    fn equal_divisions() {
+        let divisions = |divisions| NonZeroU32::new(divisions).unwrap();
+        let pitch = edo(divisions(12), ConcertPitch::default());
+
+        for (key, pitch) in pitch.iter().enumerate() {
+            assert_eqf!(*pitch, key as f64 * 100.0);
+        }
+
+        let pitch = edo(divisions(19), ConcertPitch::default());
+
+        assert_eqf!(pitch[69], 6900.0);
+        assert_eqf!(pitch[88] - pitch[69], 1200.0);
+
+        // Bohlen-Pierce scale divides the tritave
+        let pitch = equal_division(divisions(13), 1200.0 * 3f64.log2(), ConcertPitch::default());
+
+        assert_eqf!(pitch[82] - pitch[69], 1200.0 * 3f64.log2());
+    }
+
+    #[test]
+    fn temperaments() {
+        let pythagorean = Temperament::pythagorean();
+
+        assert_eqf!(pythagorean.offsets[7], 1.955);
+
+        let meantone = Temperament::quarter_comma_meantone();
+
+        assert_eqf!(meantone.offsets[4], ratio_cents((5, 4)) - 400.0);
+        assert_eqf!(meantone.offsets[3], 310.265 - 300.0);
+
+        let just = Temperament::just_5_limit(Key::new(62).unwrap());
+
+        // the major third above D is F#
+        assert_eqf!(
+            just.offsets[6] - just.offsets[2],
+            ratio_cents((5, 4)) - 400.0
+        );
+
+        for temperament in Temperament::historical() {
+            let cents = temperament.key_tuning(ConcertPitch::default());
+
+            assert_eqf!(cents[69], 6900.0);
+            assert_eqf!(cents[81] - cents[69], 1200.0);
+        }
+
+        let baroque = ConcertPitch::new(415.0);
+        let cents = Temperament::vallotti().key_tuning(baroque);
+
+        assert_eqf!(cents[69], baroque.key_to_cents(Key::A4));
+
+        let octave = Temperament::vallotti().octave_tuning(ConcertPitch::default());
+
+        assert_eqf!(octave[9], 0.0);
+        assert_eqf!(octave[0], 5.865);
+    }
+}
@@ -0,0 +1,431 @@
+use crate::{Bank, Chan, IntoMidi, Key, Prog, Result, Status, Synth, Temperament, Vel};
+
+/**
+The weights of intervals above the chord root used to estimate it
+
+Thirds and fifth are the strongest evidence of the root,
+sevenths and added tones are weaker.
+ */
+const ROOT_WEIGHTS: [f64; 12] = [0.0, 0.0, 0.5, 2.0, 2.0, 0.0, 0.5, 3.0, 0.0, 1.0, 1.0, 1.0];
+
+/// The bonus of the bass note to resolve ambiguous chords like C6/Am7
+const BASS_WEIGHT: f64 = 0.5;
+
+/**
+The strategy of keeping pitch between chords
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DriftPolicy {
+    /// The chord root always sounds at its equal-tempered pitch
+    FixedRoot,
+    /**
+    The notes held across chord change keep their pitch
+
+    Pure intervals make the pitch drift (like comma pump),
+    so the root deviation from equal temperament is limited by `max_drift` cents.
+     */
+    CommonTone { max_drift: f64 },
+}
+
+impl Default for DriftPolicy {
+    fn default() -> Self {
+        DriftPolicy::CommonTone { max_drift: 25.0 }
+    }
+}
+
+/**
+The adaptive just intonation tuner
+
+Observes the notes on a channel and retunes the held notes to just
+intervals relative to the estimated chord root. The tuner owns a
+dedicated tuning program which is activated on the channel.
+
+Send notes through [`DynamicTuner::note_on`] and [`DynamicTuner::note_off`]
+instead of synth, so the new notes start at their tuned pitch.
+The library applies tuning changes to new notes only, so the held notes
+which change pitch are retriggered with their velocity.
+ */
+#[derive(Debug, Clone)]
+pub struct DynamicTuner {
+    chan: Chan,
+    bank: Bank,
+    prog: Prog,
+    policy: DriftPolicy,
+    max_deviation: f64,
+    intervals: [f64; 12],
+    /// The velocities of held keys
+    held: [Option<Vel>; 128],
+    /// The keys held at the last retuning
+    tuned: [bool; 128],
+    pitch: [f64; 128],
+    root: Option<Key>,
+    /// The deviation of root from equal temperament
+    drift: f64,
+}
+
+impl DynamicTuner {
+    /**
+    Create tuner which uses tuning program `bank:prog` on channel
+
+    The tuning is created in equal temperament and activated on the channel.
+     */
+    pub fn new<C, B, P>(synth: &Synth, chan: C, bank: B, prog: P) -> Result<Self>
+    where
+        C: IntoMidi<Chan>,
+        B: IntoMidi<Bank>,
+        P: IntoMidi<Prog>,
+    {
+        let (chan, bank, prog) = (chan.into_midi()?, bank.into_midi()?, prog.into_midi()?);
+        let mut pitch = [0.0; 128];
+        for (key, pitch) in pitch.iter_mut().enumerate() {
+            *pitch = key as f64 * 100.0;
+        }
+
+        synth.create_key_tuning(bank, prog, "Dynamic just intonation", &pitch)?;
+        synth.activate_tuning(chan, bank, prog, true)?;
+
+        Ok(Self {
+            chan,
+            bank,
+            prog,
+            policy: DriftPolicy::default(),
+            max_deviation: 50.0,
+            intervals: Temperament::just_5_limit(Key::MIN).offsets,
+            held: [None; 128],
+            tuned: [false; 128],
+            pitch,
+            root: None,
+            drift: 0.0,
+        })
+    }
+
+    /// Set the drift limiting strategy
+    pub fn with_drift(mut self, policy: DriftPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Set the maximum deviation of every note from equal temperament in cents
+    pub fn with_max_deviation(mut self, cents: f64) -> Self {
+        self.max_deviation = cents.abs();
+        self
+    }
+
+    /**
+    Use intervals of temperament instead of 5-limit just intonation
+
+    The deviations of temperament with tonic C are applied relative to the chord root.
+     */
+    pub fn with_intervals(mut self, temperament: &Temperament) -> Self {
+        self.intervals = temperament.offsets;
+        self
+    }
+
+    /// Get the estimated root of held chord
+    pub fn root(&self) -> Option<Key> {
+        self.root
+    }
+
+    /// Get the current deviation of key from equal temperament in cents
+    pub fn deviation(&self, key: Key) -> f64 {
+        self.pitch[key.get() as usize] - key.get() as f64 * 100.0
+    }
+
+    /// Retune held notes including new one and send noteon
+    pub fn note_on<K, V>(&mut self, synth: &Synth, key: K, vel: V) -> Status
+    where
+        K: IntoMidi<Key>,
+        V: IntoMidi<Vel>,
+    {
+        let (key, vel) = (key.into_midi()?, vel.into_midi()?);
+        if vel == Vel::OFF {
+            return self.note_off(synth, key);
+        }
+        self.held[key.get() as usize] = Some(vel);
+        let changed = self.retune(synth)?;
+        self.retrigger(synth, changed.into_iter().filter(|changed| *changed != key))?;
+        synth.note_on(self.chan, key, vel)
+    }
+
+    /// Send noteoff and retune remaining held notes
+    pub fn note_off<K: IntoMidi<Key>>(&mut self, synth: &Synth, key: K) -> Status {
+        let key = key.into_midi()?;
+        self.held[key.get() as usize] = None;
+        // the voice may be already ended, but the rest still needs retuning
+        let status = synth.note_off(self.chan, key);
+        let changed = self.retune(synth)?;
+        self.retrigger(synth, changed)?;
+        status
+    }
+
+    /// Forget held notes and restore equal temperament
+    pub fn reset(&mut self, synth: &Synth) -> Status {
+        self.held = [None; 128];
+        self.tuned = [false; 128];
+        self.root = None;
+        self.drift = 0.0;
+        let keys = (0..128).collect::<Vec<u32>>();
+        for (key, pitch) in self.pitch.iter_mut().enumerate() {
+            *pitch = key as f64 * 100.0;
+        }
+        synth.tune_notes(self.bank, self.prog, keys, &self.pitch[..], true)
+    }
+
+    fn held_keys(&self) -> impl Iterator<Item = Key> + Clone + '_ {
+        self.held
+            .iter()
+            .enumerate()
+            .filter(|(_, held)| held.is_some())
+            .map(|(key, _)| Key::saturating(key as _))
+    }
+
+    /// Update the tuning and get the held keys which pitch is changed
+    fn retune(&mut self, synth: &Synth) -> Result<Vec<Key>> {
+        let root = match estimate_root(self.held_keys()) {
+            Some(root) => root,
+            None => {
+                self.tuned = [false; 128];
+                self.root = None;
+                return Ok(Vec::new());
+            }
+        };
+
+        if let DriftPolicy::CommonTone { max_drift } = self.policy {
+            // keep the average pitch of common tones
+            let (sum, count) = self
+                .held_keys()
+                .filter(|key| self.tuned[key.get() as usize])
+                .map(|key| self.deviation(key) - self.interval(root, key))
+                .fold((0.0, 0), |(sum, count), drift| (sum + drift, count + 1));
+            if count > 0 {
+                self.drift = (sum / count as f64).clamp(-max_drift, max_drift);
+            }
+        } else {
+            self.drift = 0.0;
+        }
+
+        let mut keys = Vec::new();
+        let mut pitch = Vec::new();
+        for key in self.held_keys().collect::<Vec<_>>() {
+            let deviation = (self.interval(root, key) + self.drift)
+                .clamp(-self.max_deviation, self.max_deviation);
+            let cents = key.get() as f64 * 100.0 + deviation;
+            if (self.pitch[key.get() as usize] - cents).abs() > f64::EPSILON {
+                self.pitch[key.get() as usize] = cents;
+                keys.push(key.get());
+                pitch.push(cents);
+            }
+        }
+        for (tuned, held) in self.tuned.iter_mut().zip(&self.held) {
+            *tuned = held.is_some();
+        }
+        self.root = Some(root);
+
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        synth.tune_notes(self.bank, self.prog, &keys, pitch, true)?;
+        Ok(keys.into_iter().map(Key::saturating).collect())
+    }
+
+    /// Restart the sounding notes, so they pick up the changed tuning
+    fn retrigger(&self, synth: &Synth, keys: impl IntoIterator<Item = Key>) -> Status {
+        for key in keys {
+            if let Some(vel) = self.held[key.get() as usize] {
+                // fails when the held voice is already ended
+                let _ = synth.note_off(self.chan, key);
+                synth.note_on(self.chan, key, vel)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The deviation of just interval from root to key relative to equal temperament
+    fn interval(&self, root: Key, key: Key) -> f64 {
+        let interval = (key.get() + 12 - root.get() % 12) as usize % 12;
+        self.intervals[interval] - self.intervals[0]
+    }
+}
+
+/// Estimate the chord root as the held pitch class which explains the chord best
+fn estimate_root(keys: impl Iterator<Item = Key> + Clone) -> Option<Key> {
+    let bass = keys.clone().next()?;
+    keys.clone()
+        .map(|root| {
+            let score = keys
+                .clone()
+                .map(|key| ROOT_WEIGHTS[(key.get() + 12 - root.get() % 12) as usize % 12])
+                .sum::<f64>()
+                + if root.get() % 12 == bass.get() % 12 {
+                    BASS_WEIGHT
+                } else {
+                    0.0
+                };
+            (root, score)
+        })
+        // the first (lowest) candidate wins on equal scores
+        .fold(None, |best: Option<(Key, f64)>, (root, score)| match best {
+            Some((_, best_score)) if best_score >= score => best,
+            _ => Some((root, score)),
+        })
+        .map(|(root, _)| root)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{GenParam, Settings};
+
+    fn keys(keys: &[u32]) -> impl Iterator<Item = Key> + Clone + '_ {
+        keys.iter().map(|key| Key::new(*key).unwrap())
+    }
+
+    macro_rules! assert_eqf {
+        ($a:expr, $b:expr) => {{
+            let (a, b): (f64, f64) = ($a, $b);
+            assert!((a - b).abs() < 1.0e-3, "{} != {}", a, b);
+        }};
+    }
+
+    #[test]
+    fn root_estimation() {
+        assert_eq!(estimate_root(keys(&[])), None);
+        // C major
+        assert_eq!(estimate_root(keys(&[60, 64, 67])), Key::new(60).ok());
+        // C major first inversion
+        assert_eq!(estimate_root(keys(&[52, 60, 67])), Key::new(60).ok());
+        // A minor
+        assert_eq!(estimate_root(keys(&[57, 60, 64])), Key::new(57).ok());
+        // G dominant seventh
+        assert_eq!(estimate_root(keys(&[55, 59, 62, 65])), Key::new(55).ok());
+    }
+
+    #[test]
+    fn retune_chord() {
+        let synth = Synth::new(Settings::new().unwrap()).unwrap();
+        synth.sfload("sf_/Boomwhacker.sf2", true).unwrap();
+
+        let mut tuner = DynamicTuner::new(&synth, 0, 0, 0)
+            .unwrap()
+            .with_drift(DriftPolicy::FixedRoot);
+
+        tuner.note_on(&synth, 60, 100).unwrap();
+        tuner.note_on(&synth, 64, 100).unwrap();
+        tuner.note_on(&synth, 67, 100).unwrap();
+
+        assert_eq!(tuner.root(), Key::new(60).ok());
+        assert_eqf!(tuner.deviation(Key::new(60).unwrap()), 0.0);
+        assert_eqf!(tuner.deviation(Key::new(64).unwrap()), -13.686);
+        assert_eqf!(tuner.deviation(Key::new(67).unwrap()), 1.955);
+        assert_eqf!(synth.tuning_dump_pitch(0, 0).unwrap()[64], 6400.0 - 13.686);
+
+        let mut tuner = tuner.with_max_deviation(10.0);
+
+        tuner.note_off(&synth, 64).unwrap();
+        tuner.note_on(&synth, 64, 100).unwrap();
+
+        assert_eqf!(tuner.deviation(Key::new(64).unwrap()), -10.0);
+    }
+
+    /// Estimate the period of left channel in frames using autocorrelation
+    fn period(samples: &[f32]) -> usize {
+        let left = samples.iter().step_by(2).copied().collect::<Vec<_>>();
+        (60..400)
+            .map(|lag| {
+                let corr = left
+                    .iter()
+                    .zip(&left[lag..])
+                    .map(|(a, b)| a * b)
+                    .sum::<f32>();
+                (lag, corr)
+            })
+            .fold(
+                (0, f32::MIN),
+                |best, next| if next.1 > best.1 { next } else { best },
+            )
+            .0
+    }
+
+    /// Render the tail of notes skipping the releases of stopped ones
+    fn render(synth: &Synth) -> Vec<f32> {
+        let mut samples = vec![0f32; 4410 * 2];
+        synth.write(samples.as_mut_slice()).unwrap();
+        synth.write(samples.as_mut_slice()).unwrap();
+        samples
+    }
+
+    #[test]
+    fn retune_held_note() {
+        let synth = Synth::new(Settings::new().unwrap()).unwrap();
+        synth.sfload("sf_/Boomwhacker.sf2", true).unwrap();
+
+        // the wide major third makes the change audible
+        let mut offsets = [0.0; 12];
+        offsets[4] = 50.0;
+        let wide = Temperament {
+            name: "Wide thirds".into(),
+            offsets,
+        };
+        let mut tuner = DynamicTuner::new(&synth, 0, 0, 0)
+            .unwrap()
+            .with_drift(DriftPolicy::FixedRoot)
+            .with_intervals(&wide);
+
+        tuner.note_on(&synth, 60, 100).unwrap();
+        tuner.note_on(&synth, 64, 100).unwrap();
+        assert_eqf!(tuner.deviation(Key::new(64).unwrap()), 50.0);
+        render(&synth);
+
+        // the held note becomes the root in equal temperament
+        tuner.note_off(&synth, 60).unwrap();
+        assert_eqf!(tuner.deviation(Key::new(64).unwrap()), 0.0);
+        let retuned = period(&render(&synth));
+
+        let reference = Synth::new(Settings::new().unwrap()).unwrap();
+        reference.sfload("sf_/Boomwhacker.sf2", true).unwrap();
+        reference.note_on(0, 64, 100).unwrap();
+        let equal = period(&render(&reference));
+
+        // the retuned note matches equal temperament while the wide third is about 4 frames shorter
+        assert!((retuned as isize - equal as isize).abs() <= 1);
+    }
+
+    /// Render until the sounding voices are ended
+    fn render_silence(synth: &Synth) {
+        let mut samples = vec![0f32; 4410 * 2];
+        for _ in 0..100 {
+            synth.write(samples.as_mut_slice()).unwrap();
+            if samples.iter().all(|sample| *sample == 0.0) {
+                return;
+            }
+        }
+        panic!("Voices are not ended");
+    }
+
+    #[test]
+    fn retune_ended_note() {
+        let synth = Synth::new(Settings::new().unwrap()).unwrap();
+        synth.sfload("sf_/Boomwhacker.sf2", true).unwrap();
+        // decay to silence, so the voices are ended while the keys are held
+        synth.set_gen(0, GenParam::VolEnvDecay, -4000.0).unwrap();
+        synth.set_gen(0, GenParam::VolEnvSustain, 1440.0).unwrap();
+
+        let mut tuner = DynamicTuner::new(&synth, 0, 0, 0)
+            .unwrap()
+            .with_drift(DriftPolicy::FixedRoot);
+
+        tuner.note_on(&synth, 64, 100).unwrap();
+        render_silence(&synth);
+
+        // the ended held note is retuned and retriggered
+        tuner.note_on(&synth, 60, 100).unwrap();
+        assert_eqf!(tuner.deviation(Key::new(64).unwrap()), -13.686);
+        assert_eqf!(synth.tuning_dump_pitch(0, 0).unwrap()[64], 6400.0 - 13.686);
+        render_silence(&synth);
+
+        // the noteoff of ended voice fails but the remaining note is retuned
+        assert!(tuner.note_off(&synth, 60).is_err());
+        assert_eqf!(tuner.deviation(Key::new(64).unwrap()), 0.0);
+        assert_eqf!(synth.tuning_dump_pitch(0, 0).unwrap()[64], 6400.0);
+    }
+}
@@ -1,30 +1,51 @@
 use std::{
     error::Error as StdError,
+    ffi::CStr,
     fmt::{Display, Formatter, Result as FmtResult},
+    hash::{Hash, Hasher},
+    io::{Error as IoError, ErrorKind},
+    path::{Path, PathBuf},
     ptr::NonNull,
     result::Result as StdResult,
+    str::from_utf8,
 };
 
-/// Channel number
-pub type Chan = u32;
+/**
+The raw MIDI number types
 
-/// Key number
-pub type Key = u32;
+The MIDI values were plain `u32` numbers before the checked types
+like [`Chan`](crate::Chan) were introduced. The methods of synth still
+accept plain numbers, so these aliases are kept to ease migration.
+ */
+pub mod raw {
+    /// Channel number
+    #[deprecated(note = "Use checked `fluidlite::Chan` or `u32`")]
+    pub type Chan = u32;
 
-/// Velocity value
-pub type Vel = u32;
+    /// Key number
+    #[deprecated(note = "Use checked `fluidlite::Key` or `u32`")]
+    pub type Key = u32;
 
-/// Control number
-pub type Ctrl = u32;
+    /// Velocity value
+    #[deprecated(note = "Use checked `fluidlite::Vel` or `u32`")]
+    pub type Vel = u32;
 
-/// Control value
-pub type Val = u32;
+    /// Control number
+    #[deprecated(note = "Use checked `fluidlite::Ctrl` or `u32`")]
+    pub type Ctrl = u32;
 
-/// Program number (`0..=127`)
-pub type Prog = u32;
+    /// Control value
+    #[deprecated(note = "Use checked `fluidlite::Val` or `u32`")]
+    pub type Val = u32;
 
-/// Bank number (`0..=127`)
-pub type Bank = u32;
+    /// Program number (`0..=127`)
+    #[deprecated(note = "Use checked `fluidlite::Prog` or `u32`")]
+    pub type Prog = u32;
+
+    /// Bank number (`0..=127`)
+    #[deprecated(note = "Use checked `fluidlite::Bank` or `u32`")]
+    pub type Bank = u32;
+}
 
 /// Font Id
 pub type FontId = u32;
@@ -38,12 +59,117 @@ pub type Result<T> = StdResult<T, Error>;
 /// Result without value
 pub type Status = Result<()>;
 
+/**
+The error message reported by fluidlite
+
+The message is copied into a fixed-capacity inline buffer so creating
+this error never touches the heap. This makes it safe to handle failures
+in the audio thread. Longer messages are truncated.
+ */
+#[derive(Clone, Copy)]
+pub struct FluidError {
+    len: u8,
+    buf: [u8; FluidError::CAPACITY],
+}
+
+impl FluidError {
+    /// The maximum length of stored message in bytes
+    pub const CAPACITY: usize = 95;
+
+    pub(crate) fn from_cstr(message: &CStr) -> Self {
+        let message = message.to_bytes();
+        // truncate at the last valid UTF-8 boundary within the capacity
+        let len = message.len().min(Self::CAPACITY);
+        let len = match from_utf8(&message[..len]) {
+            Ok(_) => len,
+            Err(error) => error.valid_up_to(),
+        };
+
+        let mut buf = [0u8; Self::CAPACITY];
+        buf[..len].copy_from_slice(&message[..len]);
+
+        Self { len: len as _, buf }
+    }
+
+    /// Get the message text
+    pub fn as_str(&self) -> &str {
+        from_utf8(&self.buf[..self.len as usize]).unwrap_or_default()
+    }
+}
+
+impl AsRef<str> for FluidError {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for FluidError {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for FluidError {}
+
+impl Hash for FluidError {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl std::fmt::Debug for FluidError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl Display for FluidError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        self.as_str().fmt(f)
+    }
+}
+
 /// Common error type
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum Error {
+    /// Allocation error
     Alloc,
-    Fluid(String),
+    /// Generic error reported by fluidlite
+    Fluid(FluidError),
+    /// Invalid path
     Path,
+    /// Channel number is out of range
+    InvalidChannel { chan: u32, max: u32 },
+    /// Key number is out of range
+    InvalidKey { key: u32 },
+    /// Velocity value is out of range
+    InvalidVel { vel: u32 },
+    /// Control number is out of range
+    InvalidCtrl { ctrl: u32 },
+    /// Control value is out of range
+    InvalidValue { val: u32, max: u32 },
+    /// Program number is out of range
+    InvalidProg { prog: u32 },
+    /// Bank number is out of range
+    InvalidBank { bank: u32 },
+    /// No preset selected on channel
+    NoPreset { chan: u32 },
+    /// SoundFont loading failed with the error reported by fluidlite (if any)
+    FontLoad {
+        path: PathBuf,
+        error: Option<FluidError>,
+    },
+    /// Setting cannot be changed
+    Setting { name: String },
+    /// Tuning operation failed
+    Tuning { bank: u32, prog: u32 },
+    /// Invalid text at line
+    Parse { line: usize, reason: &'static str },
+    /// Invalid binary data or structure
+    Format { reason: &'static str },
+    /// I/O operation failed
+    Io { kind: ErrorKind, message: String },
 }
 
 impl StdError for Error {}
@@ -58,6 +184,58 @@ impl Display for Error {
                 error.fmt(f)
             }
             Path => "Invalid path".fmt(f),
+            InvalidChannel { chan, max } => {
+                write!(f, "Invalid channel {} (max {})", chan, max)
+            }
+            InvalidKey { key } => write!(f, "Invalid key {} (max 127)", key),
+            InvalidVel { vel } => write!(f, "Invalid velocity {} (max 127)", vel),
+            InvalidCtrl { ctrl } => write!(f, "Invalid control {} (max 127)", ctrl),
+            InvalidValue { val, max } => write!(f, "Invalid value {} (max {})", val, max),
+            InvalidProg { prog } => write!(f, "Invalid program {} (max 127)", prog),
+            InvalidBank { bank } => write!(f, "Invalid bank {} (max 16383)", bank),
+            NoPreset { chan } => write!(f, "No preset on channel {}", chan),
+            FontLoad { path, error: None } => {
+                write!(f, "Unable to load SoundFont {}", path.display())
+            }
+            FontLoad {
+                path,
+                error: Some(error),
+            } => write!(f, "Unable to load SoundFont {}: {}", path.display(), error),
+            Setting { name } => write!(f, "Unable to change setting {}", name),
+            Tuning { bank, prog } => write!(f, "Tuning {}:{} failed", bank, prog),
+            Parse { line, reason } => write!(f, "Parse error at line {}: {}", line, reason),
+            Format { reason } => write!(f, "Invalid data: {}", reason),
+            Io { message, .. } => write!(f, "I/O error: {}", message),
+        }
+    }
+}
+
+impl Error {
+    /// The I/O error of file with path in message
+    pub(crate) fn file(path: &Path, error: IoError) -> Self {
+        Error::Io {
+            kind: error.kind(),
+            message: format!("{}: {}", path.display(), error),
+        }
+    }
+
+    /// The loading error of font keeping the message of library
+    pub(crate) fn font_load(path: &Path, error: Error) -> Self {
+        Error::FontLoad {
+            path: path.into(),
+            error: match error {
+                Error::Fluid(error) if !error.as_str().is_empty() => Some(error),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl From<IoError> for Error {
+    fn from(error: IoError) -> Self {
+        Error::Io {
+            kind: error.kind(),
+            message: error.to_string(),
         }
     }
 }
@@ -71,3 +249,26 @@ pub(crate) fn result_from_ptr<T>(ptr: *mut T) -> Result<NonNull<T>> {
 pub(crate) fn option_from_ptr<T>(ptr: *mut T) -> Option<NonNull<T>> {
     NonNull::new(ptr)
 }
+
+#[cfg(test)]
+mod test {
+    use super::FluidError;
+    use std::ffi::CStr;
+
+    #[test]
+    fn fluid_error() {
+        let error = FluidError::from_cstr(CStr::from_bytes_with_nul(b"No preset found\0").unwrap());
+
+        assert_eq!(error.as_str(), "No preset found");
+        assert_eq!(error.to_string(), "No preset found");
+    }
+
+    #[test]
+    fn fluid_error_truncated() {
+        let mut message = "\u{444}".repeat(FluidError::CAPACITY).into_bytes();
+        message.push(0);
+        let error = FluidError::from_cstr(CStr::from_bytes_with_nul(&message).unwrap());
+
+        assert_eq!(error.as_str(), "\u{444}".repeat(FluidError::CAPACITY / 2));
+    }
+}
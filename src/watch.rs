@@ -0,0 +1,353 @@
+use crate::{Error, FontId, IsFont, Result, Synth};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Receiver},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// The callback which receives reloading errors
+type ErrorFn = Box<dyn FnMut(&Path, Error)>;
+
+/**
+The watcher which reloads SoundFonts of synth when its files are changed
+
+The fonts loaded via [`Synth::sfload`] are picked up on each [`FontWatcher::poll`],
+which should be called periodically from the thread which owns synth.
+The changed fonts are reloaded using [`Synth::sfreload`] when the file
+stays unchanged during the delay, so the ID, the position on the stack
+and the bank offset are kept.
+
+The changed file is read once and checked by loading it as a separate font,
+then the font is reloaded from the same data. So the font which fails
+to load is reported and the previous version stays in use.
+
+```no_run
+use fluidlite::{FontWatcher, Settings, Synth};
+use std::time::Duration;
+
+let synth = Synth::new(Settings::new().unwrap()).unwrap();
+synth.sfload("sf_/Boomwhacker.sf2", true).unwrap();
+
+let mut watcher = FontWatcher::new()
+    .unwrap()
+    .with_delay(Duration::from_millis(500))
+    .on_error(|path, error| eprintln!("{}: {}", path.display(), error));
+
+loop {
+    for id in watcher.poll(&synth) {
+        println!("Reloaded font {}", id);
+    }
+    // render audio here
+}
+```
+ */
+pub struct FontWatcher {
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    /// The watched fonts by ID
+    fonts: HashMap<FontId, WatchedFont>,
+    /// The watched directories with number of fonts in it
+    dirs: HashMap<PathBuf, usize>,
+    /// The time of last change of fonts
+    changes: HashMap<FontId, Instant>,
+    delay: Duration,
+    on_error: Option<ErrorFn>,
+}
+
+struct WatchedFont {
+    /// The name which font is loaded with
+    name: PathBuf,
+    /// The canonical path of file
+    path: Option<PathBuf>,
+}
+
+impl FontWatcher {
+    /// The default delay of reloading
+    pub const DEFAULT_DELAY: Duration = Duration::from_millis(300);
+
+    /// Create the watcher
+    pub fn new() -> Result<Self> {
+        let (sender, events) = channel();
+        let watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send(event);
+        })
+        .map_err(watch_error)?;
+
+        Ok(Self {
+            watcher,
+            events,
+            fonts: HashMap::new(),
+            dirs: HashMap::new(),
+            changes: HashMap::new(),
+            delay: Self::DEFAULT_DELAY,
+            on_error: None,
+        })
+    }
+
+    /// Set the time which file should stay unchanged before reloading
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Set the callback which receives reloading and watching errors
+    pub fn on_error<F: FnMut(&Path, Error) + 'static>(mut self, on_error: F) -> Self {
+        self.on_error = Some(Box::new(on_error));
+        self
+    }
+
+    /**
+    Watch the loaded fonts and reload the changed ones
+
+    Returns the IDs of reloaded fonts.
+     */
+    pub fn poll(&mut self, synth: &Synth) -> Vec<FontId> {
+        self.update_fonts(synth);
+        self.handle_events();
+
+        let now = Instant::now();
+        let ready = self
+            .changes
+            .iter()
+            .filter(|(_, time)| now.duration_since(**time) >= self.delay)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+
+        let mut reloaded = Vec::new();
+        for id in ready {
+            self.changes.remove(&id);
+            let name = match self.fonts.get(&id) {
+                Some(font) => font.name.clone(),
+                None => continue,
+            };
+            match reload(synth, id) {
+                Ok(()) => reloaded.push(id),
+                Err(error) => self.report(&name, error),
+            }
+        }
+        reloaded
+    }
+
+    /// Check that the font is watched
+    pub fn is_watched(&self, id: FontId) -> bool {
+        self.fonts
+            .get(&id)
+            .and_then(|font| font.path.as_ref())
+            .is_some()
+    }
+
+    /// Watch new fonts and forget unloaded ones
+    fn update_fonts(&mut self, synth: &Synth) {
+        let ids = synth
+            .sfont_iter()
+            .map(|font| font.get_id())
+            .collect::<HashSet<_>>();
+
+        let unloaded = self
+            .fonts
+            .keys()
+            .filter(|id| !ids.contains(id))
+            .copied()
+            .collect::<Vec<_>>();
+        for id in unloaded {
+            if let Some(path) = self.fonts.remove(&id).and_then(|font| font.path) {
+                self.unwatch_dir(&path);
+            }
+            self.changes.remove(&id);
+        }
+
+        for id in ids {
+            if self.fonts.contains_key(&id) {
+                continue;
+            }
+            let name = match synth.get_sfont_by_id(id).and_then(|font| font.get_name()) {
+                Some(name) => PathBuf::from(name),
+                None => continue,
+            };
+            // the fonts loaded from memory haven't files
+            let path = name.canonicalize().ok().filter(|path| path.is_file());
+            if let Some(path) = &path {
+                if let Err(error) = self.watch_dir(path) {
+                    self.report(&name, error);
+                }
+            }
+            self.fonts.insert(id, WatchedFont { name, path });
+        }
+    }
+
+    /// Watch the directory of file, so the files replaced by editors are followed
+    fn watch_dir(&mut self, path: &Path) -> Result<()> {
+        let dir = path.parent().ok_or(Error::Path)?;
+        if let Some(count) = self.dirs.get_mut(dir) {
+            *count += 1;
+            return Ok(());
+        }
+        self.watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(watch_error)?;
+        self.dirs.insert(dir.into(), 1);
+        Ok(())
+    }
+
+    fn unwatch_dir(&mut self, path: &Path) {
+        let dir = match path.parent() {
+            Some(dir) => dir,
+            None => return,
+        };
+        if let Some(count) = self.dirs.get_mut(dir) {
+            *count -= 1;
+            if *count == 0 {
+                self.dirs.remove(dir);
+                let _ = self.watcher.unwatch(dir);
+            }
+        }
+    }
+
+    /// Remember the time of changes of watched files
+    fn handle_events(&mut self) {
+        while let Ok(event) = self.events.try_recv() {
+            let event = match event {
+                Ok(event) => event,
+                Err(error) => {
+                    let path = error.paths.first().cloned().unwrap_or_default();
+                    self.report(&path, watch_error(error));
+                    continue;
+                }
+            };
+            if matches!(event.kind, EventKind::Access(_)) {
+                continue;
+            }
+
+            let now = Instant::now();
+            for (id, font) in &self.fonts {
+                if font.path.iter().any(|path| event.paths.contains(path)) {
+                    self.changes.insert(*id, now);
+                }
+            }
+        }
+    }
+
+    fn report(&mut self, path: &Path, error: Error) {
+        if let Some(on_error) = &mut self.on_error {
+            on_error(path, error);
+        }
+    }
+}
+
+/**
+Reload font keeping the previous version when the file fails to load
+
+The data is loaded as a new font first, so the reloading from
+the same data doesn't fail when the file is changed again.
+ */
+fn reload(synth: &Synth, id: FontId) -> Result<()> {
+    let name = synth
+        .get_sfont_by_id(id)
+        .and_then(|font| font.get_name())
+        .ok_or(Error::Path)?;
+    let offset = synth.get_bank_offset(id).unwrap_or(0);
+    let data: Arc<[u8]> = fs::read(&name)
+        .map_err(|error| Error::file(Path::new(&name), error))?
+        .into();
+
+    let check_name = format!("fluidlite-watch-check-{}.sf2", id);
+    let check_id = synth.sfload_bytes(check_name, data.clone(), false)?;
+    synth.sfunload(check_id, false)?;
+
+    synth.sfreload_data(id, data)?;
+    synth.set_bank_offset(id, offset)
+}
+
+fn watch_error(error: notify::Error) -> Error {
+    match error.kind {
+        notify::ErrorKind::Io(error) => error.into(),
+        _ => Error::Io {
+            kind: ErrorKind::Other,
+            message: error.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FontWatcher, IsFont, Settings, Synth};
+    use std::{
+        cell::RefCell,
+        fs,
+        rc::Rc,
+        thread::sleep,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn reload_changed_font() {
+        let dir = std::env::temp_dir().join(format!("fluidlite-watch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Boomwhacker.sf2");
+        fs::copy("sf_/Boomwhacker.sf2", &path).unwrap();
+
+        let synth = Synth::new(Settings::new().unwrap()).unwrap();
+        synth.sfload("sf_/Boomwhacker.sf3", true).unwrap();
+        let id = synth.sfload(&path, true).unwrap();
+        synth.set_bank_offset(id, 2).unwrap();
+
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let mut watcher = FontWatcher::new()
+            .unwrap()
+            .with_delay(Duration::from_millis(50))
+            .on_error({
+                let errors = errors.clone();
+                move |path, error| errors.borrow_mut().push((path.to_owned(), error))
+            });
+
+        let poll_until = |watcher: &mut FontWatcher, done: &dyn Fn(&[u32]) -> bool| {
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_secs(5) {
+                let reloaded = watcher.poll(&synth);
+                if done(&reloaded) {
+                    return reloaded;
+                }
+                sleep(Duration::from_millis(10));
+            }
+            Vec::new()
+        };
+
+        assert!(watcher.poll(&synth).is_empty());
+        assert!(watcher.is_watched(id));
+
+        fs::copy("sf_/Boomwhacker.sf2", &path).unwrap();
+        assert_eq!(poll_until(&mut watcher, &|ids| !ids.is_empty()), [id]);
+        assert_eq!(synth.sfcount(), 2);
+        assert_eq!(synth.get_sfont(0).unwrap().get_id(), id);
+        assert_eq!(synth.get_bank_offset(id).unwrap(), 2);
+
+        // the broken font is reported and the previous version is kept in place
+        fs::write(&path, b"RIFF").unwrap();
+        poll_until(&mut watcher, &|_| !errors.borrow().is_empty());
+        assert_eq!(errors.borrow()[0].0, path);
+        assert_eq!(synth.sfcount(), 2);
+        assert_eq!(synth.get_sfont(0).unwrap().get_id(), id);
+        assert_eq!(synth.get_bank_offset(id).unwrap(), 2);
+        assert!(watcher.is_watched(id));
+
+        // the removed file is reported too
+        fs::remove_file(&path).unwrap();
+        poll_until(&mut watcher, &|_| errors.borrow().len() > 1);
+        assert_eq!(errors.borrow()[1].0, path);
+        assert_eq!(synth.sfcount(), 2);
+        assert!(synth.get_sfont_by_id(id).unwrap().is_loaded());
+
+        synth.sfunload(id, true).unwrap();
+        watcher.poll(&synth);
+        assert!(!watcher.is_watched(id));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
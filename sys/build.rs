@@ -316,6 +316,8 @@ fn build_library(src_dir: &Path, lib_dir: &Path) {
             build.define("SF3_SUPPORT", "SF3_STB_VORBIS");
             build.include(src_dir.join("stb"));
             build.file(src_dir.join("stb").join("stb_vorbis.c"));
+            // the decoder is exported for SF3 sample export
+            println!("cargo:rustc-cfg=fluidlite_stb_vorbis");
         }
 
         #[cfg(not(feature = "with-stb"))]
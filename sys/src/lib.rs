@@ -11,6 +11,69 @@ include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 #[cfg(not(feature = "bindgen"))]
 include!(concat!("bindings/", env!("FLUIDLITE_BINDINGS")));
 
+/// The bundled _stb_vorbis_ decoder is built (with `with-sf3` and `with-stb` features)
+pub const HAS_STB_VORBIS: bool = cfg!(fluidlite_stb_vorbis);
+
+#[cfg(fluidlite_stb_vorbis)]
+extern "C" {
+    fn stb_vorbis_decode_memory(
+        mem: *const std::os::raw::c_uchar,
+        len: std::os::raw::c_int,
+        channels: *mut std::os::raw::c_int,
+        sample_rate: *mut std::os::raw::c_int,
+        output: *mut *mut std::os::raw::c_short,
+    ) -> std::os::raw::c_int;
+    fn free(ptr: *mut std::os::raw::c_void);
+}
+
+/**
+Decode the whole Ogg Vorbis stream using the bundled _stb_vorbis_ decoder
+
+Returns the interleaved samples with the number of channels.
+Returns `None` when the data is invalid or the decoder isn't built
+(see [`HAS_STB_VORBIS`]).
+ */
+pub fn stb_vorbis_decode(data: &[u8]) -> Option<(Vec<i16>, usize)> {
+    #[cfg(fluidlite_stb_vorbis)]
+    {
+        use std::convert::TryFrom;
+
+        let len = std::os::raw::c_int::try_from(data.len()).ok()?;
+        let mut channels = 0;
+        let mut sample_rate = 0;
+        let mut output = std::ptr::null_mut();
+        let frames = unsafe {
+            stb_vorbis_decode_memory(
+                data.as_ptr(),
+                len,
+                &mut channels,
+                &mut sample_rate,
+                &mut output,
+            )
+        };
+        if output.is_null() {
+            return None;
+        }
+        let points = if frames >= 0 && channels > 0 {
+            let len = frames as usize * channels as usize;
+            Some((
+                unsafe { std::slice::from_raw_parts(output, len) }.to_vec(),
+                channels as usize,
+            ))
+        } else {
+            None
+        };
+        unsafe { free(output as _) };
+        points
+    }
+
+    #[cfg(not(fluidlite_stb_vorbis))]
+    {
+        let _ = data;
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;